@@ -1,6 +1,7 @@
 //! CLI definition using clap (Pebble Spec v1.0)
 
 use clap::{Parser, Subcommand, Args};
+use std::net::{IpAddr, SocketAddr};
 
 #[derive(Parser)]
 #[command(name = "cf")]
@@ -10,11 +11,17 @@ use clap::{Parser, Subcommand, Args};
 CF CLI - Cloudflare 基础设施管理工具
 
 功能说明:
-  dns      域名解析管理 - 添加、查看、删除域名记录
-  r2       文件存储 - 上传图片、文件到云端，获取公开链接
-  caddy    反向代理 - 配置网站域名指向服务
-  service  服务检查 - 检测服务是否正常运行
-  registry 配置管理 - 管理本地域名配置文件
+  dns         域名解析管理 - 添加、查看、删除域名记录
+  zone        域名安全设置 - SSL 模式、强制 HTTPS、最低 TLS 版本、HSTS
+  cache       缓存清理 - 发布静态文件后清除 Cloudflare 缓存
+  firewall    防火墙 - 封禁 IP、ASN、国家，管理 WAF 自定义规则
+  redirect    跳转规则 - 域名迁移、短链接重定向
+  tunnel      内网穿透 - 通过 Cloudflare Tunnel 暴露没有公网 IP 的服务
+  origin-cert 源站证书 - 签发 Origin CA 证书并安装到 Caddy 服务器
+  r2          文件存储 - 上传图片、文件到云端，获取公开链接
+  caddy       反向代理 - 配置网站域名指向服务
+  service     服务检查 - 检测服务是否正常运行
+  registry    配置管理 - 管理本地域名配置文件
 
 使用前准备:
   需要在 .env 文件中配置 Cloudflare API 密钥
@@ -60,6 +67,24 @@ pub enum Commands {
     /// 域名解析管理 - 添加/查看/删除 DNS 记录
     Dns(DnsCommand),
 
+    /// 域名安全设置 - SSL/HTTPS/TLS/HSTS 等 zone 设置
+    Zone(ZoneCommand),
+
+    /// 缓存清理 - 按链接、前缀、主机名或标签清除 Cloudflare 缓存
+    Cache(CacheCommand),
+
+    /// 防火墙 - 封禁 IP/ASN/国家，管理 WAF 自定义规则，支持到期自动清理
+    Firewall(FirewallCommand),
+
+    /// 跳转规则 - 管理域名迁移与短链接重定向 (Single Redirects)
+    Redirect(RedirectCommand),
+
+    /// 内网穿透 - 管理 Cloudflare Tunnel 及其域名路由
+    Tunnel(TunnelCommand),
+
+    /// 源站证书 - 签发 Cloudflare Origin CA 证书并安装到 Caddy 服务器
+    OriginCert(OriginCertCommand),
+
     /// 反向代理配置 - 管理 Caddy 服务器
     Caddy(CaddyCommand),
 
@@ -83,19 +108,60 @@ pub struct DnsCommand {
 
 #[derive(Subcommand)]
 pub enum DnsAction {
-    /// List all DNS records for a domain
+    /// List DNS records for a domain (all pages, optionally filtered)
     #[command(after_help = "Examples:
   cf dns list --domain example.com
-  cf dns list --domain yourdomain.com")]
+  cf dns list --domain yourdomain.com
+  cf dns list --domain example.com --type CNAME --proxied true
+  cf dns list --domain example.com --name-contains api --sort name --direction desc --limit 20
+  cf dns list --domain example.com --tag owner:platform")]
     List {
         /// Domain name (e.g., example.com, yourdomain.com)
         #[arg(short, long)]
         domain: String,
+
+        /// Only records of this type
+        #[arg(short = 't', long = "type", ignore_case = true,
+              value_parser = ["A", "AAAA", "CNAME", "TXT", "MX", "SRV", "CAA", "NS", "PTR", "HTTPS", "SVCB"])]
+        record_type: Option<String>,
+
+        /// Only records whose name contains this text
+        #[arg(long)]
+        name_contains: Option<String>,
+
+        /// Only records with exactly this content
+        #[arg(long)]
+        content: Option<String>,
+
+        /// Only proxied (true) or DNS-only (false) records
+        #[arg(long)]
+        proxied: Option<bool>,
+
+        /// Only records whose comment contains this text
+        #[arg(long)]
+        comment: Option<String>,
+
+        /// Only records with this tag: "key:value", or "key" for any value
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// Sort field
+        #[arg(long, value_parser = ["type", "name", "content", "ttl", "proxied"])]
+        sort: Option<String>,
+
+        /// Sort direction
+        #[arg(long, default_value = "asc", value_parser = ["asc", "desc"])]
+        direction: String,
+
+        /// Stop after this many records
+        #[arg(short, long)]
+        limit: Option<usize>,
     },
 
-    /// Get a specific DNS record
+    /// Get the DNS records at a name
     #[command(after_help = "Examples:
-  cf dns get --domain example.com --name myapp")]
+  cf dns get --domain example.com --name myapp
+  cf dns get --domain example.com --name @ --type TXT")]
     Get {
         /// Domain name
         #[arg(short, long)]
@@ -104,11 +170,24 @@ pub enum DnsAction {
         /// Subdomain name (without domain suffix)
         #[arg(short, long)]
         name: String,
+
+        /// Only records of this type
+        #[arg(short = 't', long = "type", ignore_case = true,
+              value_parser = ["A", "AAAA", "CNAME", "TXT", "MX", "SRV", "CAA", "NS", "PTR", "HTTPS", "SVCB"])]
+        record_type: Option<String>,
     },
 
-    /// Create a new DNS A record
+    /// Create a new DNS record
     #[command(after_help = "Examples:
-  cf dns create --domain example.com --name myapp --ip 1.2.3.4 --desc 'My App'")]
+  cf dns create --domain example.com --name myapp --ip 1.2.3.4 --desc 'My App'
+  cf dns create --domain example.com --name myapp --ip 1.2.3.4 --wait --wait-timeout 300
+  cf dns create --domain example.com --name myapp --ip 1.2.3.4 --ttl 300 --tag owner:platform
+  cf dns create --domain example.com --name myapp --type AAAA --content 2001:db8::1
+  cf dns create --domain example.com --name www --type CNAME --content example.pages.dev --proxied
+  cf dns create --domain example.com --name @ --type TXT --content 'v=spf1 -all'
+  cf dns create --domain example.com --name @ --type MX --content mail.example.com --priority 10
+  cf dns create --domain example.com --name _sip._tcp --type SRV --content sip.example.com --priority 10 --weight 5 --port 5060
+  cf dns create --domain example.com --name @ --type CAA --content '0 issue \"letsencrypt.org\"'")]
     Create {
         /// Domain name
         #[arg(short, long)]
@@ -118,22 +197,127 @@ pub enum DnsAction {
         #[arg(short, long)]
         name: String,
 
-        /// IP address
+        /// Record type
+        #[arg(short = 't', long = "type", default_value = "A", ignore_case = true,
+              value_parser = ["A", "AAAA", "CNAME", "TXT", "MX", "SRV", "CAA"])]
+        record_type: String,
+
+        /// Record content: IP address (A/AAAA), hostname (CNAME/MX/SRV target),
+        /// text (TXT) or "flags tag value" (CAA)
+        #[arg(short, long, visible_alias = "ip", short_alias = 'i')]
+        content: String,
+
+        /// Priority (MX and SRV)
+        #[arg(long)]
+        priority: Option<u16>,
+
+        /// Weight (SRV)
+        #[arg(long)]
+        weight: Option<u16>,
+
+        /// Port (SRV)
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// Description, stored as the record comment
+        #[arg(long, visible_alias = "comment")]
+        desc: Option<String>,
+
+        /// TTL in seconds (1 = auto)
+        #[arg(long, value_parser = parse_ttl)]
+        ttl: Option<u32>,
+
+        /// Tag as key:value (repeatable)
+        #[arg(long = "tag", value_parser = parse_tag)]
+        tags: Vec<String>,
+
+        /// Enable Cloudflare proxy (orange cloud, A/AAAA/CNAME only); `--proxied false` turns it off on upsert
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        proxied: Option<bool>,
+
+        /// Update the record in place if one with the same name and type exists
+        #[arg(long)]
+        upsert: bool,
+
+        #[command(flatten)]
+        wait: WaitArgs,
+    },
+
+    /// Update an existing DNS record in place
+    #[command(after_help = "Examples:
+  cf dns update --domain example.com --name myapp --ip 5.6.7.8
+  cf dns update --domain example.com --name myapp --ip 5.6.7.8 --wait
+  cf dns update --domain example.com --name myapp --proxied true --ttl 300
+  cf dns update --domain example.com --name @ --type MX --content mx2.example.com --priority 20
+  cf dns update --domain example.com --name _sip._tcp --type SRV --weight 10 --port 5061
+  cf dns update --domain example.com --name lb --id 372e67954025e0ba6aaa6d586b9e0b59 --ip 5.6.7.8
+  cf dns update --domain example.com --name myapp --comment 'Moved to new host'
+  cf dns update --domain example.com --name myapp --tag owner:platform --untag temp")]
+    Update {
+        /// Domain name
         #[arg(short, long)]
-        ip: String,
+        domain: String,
 
-        /// Description
-        #[arg(long, default_value = "")]
-        desc: String,
+        /// Subdomain name
+        #[arg(short, long)]
+        name: String,
 
-        /// Enable Cloudflare proxy (orange cloud)
-        #[arg(long, default_value = "false")]
-        proxied: bool,
+        /// Only match records of this type
+        #[arg(short = 't', long = "type", ignore_case = true,
+              value_parser = ["A", "AAAA", "CNAME", "TXT", "MX", "SRV", "CAA"])]
+        record_type: Option<String>,
+
+        /// Update the record with this ID (when several share the name)
+        #[arg(long)]
+        id: Option<String>,
+
+        /// New record content
+        #[arg(short, long, visible_alias = "ip", short_alias = 'i')]
+        content: Option<String>,
+
+        /// New priority (MX and SRV)
+        #[arg(long)]
+        priority: Option<u16>,
+
+        /// New weight (SRV)
+        #[arg(long)]
+        weight: Option<u16>,
+
+        /// New port (SRV)
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// Enable or disable Cloudflare proxy
+        #[arg(long)]
+        proxied: Option<bool>,
+
+        /// New TTL in seconds (1 = auto)
+        #[arg(long, value_parser = parse_ttl)]
+        ttl: Option<u32>,
+
+        /// New comment
+        #[arg(long)]
+        comment: Option<String>,
+
+        /// Add a tag as key:value, replacing any tag with the same key (repeatable)
+        #[arg(long = "tag", value_parser = parse_tag)]
+        tags: Vec<String>,
+
+        /// Remove the tag with this key (repeatable)
+        #[arg(long)]
+        untag: Vec<String>,
+
+        #[command(flatten)]
+        wait: WaitArgs,
     },
 
     /// Delete a DNS record
     #[command(after_help = "Examples:
-  cf dns delete --domain example.com --name myapp")]
+  cf dns delete --domain example.com --name myapp
+  cf dns delete --domain example.com --name @ --type TXT
+  cf dns delete --domain example.com --name lb --content 1.2.3.4
+  cf dns delete --domain example.com --name lb --id 372e67954025e0ba6aaa6d586b9e0b59
+  cf dns delete --domain example.com --name myapp --wait")]
     Delete {
         /// Domain name
         #[arg(short, long)]
@@ -142,6 +326,687 @@ pub enum DnsAction {
         /// Subdomain name
         #[arg(short, long)]
         name: String,
+
+        /// Only match records of this type
+        #[arg(short = 't', long = "type", ignore_case = true,
+              value_parser = ["A", "AAAA", "CNAME", "TXT", "MX", "SRV", "CAA", "NS", "PTR", "HTTPS", "SVCB"])]
+        record_type: Option<String>,
+
+        /// Only match the record with this content
+        #[arg(short, long, visible_alias = "ip", short_alias = 'i')]
+        content: Option<String>,
+
+        /// Delete the record with this ID
+        #[arg(long)]
+        id: Option<String>,
+
+        #[command(flatten)]
+        wait: WaitArgs,
+    },
+
+    /// Check that resolvers serve a record, polling until they do
+    #[command(after_help = "Examples:
+  cf dns verify --domain example.com --name myapp
+  cf dns verify --domain example.com --name myapp --content 1.2.3.4 --timeout 300
+  cf dns verify --domain example.com --name @ --type TXT --resolvers 1.1.1.1,9.9.9.9
+  cf dns verify --domain example.com --name old --absent")]
+    Verify {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// Subdomain name
+        #[arg(short, long)]
+        name: String,
+
+        /// Record type
+        #[arg(short = 't', long = "type", default_value = "A", ignore_case = true,
+              value_parser = ["A", "AAAA", "CNAME", "TXT", "MX", "SRV", "CAA"])]
+        record_type: String,
+
+        /// Expected content (default: the record's current content in Cloudflare)
+        #[arg(short, long, visible_alias = "ip", short_alias = 'i')]
+        content: Option<String>,
+
+        /// Wait until the record (or --content) is no longer served
+        #[arg(long)]
+        absent: bool,
+
+        /// Give up after this many seconds
+        #[arg(long, default_value = "120")]
+        timeout: u64,
+
+        /// Seconds between polls
+        #[arg(long, default_value = "5")]
+        interval: u64,
+
+        /// Resolvers to poll, as IP or IP:port (default: the zone's nameservers, 1.1.1.1 and 8.8.8.8)
+        #[arg(long, value_delimiter = ',', value_parser = parse_resolver)]
+        resolvers: Vec<SocketAddr>,
+    },
+
+    /// Run create/update/delete operations from a JSON or CSV file
+    #[command(after_help = "Examples:
+  cf dns batch --domain example.com --file ops.json
+  cf dns batch --domain example.com --file ops.csv --atomic --concurrency 8
+
+JSON: [{\"op\": \"create\", \"name\": \"api\", \"type\": \"A\", \"content\": \"1.2.3.4\"},
+       {\"op\": \"delete\", \"name\": \"old\", \"type\": \"CNAME\"}]
+CSV:  op,name,type,content,priority,proxied,ttl,comment (header row required)")]
+    Batch {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// Operations file (.json or .csv)
+        #[arg(short, long)]
+        file: String,
+
+        /// Roll back completed operations if any operation fails
+        #[arg(long)]
+        atomic: bool,
+
+        /// Operations to run at once
+        #[arg(long, default_value = "4")]
+        concurrency: usize,
+    },
+
+    /// Point a record at this host's public IP, updating it only when the IP changes
+    #[command(after_help = "Examples:
+  cf dns ddns --domain example.com --name home
+  cf dns ddns --domain example.com --name home --family both
+  cf dns ddns --domain example.com --name home --interface eth0
+  cf dns ddns --domain example.com --name home --echo-url https://api.ipify.org
+  cf dns ddns --domain example.com --name home --record-id 372e67954025e0ba6aaa6d586b9e0b59
+  cf dns ddns --domain example.com --name home --daemon --interval 300")]
+    Ddns {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// Subdomain name
+        #[arg(short, long)]
+        name: String,
+
+        /// Address family: A record (ipv4), AAAA record (ipv6) or both
+        #[arg(long, default_value = "ipv4", value_parser = ["ipv4", "ipv6", "both"])]
+        family: String,
+
+        /// Endpoint that echoes the caller's IP (plain text or Cloudflare trace format)
+        #[arg(long, default_value = "https://cloudflare.com/cdn-cgi/trace")]
+        echo_url: String,
+
+        /// Read the address from this local interface instead of an echo endpoint
+        #[arg(long, conflicts_with = "echo_url")]
+        interface: Option<String>,
+
+        /// Keep the record with this ID updated (when several share the name; one family only)
+        #[arg(long)]
+        record_id: Option<String>,
+
+        /// Proxy the record through Cloudflare (also applied to an existing record)
+        #[arg(long)]
+        proxied: bool,
+
+        /// Keep running and re-check every --interval seconds
+        #[arg(long)]
+        daemon: bool,
+
+        /// Seconds between checks in daemon mode
+        #[arg(long, default_value = "300")]
+        interval: u64,
+    },
+
+    /// Show the changes needed to make Cloudflare match registry.json
+    #[command(after_help = "Examples:
+  cf dns plan --domain example.com")]
+    Plan {
+        /// Domain name (must exist in registry.json)
+        #[arg(short, long)]
+        domain: String,
+    },
+
+    /// Push registry.json records to Cloudflare
+    #[command(after_help = "Examples:
+  cf dns apply --domain example.com
+  cf dns apply --domain example.com --prune")]
+    Apply {
+        /// Domain name (must exist in registry.json)
+        #[arg(short, long)]
+        domain: String,
+
+        /// Also delete records that are not in registry.json
+        #[arg(long)]
+        prune: bool,
+    },
+
+    /// Export a zone as a BIND zone file, JSON or CSV
+    #[command(after_help = "Examples:
+  cf dns export --domain example.com --out example.com.zone
+  cf dns export --domain example.com --format csv --out records.csv
+  cf dns export --domain example.com --format json")]
+    Export {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// Output format
+        #[arg(short, long, default_value = "bind", value_parser = ["bind", "json", "csv"])]
+        format: String,
+
+        /// Write to this file instead of including the content in the result
+        #[arg(short, long = "out")]
+        output: Option<String>,
+    },
+
+    /// Import records from an RFC 1035 (BIND) zone file
+    #[command(after_help = "Examples:
+  cf dns import --file example.com.zone --dry-run
+  cf dns import --file zone.txt --domain example.com
+  cf dns import --file zone.txt --prune")]
+    Import {
+        /// Zone file path
+        #[arg(short, long)]
+        file: String,
+
+        /// Domain name (defaults to the file's $ORIGIN)
+        #[arg(short, long)]
+        domain: Option<String>,
+
+        /// Only show the changes the import would make
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Also delete records that are not in the zone file
+        #[arg(long)]
+        prune: bool,
+    },
+}
+
+/// Propagation wait options for commands that change records
+#[derive(Args, Debug, Clone)]
+pub struct WaitArgs {
+    /// Wait until resolvers serve the change
+    #[arg(long)]
+    pub wait: bool,
+
+    /// Give up waiting after this many seconds
+    #[arg(long, default_value = "120")]
+    pub wait_timeout: u64,
+
+    /// Resolvers to poll, as IP or IP:port (default: the zone's nameservers, 1.1.1.1 and 8.8.8.8)
+    #[arg(long, value_delimiter = ',', value_parser = parse_resolver)]
+    pub resolvers: Vec<SocketAddr>,
+}
+
+/// Parse a TTL: 1 (auto) or 30-86400 seconds
+fn parse_ttl(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(ttl) if ttl == 1 || (30..=86400).contains(&ttl) => Ok(ttl),
+        _ => Err(format!("TTL must be 1 (auto) or 30-86400 seconds, got: {}", s)),
+    }
+}
+
+/// Parse a record tag in Cloudflare's key:value form
+fn parse_tag(s: &str) -> Result<String, String> {
+    match s.split_once(':') {
+        Some((key, _)) if !key.trim().is_empty() && !key.contains(char::is_whitespace) => Ok(s.to_string()),
+        _ => Err(format!("tag must be key:value, got: {}", s)),
+    }
+}
+
+/// Parse a resolver address, defaulting to port 53
+fn parse_resolver(s: &str) -> Result<SocketAddr, String> {
+    s.parse::<SocketAddr>()
+        .or_else(|_| s.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("invalid resolver address: {}", s))
+}
+
+// ============ Zone Commands ============
+
+#[derive(Args)]
+pub struct ZoneCommand {
+    #[command(subcommand)]
+    pub action: ZoneAction,
+}
+
+#[derive(Subcommand)]
+pub enum ZoneAction {
+    /// Read and enforce zone settings (SSL mode, Always Use HTTPS, min TLS, HSTS, ...)
+    Settings {
+        #[command(subcommand)]
+        action: ZoneSettingsAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ZoneSettingsAction {
+    /// Show zone settings
+    #[command(after_help = "Examples:
+  cf zone settings get --domain example.com
+  cf zone settings get --domain example.com --setting ssl --setting min_tls_version")]
+    Get {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// Only these settings (repeatable; Cloudflare setting IDs)
+        #[arg(short, long = "setting")]
+        settings: Vec<String>,
+    },
+
+    /// Change zone settings; only settings that differ are updated
+    #[command(after_help = "Examples:
+  cf zone settings set --domain example.com --ssl strict --always-https on --min-tls 1.2
+  cf zone settings set --domain example.com --hsts-max-age 31536000 --hsts-subdomains
+  cf zone settings set --domain example.com browser_cache_ttl=14400 --dry-run")]
+    Set {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// SSL/TLS encryption mode
+        #[arg(long, value_parser = ["off", "flexible", "full", "strict"])]
+        ssl: Option<String>,
+
+        /// Always Use HTTPS
+        #[arg(long, value_parser = ["on", "off"])]
+        always_https: Option<String>,
+
+        /// Minimum TLS version
+        #[arg(long, value_parser = ["1.0", "1.1", "1.2", "1.3"])]
+        min_tls: Option<String>,
+
+        /// HSTS max-age in seconds (0 disables HSTS)
+        #[arg(long)]
+        hsts_max_age: Option<u64>,
+
+        /// Apply HSTS to subdomains
+        #[arg(long, requires = "hsts_max_age")]
+        hsts_subdomains: bool,
+
+        /// Mark HSTS as preloadable
+        #[arg(long, requires = "hsts_max_age")]
+        hsts_preload: bool,
+
+        /// Any other setting as SETTING=VALUE (VALUE may be JSON)
+        #[arg(value_name = "SETTING=VALUE")]
+        values: Vec<String>,
+
+        /// Only report what would change
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Bring zone settings in line with a JSON profile and report drift
+    #[command(after_help = "Examples:
+  cf zone settings apply --domain example.com --profile baseline.json --dry-run
+  cf zone settings apply --domain example.com --profile baseline.json
+
+Profile format (Cloudflare setting IDs; objects are compared and merged by key):
+  {
+    \"ssl\": \"strict\",
+    \"always_use_https\": \"on\",
+    \"min_tls_version\": \"1.2\",
+    \"security_header\": {\"strict_transport_security\": {\"enabled\": true, \"max_age\": 31536000}}
+  }")]
+    Apply {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// JSON file mapping setting IDs to desired values
+        #[arg(short, long)]
+        profile: String,
+
+        /// Only report drift, change nothing
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+// ============ Cache Commands ============
+
+#[derive(Args)]
+pub struct CacheCommand {
+    #[command(subcommand)]
+    pub action: CacheAction,
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+    /// Purge cached content from Cloudflare's edge
+    #[command(after_help = "Examples:
+  cf cache purge --domain example.com --urls https://example.com/app.js,https://example.com/app.css
+  cf cache purge --domain example.com --prefixes example.com/assets/
+  cf cache purge --domain example.com --hosts static.example.com
+  cf cache purge --domain example.com --tags release-42
+  cf cache purge --domain example.com --everything --yes")]
+    #[command(group(clap::ArgGroup::new("targets").required(true).multiple(true)
+        .args(["urls", "prefixes", "hosts", "tags", "everything"])))]
+    Purge {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// Full URLs to purge (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        urls: Vec<String>,
+
+        /// URL prefixes without scheme, e.g. example.com/assets/ (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        prefixes: Vec<String>,
+
+        /// Hostnames whose cache to purge (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        hosts: Vec<String>,
+
+        /// Cache-Tag values to purge (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        tags: Vec<String>,
+
+        /// Purge everything cached for the zone (requires --yes)
+        #[arg(long, conflicts_with_all = ["urls", "prefixes", "hosts", "tags"])]
+        everything: bool,
+
+        /// Confirm --everything
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+// ============ Firewall Commands ============
+
+#[derive(Args)]
+pub struct FirewallCommand {
+    #[command(subcommand)]
+    pub action: FirewallAction,
+}
+
+#[derive(Subcommand)]
+pub enum FirewallAction {
+    /// List IP access rules and custom WAF rules, with expiries from registry.json
+    #[command(after_help = "Examples:
+  cf firewall list --domain example.com")]
+    List {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+    },
+
+    /// Add an IP access rule (--ip, --asn, --country) or a custom WAF rule (--expression)
+    #[command(after_help = "Examples:
+  cf firewall add --domain example.com --ip 198.51.100.7 --note \"scraper\" --expires 24h
+  cf firewall add --domain example.com --ip 198.51.100.0/24
+  cf firewall add --domain example.com --asn AS64496 --action managed_challenge --expires 2h
+  cf firewall add --domain example.com --country XX --action challenge
+  cf firewall add --domain example.com --expression '(http.request.uri.path contains \"/wp-login\")' --note \"no wordpress\"
+
+Actions: block, challenge, js_challenge, managed_challenge; allow (access rules only); log (WAF only)")]
+    #[command(group(clap::ArgGroup::new("target").required(true)
+        .args(["ip", "asn", "country", "expression"])))]
+    Add {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// IPv4/IPv6 address or CIDR range
+        #[arg(long)]
+        ip: Option<String>,
+
+        /// Autonomous system number, e.g. AS64496
+        #[arg(long)]
+        asn: Option<String>,
+
+        /// Two-letter country code
+        #[arg(long)]
+        country: Option<String>,
+
+        /// Rules language expression for a custom WAF rule
+        #[arg(long)]
+        expression: Option<String>,
+
+        /// What to do with matching requests
+        #[arg(long, default_value = "block",
+              value_parser = ["block", "challenge", "js_challenge", "managed_challenge", "allow", "log"])]
+        action: String,
+
+        /// Note shown in the dashboard (WAF rule description)
+        #[arg(long, alias = "description")]
+        note: Option<String>,
+
+        /// Remove the rule after this long on the next `cf firewall gc`, e.g. 30m, 24h, 7d
+        #[arg(long, value_parser = parse_duration)]
+        expires: Option<u64>,
+    },
+
+    /// Remove an IP access rule or custom WAF rule by ID
+    #[command(after_help = "Examples:
+  cf firewall remove --domain example.com --id 92f17202ed8bd63d69a66b86a49a8f6b")]
+    Remove {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// Rule ID (from `cf firewall list`)
+        #[arg(long)]
+        id: String,
+    },
+
+    /// Remove rules whose --expires has passed
+    #[command(after_help = "Examples:
+  cf firewall gc
+  cf firewall gc --domain example.com --dry-run
+
+Run it from cron to lift temporary blocks, e.g. every 10 minutes.")]
+    Gc {
+        /// Only this domain's rules (default: every domain in registry.json)
+        #[arg(short, long)]
+        domain: Option<String>,
+
+        /// Show what would be removed without removing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Parse a duration like 90s, 30m, 24h, 7d or 2w into seconds
+fn parse_duration(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => 0,
+    };
+    match number.parse::<u64>() {
+        Ok(n) if n > 0 && scale > 0 => Ok(n * scale),
+        _ => Err(format!("duration must be a number with s, m, h, d or w (e.g. 24h), got: {}", s)),
+    }
+}
+
+// ============ Redirect Commands ============
+
+#[derive(Args)]
+pub struct RedirectCommand {
+    #[command(subcommand)]
+    pub action: RedirectAction,
+}
+
+#[derive(Subcommand)]
+pub enum RedirectAction {
+    /// List the zone's single redirect rules
+    #[command(after_help = "Examples:
+  cf redirect list --domain example.com")]
+    List {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+    },
+
+    /// Redirect one path (on the domain or a subdomain) to a URL
+    #[command(after_help = "Examples:
+  cf redirect add --domain example.com --from /old --to https://example.com/new
+  cf redirect add --domain example.com --from go.example.com/docs --to https://docs.example.net/ --status 302
+  cf redirect add --domain example.com --from /search --to https://example.com/find --preserve-query")]
+    Add {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// Path to redirect (/old), optionally with a host in the zone (www.example.com/old)
+        #[arg(long)]
+        from: String,
+
+        /// Absolute target URL
+        #[arg(long)]
+        to: String,
+
+        /// HTTP status code
+        #[arg(long, default_value_t = 301, value_parser = parse_redirect_status)]
+        status: u16,
+
+        /// Keep the request's query string on the target URL
+        #[arg(long)]
+        preserve_query: bool,
+
+        /// Rule description (default: "<from> -> <to>")
+        #[arg(long, alias = "description")]
+        note: Option<String>,
+    },
+
+    /// Remove a redirect by rule ID or by its --from path
+    #[command(after_help = "Examples:
+  cf redirect remove --domain example.com --from /old
+  cf redirect remove --domain example.com --id 2c0fc9fa937b11eaa1b71c4d701ab86e")]
+    #[command(group(clap::ArgGroup::new("which").required(true).args(["id", "from"])))]
+    Remove {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// Rule ID (from `cf redirect list`)
+        #[arg(long)]
+        id: Option<String>,
+
+        /// Path (and optional host) the redirect matches
+        #[arg(long)]
+        from: Option<String>,
+    },
+
+    /// Sync redirects from a CSV file (header: from,to[,status][,preserve_query])
+    #[command(after_help = "Examples:
+  cf redirect import --domain example.com --file redirects.csv --dry-run
+  cf redirect import --domain example.com --file redirects.csv --prune
+
+redirects.csv:
+  from,to,status,preserve_query
+  /old,https://example.com/new,301,false
+  blog.example.com/feed,https://example.com/rss.xml,308,")]
+    Import {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// CSV file
+        #[arg(short, long)]
+        file: String,
+
+        /// Also remove redirect rules that are not in the file
+        #[arg(long)]
+        prune: bool,
+
+        /// Show the changes without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Parse a redirect status; single redirects support 301, 302, 307 and 308
+pub(crate) fn parse_redirect_status(s: &str) -> Result<u16, String> {
+    match s.trim().parse::<u16>() {
+        Ok(code @ (301 | 302 | 307 | 308)) => Ok(code),
+        _ => Err(format!("status must be 301, 302, 307 or 308, got: {}", s)),
+    }
+}
+
+// ============ Tunnel Commands ============
+
+#[derive(Args)]
+pub struct TunnelCommand {
+    #[command(subcommand)]
+    pub action: TunnelAction,
+}
+
+#[derive(Subcommand)]
+pub enum TunnelAction {
+    /// List the account's tunnels and the hostnames routed to them
+    #[command(after_help = "Examples:
+  cf tunnel list --domain example.com")]
+    List {
+        /// Domain whose API token and account to use
+        #[arg(short, long)]
+        domain: String,
+    },
+
+    /// Create a remotely managed tunnel and print its connector token
+    #[command(after_help = "Examples:
+  cf tunnel create --domain example.com --name homelab
+
+Run the connector on the host with:
+  cloudflared tunnel run --token <token>")]
+    Create {
+        /// Domain whose API token and account to use
+        #[arg(short, long)]
+        domain: String,
+
+        /// Tunnel name
+        #[arg(short, long)]
+        name: String,
+    },
+
+    /// Route a hostname to a local service through a tunnel (ingress rule + proxied CNAME)
+    #[command(after_help = "Examples:
+  cf tunnel route --domain example.com --tunnel homelab --name grafana --service http://localhost:3000
+  cf tunnel route --domain example.com --tunnel homelab --name ssh --service ssh://localhost:22")]
+    Route {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// Tunnel name or ID
+        #[arg(short, long)]
+        tunnel: String,
+
+        /// Subdomain name
+        #[arg(short, long)]
+        name: String,
+
+        /// Origin service, e.g. http://localhost:3000, ssh://localhost:22, http_status:404
+        #[arg(short, long)]
+        service: String,
+    },
+
+    /// Show a tunnel's ingress rules, or remove a hostname's rule and CNAME
+    #[command(after_help = "Examples:
+  cf tunnel ingress --domain example.com --tunnel homelab
+  cf tunnel ingress --domain example.com --tunnel homelab --remove grafana")]
+    Ingress {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+
+        /// Tunnel name or ID
+        #[arg(short, long)]
+        tunnel: String,
+
+        /// Subdomain whose route to remove
+        #[arg(long)]
+        remove: Option<String>,
     },
 }
 
@@ -157,7 +1022,9 @@ pub struct CaddyCommand {
 pub enum CaddyAction {
     /// Add reverse proxy configuration
     #[command(after_help = "Examples:
-  cf caddy add --server 1.2.3.4 --domain myapp.example.com --upstream localhost:3001")]
+  cf caddy add --server 1.2.3.4 --domain myapp.example.com --upstream localhost:3001
+  cf caddy add --server 1.2.3.4 --domain myapp.example.com --upstream localhost:3001 \\
+    --tls-cert /etc/caddy/certs/myapp.example.com.pem --tls-key /etc/caddy/certs/myapp.example.com.key")]
     Add {
         /// Server IP address
         #[arg(short, long)]
@@ -170,6 +1037,9 @@ pub enum CaddyAction {
         /// Upstream address (e.g., localhost:3001)
         #[arg(short, long)]
         upstream: String,
+
+        #[command(flatten)]
+        tls: TlsArgs,
     },
 
     /// Add load balancer configuration
@@ -191,6 +1061,9 @@ pub enum CaddyAction {
         /// Health check URI
         #[arg(long, default_value = "/health")]
         health_uri: String,
+
+        #[command(flatten)]
+        tls: TlsArgs,
     },
 
     /// Reload Caddy configuration
@@ -212,6 +1085,69 @@ pub enum CaddyAction {
     },
 }
 
+/// Certificate files on the server, used instead of ACME (e.g. from `cf origin-cert issue`)
+#[derive(Args, Clone, Debug)]
+pub struct TlsArgs {
+    /// Certificate file on the server
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<String>,
+
+    /// Private key file on the server
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<String>,
+}
+
+// ============ Origin CA Commands ============
+
+#[derive(Args)]
+pub struct OriginCertCommand {
+    #[command(subcommand)]
+    pub action: OriginCertAction,
+}
+
+#[derive(Subcommand)]
+pub enum OriginCertAction {
+    /// Issue an Origin CA certificate and install it on a Caddy server
+    #[command(after_help = "Examples:
+  cf origin-cert issue --hostnames example.com,*.example.com --server 1.2.3.4
+  cf origin-cert issue --hostnames api.example.com --server 1.2.3.4 --key-type ecc --validity 365
+
+The key and CSR are generated locally with openssl; only the CSR is sent to Cloudflare.
+The Caddyfile is not edited: existing site blocks for these hostnames keep their current
+TLS settings. Add `tls <cert> <key>` to them by hand, or create a new block with:
+  cf caddy add ... --tls-cert <cert> --tls-key <key>")]
+    Issue {
+        /// Hostnames the certificate covers (comma-separated; wildcards like *.example.com allowed)
+        #[arg(long, value_delimiter = ',', required = true)]
+        hostnames: Vec<String>,
+
+        /// Server IP address to install the certificate on
+        #[arg(short, long)]
+        server: String,
+
+        /// Validity in days
+        #[arg(long, default_value_t = 5475, value_parser = parse_validity)]
+        validity: u32,
+
+        /// Private key type
+        #[arg(long, default_value = "rsa", value_parser = ["rsa", "ecc"])]
+        key_type: String,
+
+        /// Directory on the server for the certificate and key
+        #[arg(long, default_value = "/etc/caddy/certs")]
+        dir: String,
+    },
+}
+
+/// Parse an Origin CA validity; Cloudflare only issues these lengths
+fn parse_validity(s: &str) -> Result<u32, String> {
+    const DAYS: [u32; 7] = [7, 30, 90, 365, 730, 1095, 5475];
+    match s.parse::<u32>() {
+        Ok(days) if DAYS.contains(&days) => Ok(days),
+        _ => Err(format!("validity must be one of 7, 30, 90, 365, 730, 1095 or 5475 days, got: {}", s)),
+    }
+}
+
 // ============ Service Commands ============
 
 #[derive(Args)]
@@ -307,6 +1243,15 @@ pub enum RegistryAction {
     #[command(after_help = "Examples:
   cf registry stats")]
     Stats,
+
+    /// Import live Cloudflare records into registry.json
+    #[command(after_help = "Examples:
+  cf registry import --domain example.com")]
+    Import {
+        /// Domain name
+        #[arg(short, long)]
+        domain: String,
+    },
 }
 
 // ============ R2 Commands ============
@@ -321,6 +1266,9 @@ pub enum RegistryAction {
   cf r2 list                          # 列出所有文件
   cf r2 list --prefix uploads/        # 只看 uploads 目录
 
+下载文件:
+  cf r2 download --key uploads/图片.png
+
 提示: 上传成功后会返回 public_url，可直接在浏览器打开
 "#)]
 pub struct R2Command {
@@ -335,6 +1283,16 @@ pub enum R2Action {
 示例:
   cf r2 upload --file /path/to/image.png
   cf r2 upload --file photo.jpg --key images/2024/photo.jpg
+  cf r2 upload --file app.js --key assets/app.js --purge   # 覆盖后清除 CDN 缓存
+  cf r2 upload --file db.dump --key backups/db.dump --part-size 64M --part-concurrency 8
+
+超过 --multipart-threshold (默认 64M) 的文件分片并行上传，不会整体读入内存；
+每个分片失败后自动重试，进度通过 progress 事件输出
+
+中断续传:
+  上传进度记录在项目目录 .cf/uploads/ 下，失败后可以:
+  cf r2 upload --file db.dump --key backups/db.dump --resume   # 继续上传
+  cf r2 upload --file db.dump --key backups/db.dump --abort    # 放弃并清理
 
 上传成功后返回:
   - public_url: 公开访问链接，可直接分享
@@ -353,6 +1311,115 @@ pub enum R2Action {
         /// 是否返回公开链接 (默认: 是)
         #[arg(long, default_value = "true")]
         public: bool,
+
+        /// 上传后清除该公开链接的 Cloudflare 缓存 (覆盖同名文件时使用)
+        #[arg(long)]
+        purge: bool,
+
+        #[command(flatten)]
+        multipart: MultipartArgs,
+
+        /// 继续上次中断的分片上传 (文件未变化时只上传缺少的分片)
+        #[arg(long, conflicts_with = "abort")]
+        resume: bool,
+
+        /// 放弃上次中断的分片上传，删除云端已上传的分片和本地检查点
+        #[arg(long)]
+        abort: bool,
+    },
+
+    /// 下载文件 - 流式写入本地文件或标准输出，并校验大小和 ETag
+    #[command(after_help = r#"
+示例:
+  cf r2 download --key backups/db.sql.gz                 # 保存为 ./db.sql.gz
+  cf r2 download --key backups/db.sql.gz --out /tmp/db.sql.gz
+  cf r2 download --key logs/app.log --range -4096 --out -   # 最后 4KB 输出到终端
+  cf r2 download --key data.bin --range 0-1023 --out head.bin
+
+--range 格式 (字节, 含两端):
+  0-1023   前 1024 字节
+  1024-    从 1024 字节到结尾
+  -4096    最后 4096 字节
+
+--out - 时文件内容写到 stdout，结果 JSON 改为输出到 stderr 日志
+"#)]
+    Download {
+        /// 云端文件路径
+        #[arg(short, long)]
+        key: String,
+
+        /// 保存路径，"-" 表示标准输出 (默认: 当前目录下同名文件)
+        #[arg(short, long)]
+        out: Option<String>,
+
+        /// 只下载部分字节: start-end, start- 或 -suffix
+        #[arg(long, value_parser = parse_range, allow_hyphen_values = true)]
+        range: Option<ByteRange>,
+    },
+
+    /// 同步目录 - 只上传有变化的文件，可删除云端多余文件
+    #[command(after_help = r#"
+示例:
+  cf r2 sync ./dist site/ --dry-run        # 先看计划 (JSON)，不做任何改动
+  cf r2 sync ./dist site/                  # 上传新增和有变化的文件
+  cf r2 sync ./dist site/ --delete         # 同时删除本地已不存在的云端文件
+  cf r2 sync ./dist "" --delete --allow-bucket-root   # 整个存储桶镜像本地目录
+  cf r2 sync ./assets assets/ --concurrency 16
+
+比较方式: 大小不同即上传；大小相同时比较 ETag 与本地 MD5
+(分片上传的对象按相同分片大小计算 ETag)
+"#)]
+    Sync {
+        /// 本地目录
+        dir: String,
+
+        /// 云端路径前缀 (如: site/)，空字符串表示整个存储桶
+        prefix: String,
+
+        /// 删除云端前缀下本地已不存在的文件
+        #[arg(long)]
+        delete: bool,
+
+        /// 允许前缀为空 (整个存储桶) 时使用 --delete
+        #[arg(long)]
+        allow_bucket_root: bool,
+
+        /// 只输出同步计划，不上传也不删除
+        #[arg(long)]
+        dry_run: bool,
+
+        /// 同时上传的文件数
+        #[arg(long, default_value = "4", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        concurrency: usize,
+
+        #[command(flatten)]
+        multipart: MultipartArgs,
+    },
+
+    /// 生成临时链接 - 无需公开存储桶即可下载或上传指定文件
+    #[command(after_help = r#"
+示例:
+  cf r2 presign --key reports/2024.pdf                          # 下载链接，1 小时有效
+  cf r2 presign --key reports/2024.pdf --expires 7d
+  cf r2 presign --key incoming/build.tar.gz --method put --expires 15m
+
+PUT 链接的使用方式:
+  curl -X PUT --upload-file build.tar.gz "<url>"
+
+链接用 R2 访问密钥签名 (SigV4)，不会暴露密钥；有效期最长 7 天 (604800 秒)
+"#)]
+    Presign {
+        /// 云端文件路径
+        #[arg(short, long)]
+        key: String,
+
+        /// 链接用途: get 下载, put 上传
+        #[arg(short, long, default_value = "get", value_parser = ["get", "put"])]
+        method: String,
+
+        /// 有效期: 秒数或带单位 (如 3600, 15m, 24h, 7d)
+        #[arg(short, long, default_value = "3600", value_parser = parse_presign_expiry)]
+        expires: u32,
     },
 
     /// 查看文件列表 - 列出云端已上传的文件
@@ -398,3 +1465,89 @@ pub enum R2Action {
         key: String,
     },
 }
+
+/// 分片上传设置
+#[derive(Args, Clone, Debug)]
+pub struct MultipartArgs {
+    /// 超过此大小的文件使用分片上传 (如 64M, 1G)
+    #[arg(long, default_value = "64M", env = "CLOUDFLARE_R2_MULTIPART_THRESHOLD", value_parser = parse_size)]
+    pub multipart_threshold: u64,
+
+    /// 分片大小，最小 5M (分片数超过 10000 时自动增大)
+    #[arg(long, default_value = "16M", value_parser = parse_part_size)]
+    pub part_size: u64,
+
+    /// 同时上传的分片数
+    #[arg(long, default_value = "4", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub part_concurrency: usize,
+}
+
+/// Parse a size in bytes, optionally with a K, M or G suffix (powers of 1024)
+fn parse_size(s: &str) -> Result<u64, String> {
+    let t = s.trim().trim_end_matches(['B', 'b']).trim_end_matches('i');
+    let split = t.find(|c: char| !c.is_ascii_digit()).unwrap_or(t.len());
+    let (number, unit) = t.split_at(split);
+    let scale = match unit.to_ascii_uppercase().as_str() {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => 0,
+    };
+    match number.parse::<u64>().ok().and_then(|n| n.checked_mul(scale)) {
+        Some(n) if scale > 0 => Ok(n),
+        _ => Err(format!("size must be bytes or a number with K, M or G (e.g. 64M), got: {}", s)),
+    }
+}
+
+/// Parse a multipart part size; S3 requires at least 5 MiB for all but the last part
+fn parse_part_size(s: &str) -> Result<u64, String> {
+    match parse_size(s)? {
+        n if n < 5 << 20 => Err(format!("part size must be at least 5M, got: {}", s)),
+        n => Ok(n),
+    }
+}
+
+/// Parse a presigned URL lifetime: seconds, or a duration like 15m or 7d.
+/// SigV4 presigned URLs are valid for at most 7 days.
+fn parse_presign_expiry(s: &str) -> Result<u32, String> {
+    let secs = match s.trim().parse::<u64>() {
+        Ok(n) => n,
+        Err(_) => parse_duration(s)?,
+    };
+    match u32::try_from(secs) {
+        Ok(n) if (1..=604_800).contains(&n) => Ok(n),
+        _ => Err(format!("expiry must be between 1 second and 7 days (604800), got: {}", s)),
+    }
+}
+
+/// Byte range of an object, as given to `cf r2 download --range`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteRange {
+    /// From an offset to an optional last byte (inclusive)
+    From(u64, Option<u64>),
+    /// The last n bytes
+    Last(u64),
+}
+
+/// Parse an HTTP-style byte range: 0-1023, 1024- or -4096
+fn parse_range(s: &str) -> Result<ByteRange, String> {
+    let invalid = || format!("range must be start-end, start- or -suffix in bytes (e.g. 0-1023), got: {}", s);
+    let (start, end) = s.trim().split_once('-').ok_or_else(invalid)?;
+    let number = |v: &str| v.parse::<u64>().map_err(|_| invalid());
+    match (start.is_empty(), end.is_empty()) {
+        (true, false) => match number(end)? {
+            0 => Err(invalid()),
+            n => Ok(ByteRange::Last(n)),
+        },
+        (false, true) => Ok(ByteRange::From(number(start)?, None)),
+        (false, false) => {
+            let (start, end) = (number(start)?, number(end)?);
+            if end < start {
+                return Err(invalid());
+            }
+            Ok(ByteRange::From(start, Some(end)))
+        }
+        (true, true) => Err(invalid()),
+    }
+}
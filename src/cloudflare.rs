@@ -0,0 +1,341 @@
+//! Cloudflare API client
+//!
+//! One place for the Bearer header, response envelope parsing, retries and
+//! error mapping, so every command talks to the API the same way:
+//! - 429 and 5xx responses are retried with exponential backoff, honoring `Retry-After`
+//! - 401/403 become `auth` errors, other failures keep Cloudflare's error codes in `details`
+
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+use crate::config;
+use crate::output::PebbleError;
+
+/// Default Cloudflare API base URL
+pub const DEFAULT_API_BASE: &str = "https://api.cloudflare.com/client/v4";
+
+/// Env var overriding the API base URL (e.g. a local mock server)
+pub const API_BASE_ENV: &str = "CF_API_BASE";
+
+/// Retries after the first attempt for retryable failures
+const MAX_RETRIES: u32 = 3;
+
+/// First backoff delay; doubles on each retry
+const BASE_BACKOFF_MS: u64 = 500;
+
+/// Upper bound for any single wait, including server-provided `Retry-After`
+const MAX_BACKOFF_S: u64 = 60;
+
+/// Page size when walking paginated list endpoints
+const PER_PAGE: usize = 100;
+
+// ============ Response Types ============
+
+#[derive(Debug, Deserialize)]
+struct Envelope<T> {
+    success: bool,
+    result: Option<T>,
+    #[serde(default)]
+    errors: Vec<CfError>,
+    #[serde(default)]
+    result_info: Option<ResultInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CfError {
+    pub code: i32,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResultInfo {
+    #[serde(default)]
+    page: u32,
+    #[serde(default)]
+    total_pages: u32,
+    #[serde(default)]
+    total_count: usize,
+}
+
+/// Records from a paginated listing, plus the server-side total
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total_count: usize,
+}
+
+// ============ Errors ============
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("Failed to connect to Cloudflare API: {0}")]
+    Network(#[source] reqwest::Error),
+
+    #[error("Cloudflare API error (HTTP {status}): {}", join_errors(errors))]
+    Api {
+        status: u16,
+        errors: Vec<CfError>,
+        retry_after: Option<u64>,
+    },
+
+    #[error("Failed to parse Cloudflare response (HTTP {status}): {message}")]
+    Parse { status: u16, message: String },
+}
+
+pub fn join_errors(errors: &[CfError]) -> String {
+    if errors.is_empty() {
+        return "no error details".to_string();
+    }
+    errors.iter()
+        .map(|e| format!("[{}] {}", e.code, e.message))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl ApiError {
+    /// Whether Cloudflare returned this error code (e.g. 81057 = record already exists)
+    pub fn has_code(&self, code: i32) -> bool {
+        matches!(self, ApiError::Api { errors, .. } if errors.iter().any(|e| e.code == code))
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, ApiError::Api { status: 404, .. })
+    }
+
+    /// Map to a Pebble error with Cloudflare's error codes in `details`
+    pub fn to_pebble(&self) -> PebbleError {
+        let message = self.to_string();
+        match self {
+            ApiError::Network(_) => PebbleError::net("CF_UNREACHABLE", &message),
+            ApiError::Api { status, errors, retry_after } => {
+                let err = match *status {
+                    401 | 403 => PebbleError::auth("CF_AUTH_FAILED", &message),
+                    429 => {
+                        let mut e = PebbleError::ext("CF_RATE_LIMITED", &message);
+                        e.retry_after_s = Some(retry_after.unwrap_or(60) as u32);
+                        e
+                    }
+                    s if s >= 500 => PebbleError::ext("CF_UNAVAILABLE", &message),
+                    _ => {
+                        let mut e = PebbleError::ext("CF_API_ERROR", &message);
+                        e.retryable = false;
+                        e.retry_after_s = None;
+                        e.fix = vec!["param".into(), "report".into()];
+                        e
+                    }
+                };
+                err.with_details(json!({"http_status": status, "cf_errors": errors}))
+            }
+            ApiError::Parse { status, .. } => PebbleError::ext("CF_BAD_RESPONSE", &message)
+                .with_details(json!({"http_status": status})),
+        }
+    }
+
+    fn retryable(&self, method: &Method) -> bool {
+        match self {
+            // Only idempotent requests are retried on connection failures
+            ApiError::Network(_) => *method != Method::POST,
+            ApiError::Api { status: 429, .. } => true,
+            ApiError::Api { status, .. } if *status >= 500 => *method != Method::POST,
+            _ => false,
+        }
+    }
+}
+
+// ============ Client ============
+
+/// Authenticated Cloudflare API client
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    api_token: String,
+}
+
+impl Client {
+    /// Client for the API base from `CF_API_BASE`, or the public API
+    pub fn new(api_token: &str) -> Self {
+        let base_url = std::env::var(API_BASE_ENV)
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_API_BASE.to_string());
+        Self::with_base_url(&base_url, api_token)
+    }
+
+    /// Client for another API base (e.g. a mock server)
+    pub fn with_base_url(base_url: &str, api_token: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_token: api_token.to_string(),
+        }
+    }
+
+    /// Client and zone ID for a domain, using the credentials from `config`
+    pub async fn for_domain(domain: &str) -> anyhow::Result<(Self, String)> {
+        let (zone_id, api_token) = config::get_cf_credentials(domain).await?;
+        Ok((Self::new(&api_token), zone_id))
+    }
+
+    /// Client, zone ID and zone name for a hostname, trying each parent domain
+    /// (e.g. cdn.example.com -> example.com) until one has credentials and a zone
+    pub async fn for_host(host: &str) -> anyhow::Result<(Self, String, String)> {
+        let host = host.trim_start_matches("*.").trim_end_matches('.').to_ascii_lowercase();
+        let labels: Vec<&str> = host.split('.').collect();
+
+        let mut last_error = None;
+        for start in 0..labels.len().saturating_sub(1) {
+            let candidate = labels[start..].join(".");
+            match Self::for_domain(&candidate).await {
+                Ok((client, zone_id)) => return Ok((client, zone_id, candidate)),
+                Err(e) if e.downcast_ref::<config::CredentialsError>().is_some() => last_error = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| config::CredentialsError::ZoneNotFound { domain: host }.into()))
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, path: &str, query: &impl Serialize) -> Result<T, ApiError> {
+        self.send(Method::GET, path, query, None, None).await.map(|(r, _)| r)
+    }
+
+    pub async fn post<T: DeserializeOwned>(&self, path: &str, body: &impl Serialize) -> Result<T, ApiError> {
+        let body = serde_json::to_value(body).unwrap_or_default();
+        self.send(Method::POST, path, &(), None, Some(&body)).await.map(|(r, _)| r)
+    }
+
+    pub async fn patch<T: DeserializeOwned>(&self, path: &str, body: &impl Serialize) -> Result<T, ApiError> {
+        let body = serde_json::to_value(body).unwrap_or_default();
+        self.send(Method::PATCH, path, &(), None, Some(&body)).await.map(|(r, _)| r)
+    }
+
+    pub async fn put<T: DeserializeOwned>(&self, path: &str, body: &impl Serialize) -> Result<T, ApiError> {
+        let body = serde_json::to_value(body).unwrap_or_default();
+        self.send(Method::PUT, path, &(), None, Some(&body)).await.map(|(r, _)| r)
+    }
+
+    pub async fn delete<T: DeserializeOwned>(&self, path: &str) -> Result<T, ApiError> {
+        self.send(Method::DELETE, path, &(), None, None).await.map(|(r, _)| r)
+    }
+
+    /// GET every page of a list endpoint, stopping early once `limit` items are collected
+    pub async fn get_all<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &impl Serialize,
+        limit: Option<usize>,
+    ) -> Result<Page<T>, ApiError> {
+        let mut items: Vec<T> = Vec::new();
+        let mut total_count = 0;
+        let mut page = 1;
+
+        loop {
+            let (batch, info): (Vec<T>, _) = self.send(Method::GET, path, query, Some(page), None).await?;
+            let batch_len = batch.len();
+            items.extend(batch);
+
+            // Without result_info there is nothing to page through
+            let Some(info) = info else { break };
+            total_count = info.total_count;
+
+            if let Some(limit) = limit {
+                if items.len() >= limit {
+                    items.truncate(limit);
+                    break;
+                }
+            }
+            if info.page >= info.total_pages || batch_len == 0 {
+                break;
+            }
+            page += 1;
+        }
+
+        let total_count = total_count.max(items.len());
+        Ok(Page { items, total_count })
+    }
+
+    /// Send a request, retrying rate limits and server errors with backoff
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &impl Serialize,
+        page: Option<u32>,
+        body: Option<&serde_json::Value>,
+    ) -> Result<(T, Option<ResultInfo>), ApiError> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut attempt = 0;
+
+        loop {
+            let result = self.send_once(method.clone(), &url, query, page, body).await;
+
+            match result {
+                Err(e) if attempt < MAX_RETRIES && e.retryable(&method) => {
+                    let backoff = Duration::from_millis(BASE_BACKOFF_MS << attempt);
+                    let wait = match &e {
+                        ApiError::Api { retry_after: Some(s), .. } => Duration::from_secs(*s),
+                        _ => backoff,
+                    }
+                    .min(Duration::from_secs(MAX_BACKOFF_S));
+
+                    attempt += 1;
+                    tokio::time::sleep(wait).await;
+                }
+                other => return other,
+            }
+        }
+    }
+
+    async fn send_once<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: &str,
+        query: &impl Serialize,
+        page: Option<u32>,
+        body: Option<&serde_json::Value>,
+    ) -> Result<(T, Option<ResultInfo>), ApiError> {
+        let mut req = self.http
+            .request(method, url)
+            .bearer_auth(&self.api_token)
+            .header("Content-Type", "application/json")
+            .query(query);
+        if let Some(page) = page {
+            req = req.query(&[("page", page as usize), ("per_page", PER_PAGE)]);
+        }
+        if let Some(body) = body {
+            req = req.json(body);
+        }
+
+        let resp = req.send().await.map_err(ApiError::Network)?;
+        let status = resp.status();
+        let retry_after = resp.headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok());
+
+        let text = resp.text().await.map_err(ApiError::Network)?;
+        let envelope: Envelope<T> = match serde_json::from_str(&text) {
+            Ok(env) => env,
+            // Error pages from proxies and rate limiters are often not JSON
+            Err(_) if !status.is_success() => {
+                return Err(ApiError::Api { status: status.as_u16(), errors: Vec::new(), retry_after });
+            }
+            Err(e) => {
+                return Err(ApiError::Parse { status: status.as_u16(), message: e.to_string() });
+            }
+        };
+
+        if !envelope.success || !status.is_success() {
+            return Err(ApiError::Api { status: status.as_u16(), errors: envelope.errors, retry_after });
+        }
+
+        match envelope.result {
+            Some(result) => Ok((result, envelope.result_info)),
+            None => Err(ApiError::Parse { status: status.as_u16(), message: "missing result".into() }),
+        }
+    }
+}
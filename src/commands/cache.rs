@@ -0,0 +1,167 @@
+//! Cache purge: clear Cloudflare's edge cache by URL, prefix, host, tag or everything
+
+use anyhow::Result;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::cli::{CacheAction, CacheCommand};
+use crate::cloudflare::Client;
+use crate::config::{self, CredentialsError};
+use crate::output::{Output, PebbleError};
+
+/// Most items Cloudflare accepts in one purge request
+const PURGE_CHUNK: usize = 30;
+
+/// What to purge; the API takes one kind per request
+#[derive(Debug, Default)]
+struct Targets {
+    urls: Vec<String>,
+    prefixes: Vec<String>,
+    hosts: Vec<String>,
+    tags: Vec<String>,
+    everything: bool,
+}
+
+pub async fn run(cmd: CacheCommand, out: &Output) -> Result<()> {
+    config::load_env()?;
+
+    match cmd.action {
+        CacheAction::Purge { domain, urls, prefixes, hosts, tags, everything, yes } => {
+            if everything && !yes {
+                out.error(PebbleError::input(
+                    "CONFIRMATION_REQUIRED",
+                    &format!("--everything drops every cached file for {}; pass --yes to confirm", domain),
+                )
+                .with_op("cache.purge"));
+                return Ok(());
+            }
+            let targets = Targets { urls, prefixes, hosts, tags, everything };
+            purge(&domain, &targets, out).await
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PurgeRef {
+    id: String,
+}
+
+/// Check that every target belongs to `domain`, normalizing prefixes to Cloudflare's scheme-less form
+fn validate(domain: &str, targets: &Targets) -> std::result::Result<Targets, String> {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    let in_zone = |host: &str| {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        host == domain || host.ends_with(&format!(".{}", domain))
+    };
+
+    let mut urls = Vec::new();
+    for raw in &targets.urls {
+        let url = Url::parse(raw).map_err(|_| format!("Not an absolute URL: {}", raw))?;
+        if !matches!(url.scheme(), "http" | "https") || !url.host_str().is_some_and(in_zone) {
+            return Err(format!("URL is not on {}: {}", domain, raw));
+        }
+        urls.push(raw.clone());
+    }
+
+    let mut prefixes = Vec::new();
+    for raw in &targets.prefixes {
+        let prefix = raw.trim_start_matches("https://").trim_start_matches("http://");
+        let host = prefix.split('/').next().unwrap_or("");
+        if !in_zone(host) {
+            return Err(format!("Prefix is not on {}: {}", domain, raw));
+        }
+        prefixes.push(prefix.to_string());
+    }
+
+    if let Some(host) = targets.hosts.iter().find(|h| !in_zone(h)) {
+        return Err(format!("Host is not on {}: {}", domain, host));
+    }
+
+    Ok(Targets {
+        urls,
+        prefixes,
+        hosts: targets.hosts.clone(),
+        tags: targets.tags.clone(),
+        everything: targets.everything,
+    })
+}
+
+/// Purge `targets` from a domain's zone; returns a summary of what was sent
+async fn purge_zone(domain: &str, targets: &Targets) -> std::result::Result<Value, PebbleError> {
+    let (client, zone_id) = Client::for_domain(domain).await.map_err(|e| PebbleError::from(&e))?;
+    send(&client, &zone_id, domain, targets).await
+}
+
+/// Validate and send purge requests, chunking large lists
+async fn send(client: &Client, zone_id: &str, domain: &str, targets: &Targets) -> std::result::Result<Value, PebbleError> {
+    let targets = validate(domain, targets).map_err(|msg| PebbleError::input("INVALID_PURGE_TARGET", &msg))?;
+    let path = format!("/zones/{}/purge_cache", zone_id);
+
+    let mut bodies = Vec::new();
+    if targets.everything {
+        bodies.push(json!({"purge_everything": true}));
+    }
+    for (field, items) in [("files", &targets.urls), ("prefixes", &targets.prefixes), ("hosts", &targets.hosts), ("tags", &targets.tags)] {
+        bodies.extend(items.chunks(PURGE_CHUNK).map(|chunk| json!({ field: chunk })));
+    }
+    if bodies.is_empty() {
+        return Err(PebbleError::input(
+            "NOTHING_TO_PURGE",
+            "Pass --urls, --prefixes, --hosts, --tags or --everything",
+        ));
+    }
+
+    let mut ids = Vec::new();
+    for body in &bodies {
+        let purged: PurgeRef = client.post(&path, body).await
+            .map_err(|e| e.to_pebble().with_details(json!({"domain": domain, "request": body, "completed": ids})))?;
+        ids.push(purged.id);
+    }
+
+    Ok(json!({
+        "domain": domain,
+        "everything": targets.everything,
+        "urls": targets.urls,
+        "prefixes": targets.prefixes,
+        "hosts": targets.hosts,
+        "tags": targets.tags,
+        "requests": ids.len()
+    }))
+}
+
+/// Purge single URLs, finding their zone from the host (e.g. an R2 custom domain)
+pub(crate) async fn purge_urls(urls: &[String]) -> std::result::Result<Value, PebbleError> {
+    let Some(host) = urls.first().and_then(|u| Url::parse(u).ok()).and_then(|u| u.host_str().map(String::from)) else {
+        return Err(PebbleError::input("INVALID_PURGE_TARGET", "Cannot purge a URL without a host"));
+    };
+
+    match Client::for_host(&host).await {
+        Ok((client, zone_id, zone)) => {
+            let targets = Targets { urls: urls.to_vec(), ..Default::default() };
+            send(&client, &zone_id, &zone, &targets).await
+        }
+        Err(e) if e.downcast_ref::<CredentialsError>().is_some() => {
+            Err(PebbleError::input("ZONE_NOT_FOUND", &format!("No Cloudflare zone you can access serves {}", host))
+                .with_details(json!({"host": host, "last_error": e.to_string()})))
+        }
+        Err(e) => Err(PebbleError::from(&e)),
+    }
+}
+
+async fn purge(domain: &str, targets: &Targets, out: &Output) -> Result<()> {
+    out.log("info", &format!("Purging cache for {}", domain));
+
+    match purge_zone(domain, targets).await {
+        Ok(summary) => {
+            let mut result = json!({"success": true});
+            if let (Some(result), Value::Object(summary)) = (result.as_object_mut(), summary) {
+                result.extend(summary);
+            }
+            out.result(result);
+        }
+        Err(e) => out.error(e.with_op("cache.purge")),
+    }
+
+    Ok(())
+}
@@ -2,29 +2,36 @@
 
 use anyhow::{Result, Context};
 use serde_json::json;
-use std::process::Command;
+use std::io::Write;
+use std::process::{Command, Stdio};
 
-use crate::cli::{CaddyCommand, CaddyAction};
+use crate::cli::{CaddyCommand, CaddyAction, TlsArgs};
 use crate::output::{Output, PebbleError};
 
 pub async fn run(cmd: CaddyCommand, out: &Output) -> Result<()> {
     match cmd.action {
-        CaddyAction::Add { server, domain, upstream } => {
-            add(&server, &domain, &upstream, out).await
+        CaddyAction::Add { server, domain, upstream, tls } => {
+            add(&server, &domain, &upstream, &tls, out).await
         }
-        CaddyAction::AddLb { server, domain, upstreams, health_uri } => {
-            add_lb(&server, &domain, &upstreams, &health_uri, out).await
+        CaddyAction::AddLb { server, domain, upstreams, health_uri, tls } => {
+            add_lb(&server, &domain, &upstreams, &health_uri, &tls, out).await
         }
         CaddyAction::Reload { server } => reload(&server, out).await,
         CaddyAction::Validate { server } => validate(&server, out).await,
     }
 }
 
-/// Execute SSH command and return output
-fn ssh_exec(server: &str, cmd: &str) -> Result<String> {
-    let output = Command::new("ssh")
+fn ssh(server: &str) -> Command {
+    let mut command = Command::new("ssh");
+    command
         .args(["-o", "StrictHostKeyChecking=no", "-o", "ConnectTimeout=10"])
-        .arg(format!("root@{}", server))
+        .arg(format!("root@{}", server));
+    command
+}
+
+/// Execute SSH command and return output
+pub(crate) fn ssh_exec(server: &str, cmd: &str) -> Result<String> {
+    let output = ssh(server)
         .arg(cmd)
         .output()
         .context("Failed to execute SSH command")?;
@@ -37,15 +44,59 @@ fn ssh_exec(server: &str, cmd: &str) -> Result<String> {
     Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
-async fn add(server: &str, domain: &str, upstream: &str, out: &Output) -> Result<()> {
+/// Quote a value for the remote POSIX shell
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Write a file on the server, sending the contents over stdin so secrets stay off the command line
+pub(crate) fn ssh_write(server: &str, path: &str, contents: &[u8], mode: &str) -> Result<()> {
+    let dir = path.rsplit_once('/').map(|(dir, _)| dir).filter(|d| !d.is_empty()).unwrap_or("/");
+    let cmd = format!(
+        "umask 077 && mkdir -p {} && cat > {} && chmod {} {}",
+        shell_quote(dir), shell_quote(path), mode, shell_quote(path)
+    );
+
+    let mut child = ssh(server)
+        .arg(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to execute SSH command")?;
+    child.stdin.take().context("SSH stdin unavailable")?.write_all(contents)?;
+
+    let output = child.wait_with_output().context("Failed to execute SSH command")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("SSH command failed: {}", stderr);
+    }
+
+    Ok(())
+}
+
+/// Quote a value as one Caddyfile token, so spaces and braces stay inside it
+pub(crate) fn caddy_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\\\""))
+}
+
+/// `tls` directive for a site block; without one Caddy obtains a certificate via ACME
+fn tls_directive(tls: &TlsArgs) -> String {
+    match (&tls.tls_cert, &tls.tls_key) {
+        (Some(cert), Some(key)) => format!("\n    tls {} {}", caddy_quote(cert), caddy_quote(key)),
+        _ => String::new(),
+    }
+}
+
+async fn add(server: &str, domain: &str, upstream: &str, tls: &TlsArgs, out: &Output) -> Result<()> {
     out.log("info", &format!("Adding Caddy reverse proxy: {} -> {}", domain, upstream));
 
     // Generate Caddy config block
     let config = format!(r#"
 {} {{
-    reverse_proxy {}
+    reverse_proxy {}{}
 }}
-"#, domain, upstream);
+"#, domain, upstream, tls_directive(tls));
 
     // Append to Caddyfile
     let cmd = format!(
@@ -67,6 +118,7 @@ EOF"#,
                         "server": server,
                         "domain": domain,
                         "upstream": upstream,
+                        "tls": tls.tls_cert.as_ref().map(|cert| json!({"cert": cert, "key": tls.tls_key})),
                         "message": "Configuration added. Run 'cf caddy reload' to apply."
                     }));
                 }
@@ -87,7 +139,7 @@ EOF"#,
     Ok(())
 }
 
-async fn add_lb(server: &str, domain: &str, upstreams: &str, health_uri: &str, out: &Output) -> Result<()> {
+async fn add_lb(server: &str, domain: &str, upstreams: &str, health_uri: &str, tls: &TlsArgs, out: &Output) -> Result<()> {
     let upstream_list: Vec<&str> = upstreams.split(',').map(|s| s.trim()).collect();
 
     out.log("info", &format!("Adding Caddy load balancer: {} -> {:?}", domain, upstream_list));
@@ -100,9 +152,9 @@ async fn add_lb(server: &str, domain: &str, upstreams: &str, health_uri: &str, o
         lb_policy round_robin
         health_uri {}
         health_interval 30s
-    }}
+    }}{}
 }}
-"#, domain, upstream_str, health_uri);
+"#, domain, upstream_str, health_uri, tls_directive(tls));
 
     // Append to Caddyfile
     let cmd = format!(
@@ -125,6 +177,7 @@ EOF"#,
                         "domain": domain,
                         "upstreams": upstream_list,
                         "health_uri": health_uri,
+                        "tls": tls.tls_cert.as_ref().map(|cert| json!({"cert": cert, "key": tls.tls_key})),
                         "message": "Load balancer configured. Run 'cf caddy reload' to apply."
                     }));
                 }
@@ -1,261 +1,997 @@
 //! DNS management commands
 
-use anyhow::{Result, Context};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::cli::{DnsCommand, DnsAction};
+use crate::cli::{DnsCommand, DnsAction, WaitArgs};
+use crate::cloudflare::{ApiError, Client, Page};
 use crate::config;
 use crate::output::{Output, PebbleError};
 
-const CF_API_BASE: &str = "https://api.cloudflare.com/client/v4";
+mod batch;
+mod ddns;
+mod plan;
+mod verify;
+mod zonefile;
+
+use verify::Expect;
 
 pub async fn run(cmd: DnsCommand, out: &Output) -> Result<()> {
     config::load_env()?;
 
     match cmd.action {
-        DnsAction::List { domain } => list(&domain, out).await,
-        DnsAction::Get { domain, name } => get(&domain, &name, out).await,
-        DnsAction::Create { domain, name, ip, desc, proxied } => {
-            create(&domain, &name, &ip, &desc, proxied, out).await
+        DnsAction::List { domain, record_type, name_contains, content, proxied, comment, tag, sort, direction, limit } => {
+            // "key:value" matches the exact tag, a bare "key" any value
+            let (tag, tag_present) = match tag {
+                Some(t) if t.contains(':') => (Some(t), None),
+                t => (None, t),
+            };
+            let query = ListQuery {
+                record_type: record_type.map(|t| t.to_uppercase()),
+                name_contains,
+                content,
+                proxied,
+                comment,
+                tag,
+                tag_present,
+                order: sort,
+                direction: Some(direction),
+                limit,
+            };
+            list(&domain, &query, out).await
+        }
+        DnsAction::Get { domain, name, record_type } => {
+            let record_type = record_type.map(|t| t.to_uppercase());
+            get(&domain, &name, record_type.as_deref(), out).await
+        }
+        DnsAction::Create { domain, name, record_type, content, priority, weight, port, desc, ttl, tags, proxied, upsert, wait } => {
+            let input = RecordInput {
+                record_type: record_type.to_uppercase(),
+                content,
+                priority,
+                weight,
+                port,
+            };
+            let meta = RecordMeta { desc, ttl, tags, proxied };
+            create(&domain, &name, &input, &meta, upsert, &wait, out).await
+        }
+        DnsAction::Update { domain, name, record_type, id, content, priority, weight, port, proxied, ttl, comment, tags, untag, wait } => {
+            let changes = RecordChanges { content, priority, weight, port, proxied, ttl, comment, tags, untag };
+            let record_type = record_type.map(|t| t.to_uppercase());
+            let selector = Selector { id: id.as_deref(), record_type: record_type.as_deref(), content: None };
+            update(&domain, &name, &selector, &changes, &wait, out).await
+        }
+        DnsAction::Delete { domain, name, record_type, content, id, wait } => {
+            let record_type = record_type.map(|t| t.to_uppercase());
+            let selector = Selector { id: id.as_deref(), record_type: record_type.as_deref(), content: content.as_deref() };
+            delete(&domain, &name, &selector, &wait, out).await
+        }
+        DnsAction::Verify { domain, name, record_type, content, absent, timeout, interval, resolvers } => {
+            let record_type = record_type.to_uppercase();
+            let check = verify::Check { content, absent, timeout, interval, resolvers };
+            verify::verify(&domain, &name, &record_type, &check, out).await
+        }
+        DnsAction::Batch { domain, file, atomic, concurrency } => {
+            batch::batch(&domain, &file, atomic, concurrency, out).await
+        }
+        DnsAction::Ddns { domain, name, family, echo_url, interface, record_id, proxied, daemon, interval } => {
+            let opts = ddns::Options { family, echo_url, interface, record_id, proxied, daemon, interval };
+            ddns::ddns(&domain, &name, &opts, out).await
+        }
+        DnsAction::Plan { domain } => plan::plan(&domain, out).await,
+        DnsAction::Apply { domain, prune } => plan::apply(&domain, prune, out).await,
+        DnsAction::Export { domain, format, output } => {
+            zonefile::export(&domain, &format, output.as_deref(), out).await
+        }
+        DnsAction::Import { file, domain, dry_run, prune } => {
+            zonefile::import(&file, domain.as_deref(), dry_run, prune, out).await
         }
-        DnsAction::Delete { domain, name } => delete(&domain, &name, out).await,
     }
 }
 
 // ============ Cloudflare API Types ============
 
-#[derive(Debug, Deserialize)]
-struct CfResponse<T> {
-    success: bool,
-    result: Option<T>,
-    errors: Vec<CfError>,
-}
-
-#[derive(Debug, Deserialize)]
-struct CfError {
-    code: i32,
-    message: String,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 struct CfDnsRecord {
     id: String,
     #[serde(rename = "type")]
     record_type: String,
     name: String,
+    #[serde(default)]
     content: String,
+    #[serde(default)]
     proxied: bool,
     ttl: u32,
+    /// MX priority (SRV carries it in `data`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<u16>,
+    /// Structured content for SRV and CAA records
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<CfRecordData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+    /// Custom tags in key:value form
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified_on: Option<String>,
 }
 
-// ============ Commands ============
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CfRecordData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    weight: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    flags: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+}
 
-async fn list(domain: &str, out: &Output) -> Result<()> {
-    let (zone_id, api_token) = config::get_cf_credentials(domain)?;
+/// Server-side filters and ordering for listing records
+#[derive(Debug, Default, Serialize)]
+struct ListQuery {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    record_type: Option<String>,
+    #[serde(rename = "name.contains", skip_serializing_if = "Option::is_none")]
+    name_contains: Option<String>,
+    #[serde(rename = "content.exact", skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proxied: Option<bool>,
+    #[serde(rename = "comment.contains", skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    #[serde(rename = "tag.present", skip_serializing_if = "Option::is_none")]
+    tag_present: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    direction: Option<String>,
+    /// Client-side cap, not sent to the API
+    #[serde(skip)]
+    limit: Option<usize>,
+}
 
-    out.log("info", &format!("Fetching DNS records for {}", domain));
+/// Record type and content as given on the command line
+struct RecordInput {
+    record_type: String,
+    content: String,
+    priority: Option<u16>,
+    weight: Option<u16>,
+    port: Option<u16>,
+}
+
+/// Settings for a new record beyond its type and content
+/// (None = not given: defaults on create, existing value kept on upsert)
+struct RecordMeta {
+    desc: Option<String>,
+    /// None = auto
+    ttl: Option<u32>,
+    tags: Vec<String>,
+    proxied: Option<bool>,
+}
+
+/// Which of the records at a name a command applies to
+struct Selector<'a> {
+    id: Option<&'a str>,
+    record_type: Option<&'a str>,
+    /// Current content in presentation form
+    content: Option<&'a str>,
+}
+
+/// Fields to change on an existing record (None = keep current value)
+struct RecordChanges {
+    content: Option<String>,
+    priority: Option<u16>,
+    /// SRV weight
+    weight: Option<u16>,
+    /// SRV port
+    port: Option<u16>,
+    proxied: Option<bool>,
+    ttl: Option<u32>,
+    comment: Option<String>,
+    /// Tags to add, replacing existing tags with the same key
+    tags: Vec<String>,
+    /// Keys of tags to remove
+    untag: Vec<String>,
+}
+
+impl RecordChanges {
+    fn is_empty(&self) -> bool {
+        self.content.is_none() && self.priority.is_none() && self.weight.is_none() && self.port.is_none()
+            && self.proxied.is_none()
+            && self.ttl.is_none() && self.comment.is_none() && self.tags.is_empty() && self.untag.is_empty()
+    }
+}
+
+/// Key of a key:value tag
+fn tag_key(tag: &str) -> &str {
+    tag.split_once(':').map_or(tag, |(key, _)| key)
+}
 
-    let client = reqwest::Client::new();
-    let url = format!("{}/zones/{}/dns_records", CF_API_BASE, zone_id);
+/// `existing` tags with `add` merged in by key and `remove` keys dropped
+fn merge_tags(existing: &[String], add: &[String], remove: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = existing.iter()
+        .filter(|t| !remove.iter().any(|k| k == tag_key(t)) && !add.iter().any(|a| tag_key(a) == tag_key(t)))
+        .cloned()
+        .collect();
+    tags.extend(add.iter().cloned());
+    tags
+}
+
+// ============ API Helpers ============
+
+pub(crate) fn fqdn(domain: &str, name: &str) -> String {
+    if name == "@" || name == domain {
+        domain.to_string()
+    } else {
+        format!("{}.{}", name, domain)
+    }
+}
+
+/// Record name relative to the zone ("@" for the apex)
+pub(crate) fn relative_name(domain: &str, name: &str) -> String {
+    let name = name.trim_end_matches('.');
+    if name.is_empty() || name == "@" || name.eq_ignore_ascii_case(domain) {
+        "@".to_string()
+    } else if let Some(sub) = name.strip_suffix(&format!(".{}", domain)) {
+        sub.to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Fetch records in a zone, walking every page until `query.limit` is reached
+async fn list_records(client: &Client, zone_id: &str, query: &ListQuery) -> std::result::Result<Page<CfDnsRecord>, ApiError> {
+    client.get_all(&format!("/zones/{}/dns_records", zone_id), query, query.limit).await
+}
+
+/// Look up records by FQDN, optionally restricted to one type
+async fn find_records(
+    client: &Client,
+    zone_id: &str,
+    fqdn: &str,
+    record_type: Option<&str>,
+) -> std::result::Result<Vec<CfDnsRecord>, ApiError> {
+    let mut params = vec![("name", fqdn)];
+    if let Some(t) = record_type {
+        params.push(("type", t));
+    }
+    client.get_all(&format!("/zones/{}/dns_records", zone_id), &params, None).await
+        .map(|page| page.items)
+}
+
+/// GET one record by ID
+async fn get_record(client: &Client, zone_id: &str, record_id: &str) -> std::result::Result<CfDnsRecord, ApiError> {
+    client.get(&format!("/zones/{}/dns_records/{}", zone_id, record_id), &()).await
+}
+
+/// Records at `fqdn` narrowed down by `selector`; an unknown ID yields no records
+async fn select_records(
+    client: &Client,
+    zone_id: &str,
+    fqdn: &str,
+    selector: &Selector<'_>,
+) -> std::result::Result<Vec<CfDnsRecord>, ApiError> {
+    let mut records = match selector.id {
+        Some(id) => match get_record(client, zone_id, id).await {
+            Ok(r) => vec![r],
+            Err(e) if e.has_code(81044) => Vec::new(),
+            Err(e) => return Err(e),
+        },
+        None => find_records(client, zone_id, fqdn, selector.record_type).await?,
+    };
+
+    records.retain(|r| {
+        r.name.eq_ignore_ascii_case(fqdn)
+            && selector.record_type.is_none_or(|t| r.record_type == t)
+            && selector.content.is_none_or(|c| {
+                normalize_content(&r.record_type, &presentation_content(r)) == normalize_content(&r.record_type, c)
+            })
+    });
+    Ok(records)
+}
 
-    let resp = client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", api_token))
-        .header("Content-Type", "application/json")
-        .send()
-        .await
-        .context("Failed to connect to Cloudflare API")?;
+/// Input error listing every record a name matched when exactly one was needed
+fn ambiguous(fqdn: &str, records: &[CfDnsRecord], narrow_with: &str) -> PebbleError {
+    PebbleError::input(
+        "AMBIGUOUS_RECORD",
+        &format!("{} records match {}; choose one with {}", records.len(), fqdn, narrow_with),
+    )
+    .with_details(json!({
+        "fqdn": fqdn,
+        "candidates": records.iter().map(record_json).collect::<Vec<_>>()
+    }))
+}
+
+/// POST a new record
+async fn create_record(client: &Client, zone_id: &str, body: &serde_json::Value) -> std::result::Result<CfDnsRecord, ApiError> {
+    client.post(&format!("/zones/{}/dns_records", zone_id), body).await
+}
+
+/// DELETE a record by ID
+async fn delete_record(client: &Client, zone_id: &str, record_id: &str) -> std::result::Result<serde_json::Value, ApiError> {
+    client.delete(&format!("/zones/{}/dns_records/{}", zone_id, record_id)).await
+}
+
+/// PATCH an existing record with the given fields
+async fn patch_record(
+    client: &Client,
+    zone_id: &str,
+    record_id: &str,
+    body: &serde_json::Value,
+) -> std::result::Result<CfDnsRecord, ApiError> {
+    client.patch(&format!("/zones/{}/dns_records/{}", zone_id, record_id), body).await
+}
 
-    let cf_resp: CfResponse<Vec<CfDnsRecord>> = resp.json().await
-        .context("Failed to parse Cloudflare response")?;
+/// Build the PATCH body for `changes` applied to `existing`
+fn build_patch_body(name: &str, existing: &CfDnsRecord, changes: &RecordChanges) -> std::result::Result<serde_json::Value, String> {
+    let mut body = json!({});
+    let data = existing.data.as_ref();
 
-    if !cf_resp.success {
-        let err_msg = cf_resp.errors.iter()
-            .map(|e| format!("[{}] {}", e.code, e.message))
+    if changes.content.is_some() || changes.priority.is_some() || changes.weight.is_some() || changes.port.is_some() {
+        let content = match &changes.content {
+            Some(c) => c.clone(),
+            None => match existing.record_type.as_str() {
+                "SRV" => data.and_then(|d| d.target.clone()).unwrap_or_default(),
+                _ => existing.content.clone(),
+            },
+        };
+        let input = RecordInput {
+            record_type: existing.record_type.clone(),
+            content,
+            priority: changes.priority
+                .or(existing.priority)
+                .or_else(|| data.and_then(|d| d.priority)),
+            weight: changes.weight.or_else(|| data.and_then(|d| d.weight)),
+            port: changes.port.or_else(|| data.and_then(|d| d.port)),
+        };
+        if let serde_json::Value::Object(fields) = build_record_fields(name, &input)? {
+            for (k, v) in fields {
+                body[k] = v;
+            }
+        }
+    }
+
+    if let Some(proxied) = changes.proxied {
+        if proxied && !matches!(existing.record_type.as_str(), "A" | "AAAA" | "CNAME") {
+            return Err(format!("{} records cannot be proxied", existing.record_type));
+        }
+        body["proxied"] = json!(proxied);
+    }
+    if let Some(ttl) = changes.ttl {
+        body["ttl"] = json!(ttl);
+    }
+    if let Some(comment) = &changes.comment {
+        body["comment"] = json!(comment);
+    }
+    if !changes.tags.is_empty() || !changes.untag.is_empty() {
+        body["tags"] = json!(merge_tags(&existing.tags, &changes.tags, &changes.untag));
+    }
+
+    Ok(body)
+}
+
+// ============ Validation ============
+
+/// Check that a name is a valid DNS hostname (labels of letters, digits, '-' and '_')
+fn is_hostname(s: &str) -> bool {
+    let s = s.strip_suffix('.').unwrap_or(s);
+    if s.is_empty() || s.len() > 253 {
+        return false;
+    }
+    s.split('.').all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    })
+}
+
+/// Validate content for the record type and build the type-specific part of the API body
+fn build_record_fields(name: &str, input: &RecordInput) -> std::result::Result<serde_json::Value, String> {
+    let content = input.content.trim();
+
+    match input.record_type.as_str() {
+        "A" => {
+            content.parse::<std::net::Ipv4Addr>()
+                .map_err(|_| format!("Invalid IPv4 address: {}", content))?;
+            Ok(json!({"type": "A", "content": content}))
+        }
+        "AAAA" => {
+            content.parse::<std::net::Ipv6Addr>()
+                .map_err(|_| format!("Invalid IPv6 address: {}", content))?;
+            Ok(json!({"type": "AAAA", "content": content}))
+        }
+        "CNAME" => {
+            if !is_hostname(content) {
+                return Err(format!("Invalid CNAME target hostname: {}", content));
+            }
+            Ok(json!({"type": "CNAME", "content": content}))
+        }
+        "TXT" => {
+            if content.is_empty() {
+                return Err("TXT content must not be empty".to_string());
+            }
+            Ok(json!({"type": "TXT", "content": content}))
+        }
+        "MX" => {
+            if !is_hostname(content) {
+                return Err(format!("Invalid MX mail server hostname: {}", content));
+            }
+            let priority = input.priority
+                .ok_or_else(|| "MX records require --priority".to_string())?;
+            Ok(json!({"type": "MX", "content": content, "priority": priority}))
+        }
+        "SRV" => {
+            let labels: Vec<&str> = name.split('.').collect();
+            if labels.len() < 2 || !labels[0].starts_with('_') || !labels[1].starts_with('_') {
+                return Err(format!("SRV name must start with _service._proto, got: {}", name));
+            }
+            if !is_hostname(content) && content != "." {
+                return Err(format!("Invalid SRV target hostname: {}", content));
+            }
+            let priority = input.priority
+                .ok_or_else(|| "SRV records require --priority".to_string())?;
+            let weight = input.weight
+                .ok_or_else(|| "SRV records require --weight".to_string())?;
+            let port = input.port
+                .ok_or_else(|| "SRV records require --port".to_string())?;
+            Ok(json!({
+                "type": "SRV",
+                "data": CfRecordData {
+                    priority: Some(priority),
+                    weight: Some(weight),
+                    port: Some(port),
+                    target: Some(content.to_string()),
+                    ..Default::default()
+                }
+            }))
+        }
+        "CAA" => {
+            // Presentation format: <flags> <tag> <value>, e.g. 0 issue "letsencrypt.org"
+            let mut parts = content.splitn(3, char::is_whitespace);
+            let flags = parts.next()
+                .and_then(|f| f.parse::<u8>().ok())
+                .ok_or_else(|| format!("CAA content must be 'flags tag value', got: {}", content))?;
+            let tag = parts.next().unwrap_or("");
+            if !matches!(tag, "issue" | "issuewild" | "iodef") {
+                return Err(format!("CAA tag must be issue, issuewild or iodef, got: {}", tag));
+            }
+            let value = parts.next().unwrap_or("").trim().trim_matches('"');
+            if value.is_empty() {
+                return Err("CAA value must not be empty".to_string());
+            }
+            Ok(json!({
+                "type": "CAA",
+                "data": CfRecordData {
+                    flags: Some(flags),
+                    tag: Some(tag.to_string()),
+                    value: Some(value.to_string()),
+                    ..Default::default()
+                }
+            }))
+        }
+        other => Err(format!("Unsupported record type: {}", other)),
+    }
+}
+
+/// Content in zone-file presentation form (MX and SRV carry their priority in front)
+fn presentation_content(r: &CfDnsRecord) -> String {
+    match (r.record_type.as_str(), &r.data) {
+        ("MX", _) => format!("{} {}", r.priority.unwrap_or(0), r.content),
+        ("SRV", Some(d)) => format!(
+            "{} {} {} {}",
+            d.priority.unwrap_or(0),
+            d.weight.unwrap_or(0),
+            d.port.unwrap_or(0),
+            d.target.as_deref().unwrap_or(""),
+        ),
+        _ => r.content.clone(),
+    }
+}
+
+/// Parse presentation-form content (as stored in registry.json) into record input
+fn input_from_presentation(record_type: &str, content: &str) -> std::result::Result<RecordInput, String> {
+    let fields: Vec<&str> = content.split_whitespace().collect();
+    let number = |s: &str| s.parse::<u16>().map_err(|_| format!("Invalid {} content: {}", record_type, content));
+
+    let input = match record_type {
+        "MX" if fields.len() == 2 => RecordInput {
+            record_type: "MX".to_string(),
+            content: fields[1].to_string(),
+            priority: Some(number(fields[0])?),
+            weight: None,
+            port: None,
+        },
+        "MX" => return Err(format!("MX content must be 'priority host', got: {}", content)),
+        "SRV" if fields.len() == 4 => RecordInput {
+            record_type: "SRV".to_string(),
+            content: fields[3].to_string(),
+            priority: Some(number(fields[0])?),
+            weight: Some(number(fields[1])?),
+            port: Some(number(fields[2])?),
+        },
+        "SRV" => return Err(format!("SRV content must be 'priority weight port target', got: {}", content)),
+        _ => RecordInput {
+            record_type: record_type.to_string(),
+            content: content.to_string(),
+            priority: None,
+            weight: None,
+            port: None,
+        },
+    };
+    Ok(input)
+}
+
+/// Normalize presentation content so equivalent values compare equal
+pub(crate) fn normalize_content(record_type: &str, content: &str) -> String {
+    match record_type {
+        "A" | "AAAA" => content.trim().parse::<std::net::IpAddr>()
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|_| content.trim().to_string()),
+        "TXT" => txt_value(content),
+        _ => content.split_whitespace()
+            .map(|f| f.trim_matches('"').trim_end_matches('.').to_lowercase())
             .collect::<Vec<_>>()
-            .join(", ");
-        out.error(PebbleError::ext("CF_API_ERROR", &err_msg)
-            .with_op("dns.list")
-            .with_details(json!({"domain": domain})));
-        return Ok(());
+            .join(" "),
+    }
+}
+
+/// TXT content as the single value resolvers serve: quoted character-strings
+/// (`"part one" "part two"`, as Cloudflare stores long values) are unescaped and joined
+pub(crate) fn txt_value(content: &str) -> String {
+    let content = content.trim();
+    if !content.starts_with('"') {
+        return content.to_string();
+    }
+
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => value.extend(chars.next()),
+            '"' => quoted = !quoted,
+            c if quoted => value.push(c),
+            _ => {}
+        }
+    }
+    value
+}
+
+/// JSON view of a record for command output
+fn record_json(r: &CfDnsRecord) -> serde_json::Value {
+    let mut v = json!({
+        "id": r.id,
+        "type": r.record_type,
+        "name": r.name,
+        "content": r.content,
+        "proxied": r.proxied,
+        "ttl": r.ttl
+    });
+    if let Some(priority) = r.priority {
+        v["priority"] = json!(priority);
+    }
+    if let Some(data) = &r.data {
+        v["data"] = json!(data);
+    }
+    if let Some(comment) = &r.comment {
+        v["comment"] = json!(comment);
+    }
+    v["tags"] = json!(r.tags);
+    if let Some(created_on) = &r.created_on {
+        v["created_on"] = json!(created_on);
+    }
+    if let Some(modified_on) = &r.modified_on {
+        v["modified_on"] = json!(modified_on);
+    }
+    v
+}
+
+// ============ Shared with other commands ============
+
+/// Fetch every record in a domain's zone in registry.json form, with the
+/// Cloudflare comment as `desc` and the live proxy status and TTL.
+/// Returns the zone ID alongside the records.
+pub(crate) async fn fetch_registry_records(domain: &str) -> Result<(String, Vec<config::DnsRecord>)> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
+    let page = list_records(&client, &zone_id, &ListQuery::default()).await?;
+
+    let records = page.items
+        .iter()
+        .map(|r| config::DnsRecord {
+            record_type: r.record_type.clone(),
+            name: relative_name(domain, &r.name),
+            content: presentation_content(r),
+            desc: r.comment.clone().unwrap_or_default(),
+            proxied: Some(r.proxied),
+            ttl: Some(r.ttl),
+        })
+        .collect();
+
+    Ok((zone_id, records))
+}
+
+/// Point `name` at `target` with a proxied CNAME, creating or correcting it.
+/// Other record types at the name are a conflict, since a CNAME cannot share it.
+pub(crate) async fn ensure_proxied_cname(
+    client: &Client,
+    zone_id: &str,
+    domain: &str,
+    name: &str,
+    target: &str,
+    comment: &str,
+) -> std::result::Result<serde_json::Value, PebbleError> {
+    let fqdn = fqdn(domain, name);
+    let records = find_records(client, zone_id, &fqdn, None).await
+        .map_err(|e| e.to_pebble().with_details(json!({"domain": domain})))?;
+
+    if records.iter().any(|r| r.record_type != "CNAME") {
+        return Err(PebbleError::input("HOSTNAME_IN_USE", &format!("{} already has other DNS records; remove them first", fqdn))
+            .with_details(json!({"fqdn": fqdn, "records": records.iter().map(record_json).collect::<Vec<_>>()})));
+    }
+
+    let (action, record) = match records.into_iter().next() {
+        Some(r) if r.proxied && normalize_content("CNAME", &r.content) == normalize_content("CNAME", target) => ("unchanged", r),
+        Some(r) => {
+            let body = json!({"content": target, "proxied": true, "comment": comment});
+            let updated = patch_record(client, zone_id, &r.id, &body).await
+                .map_err(|e| e.to_pebble().with_details(json!({"fqdn": fqdn, "record_id": r.id})))?;
+            ("updated", updated)
+        }
+        None => {
+            let body = json!({"type": "CNAME", "name": name, "content": target, "proxied": true, "ttl": 1, "comment": comment});
+            let created = create_record(client, zone_id, &body).await
+                .map_err(|e| e.to_pebble().with_details(json!({"fqdn": fqdn})))?;
+            ("created", created)
+        }
+    };
+
+    Ok(json!({"action": action, "fqdn": fqdn, "record_id": record.id, "content": record.content}))
+}
+
+/// Delete the CNAME at `fqdn` if it points at `target`; returns whether one was deleted
+pub(crate) async fn delete_cname_to(
+    client: &Client,
+    zone_id: &str,
+    fqdn: &str,
+    target: &str,
+) -> std::result::Result<bool, PebbleError> {
+    let records = find_records(client, zone_id, fqdn, Some("CNAME")).await
+        .map_err(|e| e.to_pebble().with_details(json!({"fqdn": fqdn})))?;
+
+    let mut deleted = false;
+    for r in records.iter().filter(|r| normalize_content("CNAME", &r.content) == normalize_content("CNAME", target)) {
+        delete_record(client, zone_id, &r.id).await
+            .map_err(|e| e.to_pebble().with_details(json!({"fqdn": fqdn, "record_id": r.id})))?;
+        deleted = true;
     }
+    Ok(deleted)
+}
+
+// ============ Commands ============
+
+async fn list(domain: &str, query: &ListQuery, out: &Output) -> Result<()> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
 
-    let records = cf_resp.result.unwrap_or_default();
+    out.log("info", &format!("Fetching DNS records for {}", domain));
+
+    let page = match list_records(&client, &zone_id, query).await {
+        Ok(p) => p,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("dns.list")
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
+
+    let records = page.items;
 
     out.result(json!({
         "success": true,
         "domain": domain,
         "count": records.len(),
-        "records": records.iter().map(|r| json!({
-            "id": r.id,
-            "type": r.record_type,
-            "name": r.name,
-            "content": r.content,
-            "proxied": r.proxied
-        })).collect::<Vec<_>>()
+        "total": page.total_count,
+        "truncated": records.len() < page.total_count,
+        "filters": query,
+        "records": records.iter().map(record_json).collect::<Vec<_>>()
     }));
 
     Ok(())
 }
 
-async fn get(domain: &str, name: &str, out: &Output) -> Result<()> {
-    let (zone_id, api_token) = config::get_cf_credentials(domain)?;
+async fn get(domain: &str, name: &str, record_type: Option<&str>, out: &Output) -> Result<()> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
 
-    let fqdn = if name == "@" || name == domain {
-        domain.to_string()
-    } else {
-        format!("{}.{}", name, domain)
-    };
+    let fqdn = fqdn(domain, name);
 
     out.log("info", &format!("Looking up DNS record: {}", fqdn));
 
-    let client = reqwest::Client::new();
-    let url = format!("{}/zones/{}/dns_records?name={}", CF_API_BASE, zone_id, fqdn);
+    let records = match find_records(&client, &zone_id, &fqdn, record_type).await {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("dns.get")
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
+
+    let mut result = json!({
+        "success": true,
+        "exists": !records.is_empty(),
+        "fqdn": fqdn,
+        "count": records.len(),
+        "records": records.iter().map(record_json).collect::<Vec<_>>()
+    });
+    // Single matches keep the `record` field older callers read
+    if let [only] = records.as_slice() {
+        result["record"] = record_json(only);
+    }
+    out.result(result);
 
-    let resp = client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", api_token))
-        .header("Content-Type", "application/json")
-        .send()
-        .await
-        .context("Failed to connect to Cloudflare API")?;
+    Ok(())
+}
 
-    let cf_resp: CfResponse<Vec<CfDnsRecord>> = resp.json().await
-        .context("Failed to parse Cloudflare response")?;
+#[allow(clippy::too_many_arguments)]
+async fn create(
+    domain: &str,
+    name: &str,
+    input: &RecordInput,
+    meta: &RecordMeta,
+    upsert: bool,
+    wait: &WaitArgs,
+    out: &Output,
+) -> Result<()> {
+    let mut body = match build_record_fields(name, input) {
+        Ok(b) => b,
+        Err(msg) => {
+            out.error(PebbleError::input("INVALID_RECORD", &msg)
+                .with_op("dns.create")
+                .with_details(json!({"type": input.record_type, "content": input.content})));
+            return Ok(());
+        }
+    };
 
-    if !cf_resp.success {
-        let err_msg = cf_resp.errors.iter()
-            .map(|e| format!("[{}] {}", e.code, e.message))
-            .collect::<Vec<_>>()
-            .join(", ");
-        out.error(PebbleError::ext("CF_API_ERROR", &err_msg)
-            .with_op("dns.get")
-            .with_details(json!({"domain": domain})));
+    let proxied = meta.proxied.unwrap_or(false);
+    if proxied && !matches!(input.record_type.as_str(), "A" | "AAAA" | "CNAME") {
+        out.error(PebbleError::input("NOT_PROXIABLE", &format!("{} records cannot be proxied", input.record_type))
+            .with_op("dns.create"));
         return Ok(());
     }
 
-    let records = cf_resp.result.unwrap_or_default();
+    let (client, zone_id) = Client::for_domain(domain).await?;
+    let fqdn = fqdn(domain, name);
 
-    if records.is_empty() {
-        out.result(json!({
-            "success": true,
-            "exists": false,
-            "fqdn": fqdn
-        }));
-    } else {
-        let r = &records[0];
-        out.result(json!({
-            "success": true,
-            "exists": true,
-            "fqdn": fqdn,
-            "record": {
-                "id": r.id,
-                "type": r.record_type,
-                "name": r.name,
-                "content": r.content,
-                "proxied": r.proxied
+    if upsert {
+        let mut existing = match find_records(&client, &zone_id, &fqdn, Some(&input.record_type)).await {
+            Ok(r) => r,
+            Err(e) => {
+                out.error(e.to_pebble()
+                    .with_op("dns.create")
+                    .with_details(json!({"domain": domain})));
+                return Ok(());
             }
-        }));
-    }
+        };
+        if existing.len() > 1 {
+            out.error(ambiguous(&fqdn, &existing, "cf dns update --id").with_op("dns.create"));
+            return Ok(());
+        }
 
-    Ok(())
-}
+        if let Some(existing) = existing.pop() {
+            out.log("info", &format!("Record exists, updating in place: {} {}", input.record_type, fqdn));
 
-async fn create(domain: &str, name: &str, ip: &str, desc: &str, proxied: bool, out: &Output) -> Result<()> {
-    let (zone_id, api_token) = config::get_cf_credentials(domain)?;
+            let changes = RecordChanges {
+                content: Some(input.content.clone()),
+                priority: input.priority,
+                weight: input.weight,
+                port: input.port,
+                proxied: meta.proxied,
+                ttl: meta.ttl,
+                comment: meta.desc.clone(),
+                tags: meta.tags.clone(),
+                untag: Vec::new(),
+            };
+            return apply_update(&client, &zone_id, domain, &fqdn, &existing, &changes, wait, "dns.create", out).await;
+        }
+    }
 
-    let fqdn = if name == "@" || name == domain {
-        domain.to_string()
-    } else {
-        format!("{}.{}", name, domain)
-    };
+    out.log("info", &format!("Creating DNS {} record: {} -> {}", input.record_type, fqdn, input.content));
 
-    out.log("info", &format!("Creating DNS A record: {} -> {}", fqdn, ip));
+    body["name"] = json!(name);
+    body["ttl"] = json!(meta.ttl.unwrap_or(1));  // 1 = auto
+    body["proxied"] = json!(proxied);
+    body["comment"] = json!(meta.desc.as_deref().unwrap_or(""));
+    if !meta.tags.is_empty() {
+        body["tags"] = json!(meta.tags);
+    }
 
-    let client = reqwest::Client::new();
-    let url = format!("{}/zones/{}/dns_records", CF_API_BASE, zone_id);
+    let record = match create_record(&client, &zone_id, &body).await {
+        Ok(r) => r,
+        // Check if record already exists
+        Err(e) if e.has_code(81057) || e.to_string().contains("already exists") => {
+            out.error(PebbleError::input("RECORD_EXISTS", "DNS record already exists (use --upsert to update it)")
+                .with_op("dns.create")
+                .with_details(json!({"fqdn": fqdn, "type": input.record_type, "content": input.content})));
+            return Ok(());
+        }
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("dns.create")
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
 
-    let body = json!({
-        "type": "A",
-        "name": name,
-        "content": ip,
-        "ttl": 1,  // Auto
+    let mut result = json!({
+        "success": true,
+        "action": "created",
+        "record_id": record.id,
+        "fqdn": fqdn,
+        "type": record.record_type,
+        "content": record.content,
         "proxied": proxied,
-        "comment": desc
+        "ttl": record.ttl,
+        "tags": record.tags
     });
+    if matches!(record.record_type.as_str(), "A" | "AAAA") {
+        result["ip"] = json!(record.content);
+    }
+    if let Some(priority) = record.priority {
+        result["priority"] = json!(priority);
+    }
+    if let Some(data) = &record.data {
+        result["data"] = json!(data);
+    }
+    if upsert {
+        result["before"] = serde_json::Value::Null;
+        result["after"] = record_json(&record);
+    }
 
-    let resp = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", api_token))
-        .header("Content-Type", "application/json")
-        .json(&body)
-        .send()
-        .await
-        .context("Failed to connect to Cloudflare API")?;
-
-    let cf_resp: CfResponse<CfDnsRecord> = resp.json().await
-        .context("Failed to parse Cloudflare response")?;
-
-    if !cf_resp.success {
-        let err_msg = cf_resp.errors.iter()
-            .map(|e| format!("[{}] {}", e.code, e.message))
-            .collect::<Vec<_>>()
-            .join(", ");
+    match verify::after_change(domain, &fqdn, verify::query_type(&record), &Expect::served(&record), wait, out).await {
+        Ok(Some(report)) => result["propagation"] = report,
+        Ok(None) => {}
+        Err(e) => {
+            out.error(e.with_op("dns.create").with_details(json!({"record_id": record.id})));
+            return Ok(());
+        }
+    }
+    out.result(result);
 
-        // Check if record already exists
-        if err_msg.contains("already exists") || cf_resp.errors.iter().any(|e| e.code == 81057) {
-            out.error(PebbleError::input("RECORD_EXISTS", "DNS record already exists")
-                .with_op("dns.create")
-                .with_details(json!({"fqdn": fqdn, "ip": ip})));
-        } else {
-            out.error(PebbleError::ext("CF_API_ERROR", &err_msg)
-                .with_op("dns.create")
+    Ok(())
+}
+
+async fn update(
+    domain: &str,
+    name: &str,
+    selector: &Selector<'_>,
+    changes: &RecordChanges,
+    wait: &WaitArgs,
+    out: &Output,
+) -> Result<()> {
+    if changes.is_empty() {
+        out.error(PebbleError::input("NO_CHANGES", "Nothing to update: pass --content, --priority, --weight, --port, --proxied, --ttl, --comment, --tag or --untag")
+            .with_op("dns.update"));
+        return Ok(());
+    }
+
+    let (client, zone_id) = Client::for_domain(domain).await?;
+    let fqdn = fqdn(domain, name);
+
+    out.log("info", &format!("Updating DNS record: {}", fqdn));
+
+    let mut records = match select_records(&client, &zone_id, &fqdn, selector).await {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("dns.update")
                 .with_details(json!({"domain": domain})));
+            return Ok(());
         }
+    };
+
+    if records.len() > 1 {
+        out.error(ambiguous(&fqdn, &records, "--type or --id").with_op("dns.update"));
         return Ok(());
     }
+    let Some(existing) = records.pop() else {
+        out.error(PebbleError::input("NOT_FOUND", &format!("DNS record not found: {}", fqdn))
+            .with_op("dns.update")
+            .with_details(json!({"fqdn": fqdn, "type": selector.record_type, "id": selector.id})));
+        return Ok(());
+    };
 
-    let record = cf_resp.result.unwrap();
+    apply_update(&client, &zone_id, domain, &fqdn, &existing, changes, wait, "dns.update", out).await
+}
 
-    out.result(json!({
+/// PATCH `existing` and report the before/after values
+#[allow(clippy::too_many_arguments)]
+async fn apply_update(
+    client: &Client,
+    zone_id: &str,
+    domain: &str,
+    fqdn: &str,
+    existing: &CfDnsRecord,
+    changes: &RecordChanges,
+    wait: &WaitArgs,
+    op: &str,
+    out: &Output,
+) -> Result<()> {
+    let name = relative_name(domain, fqdn);
+    let body = match build_patch_body(&name, existing, changes) {
+        Ok(b) => b,
+        Err(msg) => {
+            out.error(PebbleError::input("INVALID_RECORD", &msg)
+                .with_op(op)
+                .with_details(json!({"fqdn": fqdn, "type": existing.record_type})));
+            return Ok(());
+        }
+    };
+
+    let updated = match patch_record(client, zone_id, &existing.id, &body).await {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op(op)
+                .with_details(json!({"fqdn": fqdn, "record_id": existing.id})));
+            return Ok(());
+        }
+    };
+
+    let mut result = json!({
         "success": true,
-        "record_id": record.id,
+        "action": "updated",
+        "record_id": updated.id,
         "fqdn": fqdn,
-        "ip": ip,
-        "proxied": proxied
-    }));
+        "before": record_json(existing),
+        "after": record_json(&updated)
+    });
+
+    match verify::after_change(domain, fqdn, verify::query_type(&updated), &Expect::served(&updated), wait, out).await {
+        Ok(Some(report)) => result["propagation"] = report,
+        Ok(None) => {}
+        Err(e) => {
+            out.error(e.with_op(op).with_details(json!({"record_id": updated.id})));
+            return Ok(());
+        }
+    }
+    out.result(result);
 
     Ok(())
 }
 
-async fn delete(domain: &str, name: &str, out: &Output) -> Result<()> {
-    let (zone_id, api_token) = config::get_cf_credentials(domain)?;
+async fn delete(domain: &str, name: &str, selector: &Selector<'_>, wait: &WaitArgs, out: &Output) -> Result<()> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
 
-    let fqdn = if name == "@" || name == domain {
-        domain.to_string()
-    } else {
-        format!("{}.{}", name, domain)
-    };
+    let fqdn = fqdn(domain, name);
 
     out.log("info", &format!("Deleting DNS record: {}", fqdn));
 
     // First, find the record ID
-    let client = reqwest::Client::new();
-    let search_url = format!("{}/zones/{}/dns_records?name={}", CF_API_BASE, zone_id, fqdn);
-
-    let search_resp = client
-        .get(&search_url)
-        .header("Authorization", format!("Bearer {}", api_token))
-        .header("Content-Type", "application/json")
-        .send()
-        .await
-        .context("Failed to connect to Cloudflare API")?;
-
-    let search_result: CfResponse<Vec<CfDnsRecord>> = search_resp.json().await
-        .context("Failed to parse Cloudflare response")?;
-
-    let records = search_result.result.unwrap_or_default();
+    let records = match select_records(&client, &zone_id, &fqdn, selector).await {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("dns.delete")
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
 
     if records.is_empty() {
         out.result(json!({
@@ -266,39 +1002,37 @@ async fn delete(domain: &str, name: &str, out: &Output) -> Result<()> {
         }));
         return Ok(());
     }
+    if records.len() > 1 {
+        out.error(ambiguous(&fqdn, &records, "--type, --content or --id").with_op("dns.delete"));
+        return Ok(());
+    }
 
     // Delete the record
-    let record_id = &records[0].id;
-    let delete_url = format!("{}/zones/{}/dns_records/{}", CF_API_BASE, zone_id, record_id);
-
-    let delete_resp = client
-        .delete(&delete_url)
-        .header("Authorization", format!("Bearer {}", api_token))
-        .header("Content-Type", "application/json")
-        .send()
-        .await
-        .context("Failed to connect to Cloudflare API")?;
-
-    let delete_result: CfResponse<serde_json::Value> = delete_resp.json().await
-        .context("Failed to parse Cloudflare response")?;
-
-    if !delete_result.success {
-        let err_msg = delete_result.errors.iter()
-            .map(|e| format!("[{}] {}", e.code, e.message))
-            .collect::<Vec<_>>()
-            .join(", ");
-        out.error(PebbleError::ext("CF_API_ERROR", &err_msg)
+    let deleted = &records[0];
+    let record_id = &deleted.id;
+    if let Err(e) = delete_record(&client, &zone_id, record_id).await {
+        out.error(e.to_pebble()
             .with_op("dns.delete")
             .with_details(json!({"domain": domain})));
         return Ok(());
     }
 
-    out.result(json!({
+    let mut result = json!({
         "success": true,
         "deleted": true,
         "fqdn": fqdn,
         "record_id": record_id
-    }));
+    });
+
+    match verify::after_change(domain, &fqdn, verify::query_type(deleted), &Expect::removed(deleted), wait, out).await {
+        Ok(Some(report)) => result["propagation"] = report,
+        Ok(None) => {}
+        Err(e) => {
+            out.error(e.with_op("dns.delete").with_details(json!({"record_id": record_id})));
+            return Ok(());
+        }
+    }
+    out.result(result);
 
     Ok(())
 }
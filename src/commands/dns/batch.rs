@@ -0,0 +1,421 @@
+//! Bulk record changes from a JSON or CSV file, with optional all-or-nothing rollback
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use super::{
+    build_patch_body, build_record_fields, create_record, delete_record, fqdn, patch_record, relative_name,
+    select_records, CfDnsRecord, RecordChanges, RecordInput, Selector,
+};
+use crate::cloudflare::{ApiError, Client};
+use crate::output::{Output, PebbleError};
+use crate::util::split_csv_line;
+
+/// One line of a batch file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Operation {
+    /// create, update or delete
+    op: String,
+    name: String,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    record_type: Option<String>,
+    /// Record ID to update or delete instead of matching by name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    weight: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proxied: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ttl: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+impl Operation {
+    fn record_type(&self) -> String {
+        self.record_type.as_deref().unwrap_or("A").to_uppercase()
+    }
+
+    fn changes(&self) -> RecordChanges {
+        RecordChanges {
+            content: self.content.clone(),
+            priority: self.priority,
+            weight: self.weight,
+            port: self.port,
+            proxied: self.proxied,
+            ttl: self.ttl,
+            comment: self.comment.clone(),
+            tags: Vec::new(),
+            untag: Vec::new(),
+        }
+    }
+
+    /// API body for a create
+    fn create_body(&self) -> std::result::Result<serde_json::Value, String> {
+        let input = RecordInput {
+            record_type: self.record_type(),
+            content: self.content.clone().ok_or("create needs content")?,
+            priority: self.priority,
+            weight: self.weight,
+            port: self.port,
+        };
+        let proxied = self.proxied.unwrap_or(false);
+        if proxied && !matches!(input.record_type.as_str(), "A" | "AAAA" | "CNAME") {
+            return Err(format!("{} records cannot be proxied", input.record_type));
+        }
+
+        let mut body = build_record_fields(&self.name, &input)?;
+        body["name"] = json!(self.name);
+        body["ttl"] = json!(self.ttl.unwrap_or(1));
+        body["proxied"] = json!(proxied);
+        if let Some(comment) = &self.comment {
+            body["comment"] = json!(comment);
+        }
+        Ok(body)
+    }
+
+    /// Check an operation before anything is sent
+    fn validate(&self) -> std::result::Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name is required".into());
+        }
+        match self.op.as_str() {
+            "create" => self.create_body().map(|_| ()),
+            "update" => {
+                if self.changes().is_empty() {
+                    return Err("update needs content, priority, weight, port, proxied, ttl or comment".into());
+                }
+                Ok(())
+            }
+            "delete" => Ok(()),
+            other => Err(format!("unknown op '{}' (expected create, update or delete)", other)),
+        }
+    }
+}
+
+/// How to revert a completed operation
+enum Undo {
+    /// Delete a record the batch created
+    Delete { id: String },
+    /// PATCH an updated record back to its previous state
+    Restore { id: String, body: serde_json::Value },
+    /// Re-create a record the batch deleted
+    Recreate { body: serde_json::Value },
+}
+
+/// Why an operation failed: its own fields or target, or the API call itself
+enum Failure {
+    Input(String),
+    Api(ApiError),
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure::Input(message)
+    }
+}
+
+impl From<ApiError> for Failure {
+    fn from(err: ApiError) -> Self {
+        Failure::Api(err)
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::Input(message) => f.write_str(message),
+            Failure::Api(err) => err.fmt(f),
+        }
+    }
+}
+
+/// Result of one operation: the affected record ID and how to undo it
+type Outcome = std::result::Result<(String, Undo), Failure>;
+
+// ============ File Parsing ============
+
+fn load(file: &str) -> Result<std::result::Result<Vec<Operation>, String>> {
+    let text = std::fs::read_to_string(file).with_context(|| format!("Failed to read {}", file))?;
+
+    if file.to_lowercase().ends_with(".csv") {
+        return Ok(parse_csv(&text));
+    }
+    Ok(serde_json::from_str::<Vec<Operation>>(&text).map_err(|e| format!("Invalid batch JSON: {}", e)))
+}
+
+/// Parse CSV with a header row naming the Operation fields (op,name,type,content,...)
+fn parse_csv(text: &str) -> std::result::Result<Vec<Operation>, String> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty() && !l.starts_with('#'));
+    let header: Vec<String> = match lines.next() {
+        Some(h) => split_csv_line(h).into_iter().map(|c| c.trim().to_lowercase()).collect(),
+        None => return Ok(Vec::new()),
+    };
+
+    lines.enumerate()
+        .map(|(i, line)| {
+            let mut row = serde_json::Map::new();
+            for (key, value) in header.iter().zip(split_csv_line(line)) {
+                let value = value.trim();
+                if value.is_empty() {
+                    continue;
+                }
+                let value = match key.as_str() {
+                    "priority" | "weight" | "port" | "ttl" => json!(value.parse::<u64>()
+                        .map_err(|_| format!("row {}: {} must be a number, got '{}'", i + 2, key, value))?),
+                    "proxied" => match value.to_lowercase().as_str() {
+                        "true" => json!(true),
+                        "false" => json!(false),
+                        _ => return Err(format!("row {}: proxied must be true or false, got '{}'", i + 2, value)),
+                    },
+                    _ => json!(value),
+                };
+                row.insert(key.clone(), value);
+            }
+            serde_json::from_value(serde_json::Value::Object(row)).map_err(|e| format!("row {}: {}", i + 2, e))
+        })
+        .collect()
+}
+
+// ============ Execution ============
+
+/// Find the single record an update/delete refers to
+async fn target(client: &Client, zone_id: &str, domain: &str, op: &Operation) -> std::result::Result<CfDnsRecord, Failure> {
+    let fqdn = fqdn(domain, &op.name);
+    let record_type = op.record_type.as_ref().map(|t| t.to_uppercase());
+    let selector = Selector {
+        id: op.id.as_deref(),
+        record_type: record_type.as_deref(),
+        // For deletes, content picks one record out of a round-robin set
+        content: op.content.as_deref().filter(|_| op.op == "delete"),
+    };
+    let mut records = select_records(client, zone_id, &fqdn, &selector).await?;
+
+    match records.len() {
+        0 => Err(format!("no matching record for {}", fqdn).into()),
+        1 => Ok(records.remove(0)),
+        n => Err(format!("{} records match {}; add type, content or id", n, fqdn).into()),
+    }
+}
+
+/// Body that recreates or restores `r` as it was
+fn restore_body(r: &CfDnsRecord) -> serde_json::Value {
+    let mut body = json!({
+        "type": r.record_type,
+        "name": r.name,
+        "proxied": r.proxied,
+        "ttl": r.ttl,
+        "comment": r.comment,
+        "tags": r.tags
+    });
+    match (&r.data, r.record_type.as_str()) {
+        (Some(data), "SRV" | "CAA") => body["data"] = json!(data),
+        _ => body["content"] = json!(r.content),
+    }
+    if let Some(priority) = r.priority {
+        body["priority"] = json!(priority);
+    }
+    body
+}
+
+async fn run_one(client: Client, zone_id: String, domain: String, op: Operation) -> Outcome {
+    match op.op.as_str() {
+        "create" => {
+            let body = op.create_body()?;
+            let record = create_record(&client, &zone_id, &body).await?;
+            Ok((record.id.clone(), Undo::Delete { id: record.id }))
+        }
+        "update" => {
+            let existing = target(&client, &zone_id, &domain, &op).await?;
+            let name = relative_name(&domain, &existing.name);
+            let body = build_patch_body(&name, &existing, &op.changes())?;
+            let updated = patch_record(&client, &zone_id, &existing.id, &body).await?;
+            Ok((updated.id.clone(), Undo::Restore { id: updated.id, body: restore_body(&existing) }))
+        }
+        _ => {
+            let existing = target(&client, &zone_id, &domain, &op).await?;
+            delete_record(&client, &zone_id, &existing.id).await?;
+            Ok((existing.id.clone(), Undo::Recreate { body: restore_body(&existing) }))
+        }
+    }
+}
+
+/// Revert a completed operation, returning the ID of the record it touched
+async fn undo(client: &Client, zone_id: &str, undo: &Undo) -> std::result::Result<String, String> {
+    match undo {
+        Undo::Delete { id } => delete_record(client, zone_id, id).await.map(|_| id.clone()),
+        Undo::Restore { id, body } => patch_record(client, zone_id, id, body).await.map(|r| r.id),
+        Undo::Recreate { body } => create_record(client, zone_id, body).await.map(|r| r.id),
+    }
+    .map_err(|e| e.to_string())
+}
+
+pub(super) async fn batch(domain: &str, file: &str, atomic: bool, concurrency: usize, out: &Output) -> Result<()> {
+    let ops = match load(file)? {
+        Ok(ops) => ops,
+        Err(msg) => {
+            out.error(PebbleError::input("PARSE_FAIL", &msg)
+                .with_op("dns.batch")
+                .with_details(json!({"file": file})));
+            return Ok(());
+        }
+    };
+
+    // Reject the whole file before sending anything if any operation is malformed
+    let invalid: Vec<_> = ops.iter()
+        .enumerate()
+        .filter_map(|(i, op)| op.validate().err().map(|e| json!({"index": i, "operation": op, "error": e})))
+        .collect();
+    if !invalid.is_empty() {
+        out.error(PebbleError::input("INVALID_BATCH", &format!("{} of {} operation(s) are invalid", invalid.len(), ops.len()))
+            .with_op("dns.batch")
+            .with_details(json!({"file": file, "invalid": invalid})));
+        return Ok(());
+    }
+
+    let (client, zone_id) = Client::for_domain(domain).await?;
+
+    out.log("info", &format!("Running {} operation(s) on {} ({} at a time)", ops.len(), domain, concurrency));
+
+    // Operations on the same name run one after another in file order (e.g. delete
+    // an A record before creating a CNAME there); different names run in parallel
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    for (i, op) in ops.iter().enumerate() {
+        let key = fqdn(domain, &op.name).to_lowercase();
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, group)) => group.push(i),
+            None => groups.push((key, vec![i])),
+        }
+    }
+
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    // Set as soon as an operation fails, so later launches and group members see it
+    let any_failed = Arc::new(AtomicBool::new(false));
+    let mut tasks = JoinSet::new();
+    let mut outcomes: Vec<Option<Outcome>> = ops.iter().map(|_| None).collect();
+    // Completion order, so rollback can run newest first
+    let mut completed: Vec<usize> = Vec::new();
+    let mut done = 0;
+    let total = ops.len().max(1);
+
+    let mut finish = |results: Vec<(usize, Outcome)>, outcomes: &mut Vec<Option<Outcome>>| {
+        for (i, outcome) in results {
+            done += 1;
+            let op = &ops[i];
+            let status = if outcome.is_ok() { "ok" } else { "failed" };
+            out.progress((done * 100 / total) as u8, &format!("{} {} {} {}", status, op.op, op.record_type(), op.name));
+            if outcome.is_ok() {
+                completed.push(i);
+            }
+            outcomes[i] = Some(outcome);
+        }
+    };
+
+    for (_, group) in groups {
+        let permit = semaphore.clone().acquire_owned().await?;
+        while let Some(joined) = tasks.try_join_next() {
+            finish(joined?, &mut outcomes);
+        }
+        // In atomic mode, stop starting new work once something has failed
+        if atomic && any_failed.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let group: Vec<(usize, Operation)> = group.into_iter().map(|i| (i, ops[i].clone())).collect();
+        let (client, zone_id, domain) = (client.clone(), zone_id.clone(), domain.to_string());
+        let any_failed = Arc::clone(&any_failed);
+        tasks.spawn(async move {
+            let mut results = Vec::new();
+            for (i, op) in group {
+                if atomic && any_failed.load(Ordering::SeqCst) {
+                    break;
+                }
+                let outcome = run_one(client.clone(), zone_id.clone(), domain.clone(), op).await;
+                if outcome.is_err() {
+                    any_failed.store(true, Ordering::SeqCst);
+                }
+                results.push((i, outcome));
+            }
+            drop(permit);
+            results
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        finish(joined?, &mut outcomes);
+    }
+
+    let failed = outcomes.iter().flatten().filter(|o| o.is_err()).count();
+
+    let mut results: Vec<serde_json::Value> = ops.iter()
+        .zip(&outcomes)
+        .enumerate()
+        .map(|(i, (op, outcome))| match outcome {
+            Some(Ok((id, _))) => json!({"index": i, "operation": op, "status": "ok", "record_id": id}),
+            Some(Err(e)) => json!({"index": i, "operation": op, "status": "failed", "error": e.to_string()}),
+            None => json!({"index": i, "operation": op, "status": "skipped", "reason": "an earlier operation failed"}),
+        })
+        .collect();
+
+    if failed == 0 {
+        out.result(json!({
+            "success": true,
+            "domain": domain,
+            "count": ops.len(),
+            "results": results
+        }));
+        return Ok(());
+    }
+
+    let mut rollback_failed = 0;
+    if atomic {
+        out.log("warn", &format!("{} operation(s) failed; rolling back {} completed operation(s)", failed, completed.len()));
+        for &i in completed.iter().rev() {
+            let Some(Ok((_, how))) = &outcomes[i] else { continue };
+            match undo(&client, &zone_id, how).await {
+                Ok(id) => {
+                    results[i]["status"] = json!("rolled_back");
+                    results[i]["rollback_record_id"] = json!(id);
+                }
+                Err(e) => {
+                    rollback_failed += 1;
+                    results[i]["rollback_error"] = json!(e);
+                }
+            }
+        }
+    }
+
+    let message = if rollback_failed > 0 {
+        format!("{} of {} operation(s) failed and {} could not be rolled back", failed, ops.len(), rollback_failed)
+    } else if atomic {
+        format!("{} of {} operation(s) failed; completed operations were rolled back", failed, ops.len())
+    } else {
+        format!("{} of {} operation(s) failed", failed, ops.len())
+    };
+    // An API failure (rate limit, outage) keeps its category and retry advice;
+    // otherwise the operations themselves were wrong
+    let api_failure = outcomes.iter().flatten().find_map(|o| match o {
+        Err(Failure::Api(e)) => Some(e),
+        _ => None,
+    });
+    let err = match api_failure {
+        Some(e) => PebbleError { code: "BATCH_FAILED".into(), message: Some(message), ..e.to_pebble() },
+        None => PebbleError::input("BATCH_FAILED", &message),
+    };
+    out.error(err
+        .with_op("dns.batch")
+        .with_details(json!({"domain": domain, "atomic": atomic, "results": results})));
+
+    Ok(())
+}
@@ -0,0 +1,352 @@
+//! Dynamic DNS: keep A/AAAA records pointed at this host's public address
+
+use anyhow::Result;
+use serde_json::json;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::process::Command;
+use std::time::Duration;
+
+use super::{ambiguous, create_record, fqdn, patch_record, select_records, Selector};
+use crate::cloudflare::Client;
+use crate::output::{Output, PebbleError};
+
+/// Timeout for one echo request
+const ECHO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Options for `cf dns ddns`
+pub(super) struct Options {
+    /// ipv4, ipv6 or both
+    pub family: String,
+    pub echo_url: String,
+    pub interface: Option<String>,
+    /// Record to keep updated when several share the name
+    pub record_id: Option<String>,
+    /// Proxy the record; when unset an existing record keeps its proxy status
+    pub proxied: bool,
+    pub daemon: bool,
+    pub interval: u64,
+}
+
+#[derive(Clone, Copy)]
+enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    fn record_type(self) -> &'static str {
+        match self {
+            Family::V4 => "A",
+            Family::V6 => "AAAA",
+        }
+    }
+
+    /// Local bind address that forces connections over this family
+    fn unspecified(self) -> IpAddr {
+        match self {
+            Family::V4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            Family::V6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        }
+    }
+
+    fn matches(self, ip: &IpAddr) -> bool {
+        matches!((self, ip), (Family::V4, IpAddr::V4(_)) | (Family::V6, IpAddr::V6(_)))
+    }
+}
+
+fn families(family: &str) -> Vec<Family> {
+    match family {
+        "ipv6" => vec![Family::V6],
+        "both" => vec![Family::V4, Family::V6],
+        _ => vec![Family::V4],
+    }
+}
+
+// ============ Address Discovery ============
+
+/// Current public address for a family, from the interface or the echo endpoint
+async fn discover(family: Family, opts: &Options) -> std::result::Result<IpAddr, String> {
+    match &opts.interface {
+        Some(interface) => from_interface(interface, family),
+        None => from_echo(&opts.echo_url, family).await,
+    }
+}
+
+/// Ask an echo endpoint for our address, connecting over the given family.
+/// Accepts a bare address or Cloudflare trace output (`ip=...` line).
+async fn from_echo(url: &str, family: Family) -> std::result::Result<IpAddr, String> {
+    let http = reqwest::Client::builder()
+        .local_address(family.unspecified())
+        .timeout(ECHO_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let body = http.get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("{} request to {} failed: {}", family.record_type(), url, e))?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let text = body.lines()
+        .find_map(|l| l.strip_prefix("ip="))
+        .unwrap_or(&body)
+        .trim();
+    let ip: IpAddr = text.parse()
+        .map_err(|_| format!("{} did not return an IP address: {}", url, text.chars().take(80).collect::<String>()))?;
+
+    if !family.matches(&ip) {
+        return Err(format!("{} returned {} over {}", url, ip, family.record_type()));
+    }
+    Ok(ip)
+}
+
+/// First global address of a local interface (via `ip`, falling back to `ifconfig`)
+fn from_interface(interface: &str, family: Family) -> std::result::Result<IpAddr, String> {
+    let flag = match family {
+        Family::V4 => "-4",
+        Family::V6 => "-6",
+    };
+    let listing = Command::new("ip")
+        .args([flag, "-o", "addr", "show", "dev", interface, "scope", "global"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .or_else(|| Command::new("ifconfig").arg(interface).output().ok().filter(|o| o.status.success()))
+        .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+        .ok_or_else(|| format!("Cannot read addresses of interface {}", interface))?;
+
+    // Prefer stable addresses over temporary (privacy) IPv6 ones
+    let mut candidates: Vec<(bool, IpAddr)> = listing.lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            words.by_ref().find(|w| *w == "inet" || *w == "inet6")?;
+            let addr = words.next()?.split('/').next()?.split('%').next()?;
+            let ip: IpAddr = addr.parse().ok()?;
+            Some((line.contains("temporary"), ip))
+        })
+        .filter(|(_, ip)| family.matches(ip) && is_global(ip))
+        .collect();
+    candidates.sort_by_key(|(temporary, _)| *temporary);
+
+    candidates.first()
+        .map(|(_, ip)| *ip)
+        .ok_or_else(|| format!("No global {} address on interface {}", family.record_type(), interface))
+}
+
+/// Whether an address is publicly routable: not loopback, link-local, private
+/// (RFC 1918), shared CGNAT space (100.64/10) or an IPv6 unique local address
+fn is_global(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            let shared = a == 100 && (b & 0xc0) == 64;
+            !(v4.is_loopback() || v4.is_link_local() || v4.is_unspecified() || v4.is_private() || shared)
+        }
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            let link_local = (first & 0xffc0) == 0xfe80;
+            let unique_local = (first & 0xfe00) == 0xfc00;
+            !(v6.is_loopback() || v6.is_unspecified() || link_local || unique_local)
+        }
+    }
+}
+
+// ============ Sync ============
+
+/// Bring each family's record in line with the current address, once.
+/// A failing family is reported as a "failed" entry so changes already
+/// applied for the other family are not lost.
+async fn sync_once(
+    client: &Client,
+    zone_id: &str,
+    domain: &str,
+    name: &str,
+    opts: &Options,
+) -> Vec<(Family, std::result::Result<serde_json::Value, PebbleError>)> {
+    let mut changes = Vec::new();
+    for family in families(&opts.family) {
+        changes.push((family, sync_family(client, zone_id, domain, name, family, opts).await));
+    }
+    changes
+}
+
+async fn sync_family(
+    client: &Client,
+    zone_id: &str,
+    domain: &str,
+    name: &str,
+    family: Family,
+    opts: &Options,
+) -> std::result::Result<serde_json::Value, PebbleError> {
+    let fqdn = fqdn(domain, name);
+    let record_type = family.record_type();
+    let ip = discover(family, opts).await
+        .map_err(|e| PebbleError::net("IP_DISCOVERY_FAILED", &e))?;
+
+    let selector = Selector { id: opts.record_id.as_deref(), record_type: Some(record_type), content: None };
+    let mut records = select_records(client, zone_id, &fqdn, &selector).await
+        .map_err(|e| e.to_pebble().with_details(json!({"domain": domain})))?;
+    if records.len() > 1 {
+        return Err(ambiguous(&fqdn, &records, &format!("--record-id, or delete the extra {} records for the name", record_type)));
+    }
+    if let (Some(id), true) = (&opts.record_id, records.is_empty()) {
+        return Err(PebbleError::input("NOT_FOUND", &format!("No {} record {} at {}", record_type, id, fqdn)));
+    }
+
+    let change = match records.pop() {
+        None => {
+            let body = json!({
+                "type": record_type,
+                "name": name,
+                "content": ip.to_string(),
+                "ttl": 1,
+                "proxied": opts.proxied,
+                "comment": "ddns"
+            });
+            let record = create_record(client, zone_id, &body).await
+                .map_err(|e| e.to_pebble().with_details(json!({"fqdn": fqdn})))?;
+            json!({"type": record_type, "ip": ip, "previous": null, "action": "created", "record_id": record.id})
+        }
+        Some(existing) if existing.content.parse::<IpAddr>().ok() == Some(ip) && (!opts.proxied || existing.proxied) => {
+            json!({"type": record_type, "ip": ip, "previous": existing.content, "action": "unchanged", "record_id": existing.id})
+        }
+        Some(existing) => {
+            let mut body = json!({"content": ip.to_string()});
+            if opts.proxied {
+                body["proxied"] = json!(true);
+            }
+            let record = patch_record(client, zone_id, &existing.id, &body).await
+                .map_err(|e| e.to_pebble().with_details(json!({"fqdn": fqdn, "record_id": existing.id})))?;
+            json!({"type": record_type, "ip": ip, "previous": existing.content, "action": "updated", "record_id": record.id})
+        }
+    };
+    Ok(change)
+}
+
+/// Report entry for one family: its change, or the error that stopped it
+fn report_entry(family: Family, change: &std::result::Result<serde_json::Value, PebbleError>) -> serde_json::Value {
+    match change {
+        Ok(c) => c.clone(),
+        Err(e) => json!({"type": family.record_type(), "action": "failed", "error": e}),
+    }
+}
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+pub(super) async fn ddns(domain: &str, name: &str, opts: &Options, out: &Output) -> Result<()> {
+    if opts.record_id.is_some() && opts.family == "both" {
+        out.error(PebbleError::input("INVALID_ARGS", "--record-id names one record; use it with --family ipv4 or ipv6")
+            .with_op("dns.ddns"));
+        return Ok(());
+    }
+    let (client, zone_id) = Client::for_domain(domain).await?;
+    let fqdn = fqdn(domain, name);
+
+    if !opts.daemon {
+        let mut changes = sync_once(&client, &zone_id, domain, name, opts).await;
+        let records: Vec<serde_json::Value> = changes.iter()
+            .map(|(family, change)| report_entry(*family, change))
+            .collect();
+        let changed = changes.iter()
+            .any(|(_, c)| c.as_ref().is_ok_and(|c| c["action"] != "unchanged"));
+
+        match changes.iter().position(|(_, c)| c.is_err()) {
+            None => out.result(json!({
+                "success": true,
+                "fqdn": fqdn,
+                "changed": changed,
+                "records": records
+            })),
+            // Report the first failure, with every family's outcome so applied changes stay visible
+            Some(i) => {
+                let (_, Err(e)) = changes.swap_remove(i) else { unreachable!() };
+                out.error(e.with_op("dns.ddns").with_details(json!({
+                    "fqdn": fqdn,
+                    "changed": changed,
+                    "records": records
+                })));
+            }
+        }
+        return Ok(());
+    }
+
+    out.log("info", &format!("Keeping {} pointed at this host every {}s (Ctrl-C or SIGTERM to stop)", fqdn, opts.interval));
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    let mut cycles = 0;
+    let mut updates = 0;
+    let mut failures = 0;
+    loop {
+        cycles += 1;
+        // Transient failures are logged and retried on the next cycle
+        for (_, change) in sync_once(&client, &zone_id, domain, name, opts).await {
+            match change {
+                Ok(c) if c["action"] == "unchanged" => {}
+                Ok(c) => {
+                    updates += 1;
+                    out.log("info", &format!("{} {} {} -> {}", c["action"].as_str().unwrap_or(""), c["type"].as_str().unwrap_or(""), fqdn, c["ip"].as_str().unwrap_or("")));
+                }
+                Err(e) => {
+                    failures += 1;
+                    out.log("warn", &format!("[{}] {}", e.code, e.message.as_deref().unwrap_or("")));
+                }
+            }
+        }
+
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = tokio::time::sleep(Duration::from_secs(opts.interval.max(1))) => {}
+        }
+    }
+
+    out.result(json!({
+        "success": true,
+        "fqdn": fqdn,
+        "cycles": cycles,
+        "updates": updates,
+        "failures": failures
+    }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_and_shared_addresses_are_not_global() {
+        for addr in ["10.1.2.3", "172.16.0.1", "172.31.255.254", "192.168.1.10", "100.64.0.1", "100.127.255.254",
+                     "127.0.0.1", "169.254.1.1", "fd12:3456::1", "fc00::1", "fe80::1", "::1"] {
+            assert!(!is_global(&addr.parse().unwrap()), "{} should not be global", addr);
+        }
+        for addr in ["203.0.113.10", "100.128.0.1", "172.32.0.1", "2001:db8::1"] {
+            assert!(is_global(&addr.parse().unwrap()), "{} should be global", addr);
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! Registry-driven DNS sync: diff registry.json against the live zone and apply it
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{
+    build_record_fields, create_record, delete_record, fqdn, input_from_presentation, list_records,
+    normalize_content, patch_record, presentation_content, relative_name, CfDnsRecord, ListQuery,
+};
+use crate::cloudflare::Client;
+use crate::config::{self, DnsRecord};
+use crate::output::{Output, PebbleError};
+
+/// One change needed to bring the live zone in line with the desired records
+#[derive(Debug, Serialize)]
+pub(super) struct Change {
+    pub action: &'static str,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    /// Name relative to the zone, as the API expects it
+    #[serde(skip)]
+    pub relative_name: String,
+}
+
+/// Desired and live records sharing one (type, fqdn)
+type Group<'a> = (Vec<&'a DnsRecord>, Vec<&'a CfDnsRecord>);
+
+/// Diff desired records against live ones, returning the changes and the number of unchanged records
+pub(super) fn diff(domain: &str, desired: &[DnsRecord], live: &[CfDnsRecord]) -> (Vec<Change>, usize) {
+    // Group both sides by (type, fqdn) so round-robin sets are matched as a whole
+    let mut groups: BTreeMap<(String, String), Group> = BTreeMap::new();
+
+    for r in desired.iter().filter(|r| r.record_type != "comment") {
+        let rel = relative_name(domain, &r.name);
+        let key = (r.record_type.to_uppercase(), fqdn(domain, &rel).to_lowercase());
+        groups.entry(key).or_default().0.push(r);
+    }
+    for r in live {
+        let key = (r.record_type.to_uppercase(), r.name.to_lowercase());
+        groups.entry(key).or_default().1.push(r);
+    }
+
+    let mut changes = Vec::new();
+    let mut unchanged = 0;
+
+    for ((record_type, name), (want, mut have)) in groups {
+        let mut unmatched = Vec::new();
+
+        // Exact content matches first; only the comment, proxy status and TTL may still differ
+        for d in want {
+            let wanted = normalize_content(&record_type, &d.content);
+            match have.iter().position(|l| normalize_content(&record_type, &presentation_content(l)) == wanted) {
+                Some(i) => {
+                    let l = have.remove(i);
+                    let current = l.comment.clone().unwrap_or_default();
+                    let comment = (!d.desc.is_empty() && d.desc != current).then(|| d.desc.clone());
+                    let proxied = d.proxied.filter(|p| *p != l.proxied);
+                    let ttl = d.ttl.filter(|t| *t != l.ttl);
+                    if comment.is_some() || proxied.is_some() || ttl.is_some() {
+                        changes.push(Change {
+                            action: "update",
+                            record_type: record_type.clone(),
+                            name: name.clone(),
+                            record_id: Some(l.id.clone()),
+                            before: Some(presentation_content(l)),
+                            after: Some(d.content.clone()),
+                            comment,
+                            proxied,
+                            ttl,
+                            relative_name: relative_name(domain, &d.name),
+                        });
+                    } else {
+                        unchanged += 1;
+                    }
+                }
+                None => unmatched.push(d),
+            }
+        }
+
+        // Pair leftovers as in-place updates, then create or delete the rest
+        let mut have = have.into_iter();
+        for d in unmatched {
+            let comment = (!d.desc.is_empty()).then(|| d.desc.clone());
+            match have.next() {
+                Some(l) => changes.push(Change {
+                    action: "update",
+                    record_type: record_type.clone(),
+                    name: name.clone(),
+                    record_id: Some(l.id.clone()),
+                    before: Some(presentation_content(l)),
+                    after: Some(d.content.clone()),
+                    comment,
+                    proxied: d.proxied,
+                    ttl: d.ttl,
+                    relative_name: relative_name(domain, &d.name),
+                }),
+                None => changes.push(Change {
+                    action: "create",
+                    record_type: record_type.clone(),
+                    name: name.clone(),
+                    record_id: None,
+                    before: None,
+                    after: Some(d.content.clone()),
+                    comment,
+                    proxied: d.proxied,
+                    ttl: d.ttl,
+                    relative_name: relative_name(domain, &d.name),
+                }),
+            }
+        }
+        for l in have {
+            changes.push(Change {
+                action: "delete",
+                record_type: record_type.clone(),
+                name: name.clone(),
+                record_id: Some(l.id.clone()),
+                before: Some(presentation_content(l)),
+                after: None,
+                comment: None,
+                proxied: None,
+                ttl: None,
+                relative_name: relative_name(domain, &l.name),
+            });
+        }
+    }
+
+    (changes, unchanged)
+}
+
+/// Count changes per action for the result summary
+pub(super) fn summarize(changes: &[Change]) -> serde_json::Value {
+    let count = |action: &str| changes.iter().filter(|c| c.action == action).count();
+    json!({
+        "create": count("create"),
+        "update": count("update"),
+        "delete": count("delete")
+    })
+}
+
+/// Build the API body for a create/update change
+fn change_body(change: &Change) -> std::result::Result<serde_json::Value, String> {
+    let content = change.after.as_deref().unwrap_or("");
+    let input = input_from_presentation(&change.record_type, content)?;
+    let mut body = build_record_fields(&change.relative_name, &input)?;
+
+    if change.action == "create" {
+        body["name"] = json!(change.relative_name);
+        body["ttl"] = json!(change.ttl.unwrap_or(1));  // 1 = auto
+    } else if let Some(ttl) = change.ttl {
+        body["ttl"] = json!(ttl);
+    }
+    if let Some(proxied) = change.proxied {
+        body["proxied"] = json!(proxied);
+    }
+    if let Some(comment) = &change.comment {
+        body["comment"] = json!(comment);
+    }
+    Ok(body)
+}
+
+/// Run the changes against the zone, emitting progress and returning a per-change report
+pub(super) async fn execute(
+    changes: &[Change],
+    prune: bool,
+    client: &Client,
+    zone_id: &str,
+    out: &Output,
+) -> Result<Vec<serde_json::Value>> {
+    let mut report = Vec::new();
+    let total = changes.len().max(1);
+
+    for (i, change) in changes.iter().enumerate() {
+        out.progress(((i + 1) * 100 / total) as u8, &format!("{} {} {}", change.action, change.record_type, change.name));
+
+        let outcome: std::result::Result<Option<String>, String> = match change.action {
+            "delete" if !prune => {
+                report.push(json!({"change": change, "status": "skipped", "reason": "pass --prune to delete"}));
+                continue;
+            }
+            "delete" => {
+                let id = change.record_id.as_deref().unwrap_or("");
+                delete_record(client, zone_id, id).await
+                    .map(|_| Some(id.to_string()))
+                    .map_err(|e| e.to_string())
+            }
+            "update" => match change_body(change) {
+                Ok(body) => {
+                    let id = change.record_id.as_deref().unwrap_or("");
+                    patch_record(client, zone_id, id, &body).await
+                        .map(|r| Some(r.id))
+                        .map_err(|e| e.to_string())
+                }
+                Err(e) => Err(e),
+            },
+            _ => match change_body(change) {
+                Ok(body) => {
+                    create_record(client, zone_id, &body).await
+                        .map(|r| Some(r.id))
+                        .map_err(|e| e.to_string())
+                }
+                Err(e) => Err(e),
+            },
+        };
+
+        match outcome {
+            Ok(record_id) => report.push(json!({"change": change, "status": "ok", "record_id": record_id})),
+            Err(e) => report.push(json!({"change": change, "status": "failed", "error": e})),
+        }
+    }
+
+    Ok(report)
+}
+
+/// A computed plan plus what is needed to execute it
+pub(super) struct Planned {
+    changes: Vec<Change>,
+    unchanged: usize,
+    /// Live records of types the desired records do not manage, left out of the diff
+    unmanaged: usize,
+    client: Client,
+    zone_id: String,
+}
+
+/// Load the domain's records from registry.json
+fn registry_records(domain: &str, op: &str, out: &Output) -> Option<Vec<DnsRecord>> {
+    let registry = match config::load_registry() {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(PebbleError::input("PARSE_FAIL", &format!("Failed to load registry.json: {}", e))
+                .with_op(op));
+            return None;
+        }
+    };
+
+    match registry.domains.get(domain) {
+        Some(c) => Some(c.records.clone()),
+        None => {
+            out.error(PebbleError::input("NOT_FOUND", &format!("Domain not found in registry: {}", domain))
+                .with_op(op)
+                .with_details(json!({"available_domains": registry.domains.keys().collect::<Vec<_>>()})));
+            None
+        }
+    }
+}
+
+/// Fetch the live zone and diff it against `desired`
+pub(super) async fn compute(domain: &str, desired: &[DnsRecord], op: &str, out: &Output) -> Result<Option<Planned>> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
+
+    out.log("info", &format!("Fetching live DNS records for {}", domain));
+
+    let mut live = match list_records(&client, &zone_id, &ListQuery::default()).await {
+        Ok(page) => page.items,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op(op)
+                .with_details(json!({"domain": domain})));
+            return Ok(None);
+        }
+    };
+
+    // Tunnel routes belong to `cf tunnel route`; leave them out of the diff so --prune keeps them
+    let routed: Vec<String> = config::load_registry()
+        .map(|r| r.tunnels.into_keys().map(|h| h.to_lowercase()).collect())
+        .unwrap_or_default();
+    live.retain(|r| !routed.contains(&r.name.to_lowercase()));
+
+    // Only record types the desired set uses are compared, so --prune never touches
+    // e.g. NS delegations or mail records that registry.json does not describe
+    let managed: BTreeSet<String> = desired.iter()
+        .filter(|r| r.record_type != "comment")
+        .map(|r| r.record_type.to_uppercase())
+        .collect();
+    let before = live.len();
+    live.retain(|r| managed.contains(&r.record_type.to_uppercase()));
+    let unmanaged = before - live.len();
+
+    let (changes, unchanged) = diff(domain, desired, &live);
+
+    Ok(Some(Planned { changes, unchanged, unmanaged, client, zone_id }))
+}
+
+/// Report a plan without changing anything
+pub(super) fn report(domain: &str, planned: &Planned, out: &Output) {
+    out.result(json!({
+        "success": true,
+        "domain": domain,
+        "in_sync": planned.changes.is_empty(),
+        "unchanged": planned.unchanged,
+        "unmanaged": planned.unmanaged,
+        "summary": summarize(&planned.changes),
+        "changes": planned.changes
+    }));
+}
+
+/// Execute a plan and report the per-change results
+pub(super) async fn run(domain: &str, planned: &Planned, prune: bool, op: &str, out: &Output) -> Result<()> {
+    let changes = &planned.changes;
+
+    if changes.is_empty() {
+        out.result(json!({
+            "success": true,
+            "domain": domain,
+            "in_sync": true,
+            "unchanged": planned.unchanged,
+            "unmanaged": planned.unmanaged,
+            "results": []
+        }));
+        return Ok(());
+    }
+
+    out.log("info", &format!("Applying {} change(s) to {}", changes.len(), domain));
+
+    let report = execute(changes, prune, &planned.client, &planned.zone_id, out).await?;
+    let failed = report.iter().filter(|r| r["status"] == "failed").count();
+
+    if failed > 0 {
+        out.error(PebbleError::ext("APPLY_PARTIAL", &format!("{} of {} change(s) failed", failed, changes.len()))
+            .with_op(op)
+            .with_details(json!({"domain": domain, "results": report})));
+        return Ok(());
+    }
+
+    out.result(json!({
+        "success": true,
+        "domain": domain,
+        "unchanged": planned.unchanged,
+        "unmanaged": planned.unmanaged,
+        "summary": summarize(changes),
+        "pruned": prune,
+        "results": report
+    }));
+
+    Ok(())
+}
+
+pub async fn plan(domain: &str, out: &Output) -> Result<()> {
+    let Some(desired) = registry_records(domain, "dns.plan", out) else {
+        return Ok(());
+    };
+    if let Some(planned) = compute(domain, &desired, "dns.plan", out).await? {
+        report(domain, &planned, out);
+    }
+    Ok(())
+}
+
+pub async fn apply(domain: &str, prune: bool, out: &Output) -> Result<()> {
+    let Some(desired) = registry_records(domain, "dns.apply", out) else {
+        return Ok(());
+    };
+    if let Some(planned) = compute(domain, &desired, "dns.apply", out).await? {
+        run(domain, &planned, prune, "dns.apply", out).await?;
+    }
+    Ok(())
+}
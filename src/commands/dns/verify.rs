@@ -0,0 +1,517 @@
+//! Propagation checks: poll authoritative and public resolvers until they serve a record
+
+use serde::Serialize;
+use serde_json::json;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+use super::{find_records, fqdn, normalize_content, presentation_content, CfDnsRecord};
+use crate::cli::WaitArgs;
+use crate::cloudflare::Client;
+use crate::output::{Output, PebbleError};
+
+/// Public resolvers polled alongside the zone's nameservers
+const PUBLIC_RESOLVERS: [Ipv4Addr; 2] = [Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(8, 8, 8, 8)];
+
+/// How long to wait for a single UDP answer
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Poll interval used by `--wait` on create/update/delete
+pub(super) const WAIT_INTERVAL_S: u64 = 5;
+
+// ============ Expectations ============
+
+/// What every resolver must answer before the change counts as propagated
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", content = "content", rename_all = "snake_case")]
+pub(super) enum Expect {
+    /// All of these values are served
+    Present(Vec<String>),
+    /// Some answer is served (proxied records resolve to Cloudflare edge IPs)
+    Resolves,
+    /// None of these values are served; an empty list means no answer at all
+    Absent(Vec<String>),
+}
+
+impl Expect {
+    /// Expectation after creating or updating `record`
+    pub(super) fn served(record: &CfDnsRecord) -> Self {
+        if record.proxied {
+            Expect::Resolves
+        } else {
+            Expect::Present(vec![presentation_content(record)])
+        }
+    }
+
+    /// Expectation after deleting `record`
+    pub(super) fn removed(record: &CfDnsRecord) -> Self {
+        if record.proxied {
+            Expect::Absent(Vec::new())
+        } else {
+            Expect::Absent(vec![presentation_content(record)])
+        }
+    }
+
+    fn matches(&self, record_type: &str, answers: &[String]) -> bool {
+        let served: Vec<String> = answers.iter().map(|a| normalize_content(record_type, a)).collect();
+        let is_served = |c: &String| served.contains(&normalize_content(record_type, c));
+        match self {
+            Expect::Present(contents) => contents.iter().all(is_served),
+            Expect::Resolves => !answers.is_empty(),
+            Expect::Absent(contents) if contents.is_empty() => answers.is_empty(),
+            Expect::Absent(contents) => !contents.iter().any(is_served),
+        }
+    }
+}
+
+/// Query type to poll: proxied records are answered with A/AAAA edge addresses only
+pub(super) fn query_type(record: &CfDnsRecord) -> &str {
+    if record.proxied && record.record_type == "CNAME" {
+        "A"
+    } else {
+        &record.record_type
+    }
+}
+
+// ============ Polling ============
+
+/// A resolver to poll; authoritative servers are queried without recursion
+struct Resolver {
+    label: String,
+    addr: SocketAddr,
+    authoritative: bool,
+}
+
+#[derive(Serialize)]
+struct ResolverStatus {
+    resolver: String,
+    authoritative: bool,
+    matched: bool,
+    answers: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Run the `--wait` check after a change, if it was requested
+pub(super) async fn after_change(
+    domain: &str,
+    fqdn: &str,
+    record_type: &str,
+    expect: &Expect,
+    wait: &WaitArgs,
+    out: &Output,
+) -> Result<Option<serde_json::Value>, PebbleError> {
+    if !wait.wait {
+        return Ok(None);
+    }
+    let timeout = Duration::from_secs(wait.wait_timeout);
+    let interval = Duration::from_secs(WAIT_INTERVAL_S);
+    poll(domain, fqdn, record_type, expect, &wait.resolvers, timeout, interval, out)
+        .await
+        .map(Some)
+}
+
+/// Poll resolvers until all of them satisfy `expect`, or time out
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn poll(
+    domain: &str,
+    fqdn: &str,
+    record_type: &str,
+    expect: &Expect,
+    resolvers: &[SocketAddr],
+    timeout: Duration,
+    interval: Duration,
+    out: &Output,
+) -> Result<serde_json::Value, PebbleError> {
+    let qtype = type_code(record_type)
+        .ok_or_else(|| PebbleError::input("UNSUPPORTED_TYPE", &format!("Cannot verify {} records", record_type)))?;
+
+    let started = Instant::now();
+    let resolvers = resolver_list(domain, resolvers, out).await;
+    let mut statuses: Vec<Option<ResolverStatus>> = resolvers.iter().map(|_| None).collect();
+    let mut attempts = 0;
+
+    out.log("info", &format!("Waiting for {} {} on {} resolver(s)", record_type, fqdn, resolvers.len()));
+
+    loop {
+        attempts += 1;
+
+        // Resolvers that already matched keep their answer; caches only move forward
+        for (resolver, status) in resolvers.iter().zip(statuses.iter_mut()) {
+            if status.as_ref().is_some_and(|s| s.matched) {
+                continue;
+            }
+            let (answers, error) = match query(resolver.addr, fqdn, qtype, !resolver.authoritative).await {
+                Ok(answers) => (answers, None),
+                Err(e) => (Vec::new(), Some(e)),
+            };
+            *status = Some(ResolverStatus {
+                resolver: resolver.label.clone(),
+                authoritative: resolver.authoritative,
+                matched: error.is_none() && expect.matches(record_type, &answers),
+                answers,
+                error,
+            });
+        }
+
+        let matched = statuses.iter().flatten().filter(|s| s.matched).count();
+        let total = statuses.len().max(1);
+        out.progress(
+            (matched * 100 / total) as u8,
+            &format!("{}/{} resolvers serve the expected {} {}", matched, total, record_type, fqdn),
+        );
+
+        let report = json!({
+            "fqdn": fqdn,
+            "type": record_type,
+            "expected": expect,
+            "propagated": matched == total,
+            "attempts": attempts,
+            "elapsed_s": started.elapsed().as_secs(),
+            "resolvers": statuses
+        });
+
+        if matched == total {
+            return Ok(report);
+        }
+        if started.elapsed() + interval > timeout {
+            return Err(PebbleError::timeout(
+                "PROPAGATION_TIMEOUT",
+                &format!("{} {} not propagated to {}/{} resolvers after {}s", record_type, fqdn, total - matched, total, timeout.as_secs()),
+                interval.as_secs().max(1) as u32,
+            )
+            .with_details(report));
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Explicit resolvers, or the zone's nameservers plus public resolvers
+async fn resolver_list(domain: &str, explicit: &[SocketAddr], out: &Output) -> Vec<Resolver> {
+    if !explicit.is_empty() {
+        return explicit.iter()
+            .map(|addr| Resolver { label: addr.to_string(), addr: *addr, authoritative: false })
+            .collect();
+    }
+
+    let public: Vec<SocketAddr> = PUBLIC_RESOLVERS.iter().map(|ip| SocketAddr::new(IpAddr::V4(*ip), 53)).collect();
+    let mut resolvers = Vec::new();
+
+    match nameservers(domain, &public).await {
+        Ok(ns) if !ns.is_empty() => resolvers.extend(ns),
+        Ok(_) => out.log("warn", &format!("No nameservers found for {}; polling public resolvers only", domain)),
+        Err(e) => out.log("warn", &format!("Nameserver lookup for {} failed ({}); polling public resolvers only", domain, e)),
+    }
+    resolvers.extend(public.into_iter().map(|addr| Resolver {
+        label: addr.ip().to_string(),
+        addr,
+        authoritative: false,
+    }));
+    resolvers
+}
+
+/// Look up the zone's NS hosts and their IPv4 addresses through the public resolvers
+async fn nameservers(domain: &str, public: &[SocketAddr]) -> Result<Vec<Resolver>, String> {
+    let mut last_error = String::new();
+    for upstream in public {
+        let hosts = match query(*upstream, domain, TYPE_NS, true).await {
+            Ok(hosts) => hosts,
+            Err(e) => {
+                last_error = e;
+                continue;
+            }
+        };
+
+        let mut resolvers = Vec::new();
+        for host in hosts {
+            let host = host.trim_end_matches('.').to_string();
+            if let Ok(addrs) = query(*upstream, &host, TYPE_A, true).await {
+                if let Some(ip) = addrs.iter().find_map(|a| a.parse::<IpAddr>().ok()) {
+                    resolvers.push(Resolver {
+                        label: format!("{} ({})", host, ip),
+                        addr: SocketAddr::new(ip, 53),
+                        authoritative: true,
+                    });
+                }
+            }
+        }
+        return Ok(resolvers);
+    }
+    Err(last_error)
+}
+
+// ============ Command ============
+
+/// Options for `cf dns verify`
+pub(super) struct Check {
+    pub content: Option<String>,
+    pub absent: bool,
+    pub timeout: u64,
+    pub interval: u64,
+    pub resolvers: Vec<SocketAddr>,
+}
+
+pub(super) async fn verify(domain: &str, name: &str, record_type: &str, check: &Check, out: &Output) -> anyhow::Result<()> {
+    let fqdn = fqdn(domain, name);
+
+    let (qtype, expect) = match (&check.content, check.absent) {
+        (Some(content), false) => (record_type.to_string(), Expect::Present(vec![content.clone()])),
+        (Some(content), true) => (record_type.to_string(), Expect::Absent(vec![content.clone()])),
+        (None, true) => (record_type.to_string(), Expect::Absent(Vec::new())),
+        (None, false) => {
+            // Expect whatever Cloudflare currently has for the name
+            let (client, zone_id) = Client::for_domain(domain).await?;
+            let records = match find_records(&client, &zone_id, &fqdn, Some(record_type)).await {
+                Ok(r) => r,
+                Err(e) => {
+                    out.error(e.to_pebble()
+                        .with_op("dns.verify")
+                        .with_details(json!({"domain": domain})));
+                    return Ok(());
+                }
+            };
+            if records.is_empty() {
+                out.error(PebbleError::input("NOT_FOUND", &format!("No {} record for {} in Cloudflare; pass --content", record_type, fqdn))
+                    .with_op("dns.verify")
+                    .with_details(json!({"fqdn": fqdn, "type": record_type})));
+                return Ok(());
+            }
+
+            let direct: Vec<String> = records.iter().filter(|r| !r.proxied).map(presentation_content).collect();
+            if direct.is_empty() {
+                (query_type(&records[0]).to_string(), Expect::Resolves)
+            } else {
+                (record_type.to_string(), Expect::Present(direct))
+            }
+        }
+    };
+
+    let timeout = Duration::from_secs(check.timeout);
+    let interval = Duration::from_secs(check.interval.max(1));
+    match poll(domain, &fqdn, &qtype, &expect, &check.resolvers, timeout, interval, out).await {
+        Ok(report) => {
+            let mut result = json!({"success": true});
+            if let (Some(result), serde_json::Value::Object(report)) = (result.as_object_mut(), report) {
+                result.extend(report);
+            }
+            out.result(result);
+        }
+        Err(e) => out.error(e.with_op("dns.verify")),
+    }
+
+    Ok(())
+}
+
+// ============ DNS Wire Format ============
+
+const TYPE_A: u16 = 1;
+const TYPE_NS: u16 = 2;
+const TYPE_CNAME: u16 = 5;
+const TYPE_MX: u16 = 15;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_CAA: u16 = 257;
+
+const RCODE_NXDOMAIN: u8 = 3;
+
+/// Truncation flag in the high byte of the header flags
+const FLAG_TC: u8 = 0x02;
+
+fn type_code(record_type: &str) -> Option<u16> {
+    match record_type {
+        "A" => Some(TYPE_A),
+        "NS" => Some(TYPE_NS),
+        "CNAME" => Some(TYPE_CNAME),
+        "MX" => Some(TYPE_MX),
+        "TXT" => Some(TYPE_TXT),
+        "AAAA" => Some(TYPE_AAAA),
+        "SRV" => Some(TYPE_SRV),
+        "CAA" => Some(TYPE_CAA),
+        _ => None,
+    }
+}
+
+/// Send one query and return the answers of the requested type in presentation form.
+/// NXDOMAIN and empty answers both yield an empty list. A truncated UDP answer
+/// (TC bit set) is retried over TCP.
+async fn query(server: SocketAddr, name: &str, qtype: u16, recursion: bool) -> Result<Vec<String>, String> {
+    let bind: SocketAddr = if server.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }.parse().unwrap();
+    let socket = UdpSocket::bind(bind).await.map_err(|e| e.to_string())?;
+    socket.connect(server).await.map_err(|e| e.to_string())?;
+
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    let id = (nanos ^ std::process::id()) as u16;
+    let request = build_query(id, name, qtype, recursion);
+    socket.send(&request).await.map_err(|e| e.to_string())?;
+
+    let mut buf = [0u8; 4096];
+    let len = tokio::time::timeout(QUERY_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .map_err(|_| format!("no answer from {} within {}s", server, QUERY_TIMEOUT.as_secs()))?
+        .map_err(|e| e.to_string())?;
+
+    if buf.get(2).is_some_and(|flags| flags & FLAG_TC != 0) {
+        let answer = query_tcp(server, &request).await
+            .map_err(|e| format!("truncated UDP answer from {} and TCP retry failed: {}", server, e))?;
+        return parse_response(&answer, id, qtype);
+    }
+    parse_response(&buf[..len], id, qtype)
+}
+
+/// Send a query over TCP (two-byte length prefix on both sides) and return the raw answer
+async fn query_tcp(server: SocketAddr, request: &[u8]) -> Result<Vec<u8>, String> {
+    let exchange = async {
+        let mut stream = TcpStream::connect(server).await?;
+        let mut framed = (request.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(request);
+        stream.write_all(&framed).await?;
+
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len).await?;
+        let mut answer = vec![0u8; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut answer).await?;
+        Ok::<_, std::io::Error>(answer)
+    };
+    tokio::time::timeout(QUERY_TIMEOUT, exchange)
+        .await
+        .map_err(|_| format!("no TCP answer within {}s", QUERY_TIMEOUT.as_secs()))?
+        .map_err(|e| e.to_string())
+}
+
+fn build_query(id: u16, name: &str, qtype: u16, recursion: bool) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(32 + name.len());
+    msg.extend_from_slice(&id.to_be_bytes());
+    msg.extend_from_slice(&(if recursion { 0x0100u16 } else { 0 }).to_be_bytes());
+    msg.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);  // QDCOUNT=1
+    for label in name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()) {
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+    msg.extend_from_slice(&qtype.to_be_bytes());
+    msg.extend_from_slice(&1u16.to_be_bytes());  // IN
+    msg
+}
+
+fn parse_response(msg: &[u8], id: u16, qtype: u16) -> Result<Vec<String>, String> {
+    let short = || "truncated DNS response".to_string();
+    let u16_at = |pos: usize| -> Result<u16, String> {
+        msg.get(pos..pos + 2).map(|b| u16::from_be_bytes([b[0], b[1]])).ok_or_else(short)
+    };
+
+    if u16_at(0)? != id {
+        return Err("DNS response ID mismatch".into());
+    }
+    let rcode = (u16_at(2)? & 0x000f) as u8;
+    if rcode == RCODE_NXDOMAIN {
+        return Ok(Vec::new());
+    }
+    if rcode != 0 {
+        return Err(format!("DNS error rcode {}", rcode));
+    }
+
+    let qdcount = u16_at(4)?;
+    let ancount = u16_at(6)?;
+    let mut pos = 12;
+    for _ in 0..qdcount {
+        pos = read_name(msg, pos)?.1 + 4;
+    }
+
+    let mut answers = Vec::new();
+    for _ in 0..ancount {
+        pos = read_name(msg, pos)?.1;
+        let rtype = u16_at(pos)?;
+        let rdlen = u16_at(pos + 8)? as usize;
+        let start = pos + 10;
+        let rdata = msg.get(start..start + rdlen).ok_or_else(short)?;
+        pos = start + rdlen;
+
+        if rtype == qtype {
+            answers.push(rdata_text(msg, start, rtype, rdata)?);
+        }
+    }
+    Ok(answers)
+}
+
+/// Render RDATA in the same presentation form Cloudflare content uses
+fn rdata_text(msg: &[u8], start: usize, rtype: u16, rdata: &[u8]) -> Result<String, String> {
+    let short = || "truncated DNS record".to_string();
+    let u16_in = |i: usize| rdata.get(i..i + 2).map(|b| u16::from_be_bytes([b[0], b[1]])).ok_or_else(short);
+
+    let text = match rtype {
+        TYPE_A => {
+            let b: [u8; 4] = rdata.try_into().map_err(|_| short())?;
+            Ipv4Addr::from(b).to_string()
+        }
+        TYPE_AAAA => {
+            let b: [u8; 16] = rdata.try_into().map_err(|_| short())?;
+            Ipv6Addr::from(b).to_string()
+        }
+        TYPE_NS | TYPE_CNAME => read_name(msg, start)?.0,
+        TYPE_MX => format!("{} {}", u16_in(0)?, read_name(msg, start + 2)?.0),
+        TYPE_SRV => format!(
+            "{} {} {} {}",
+            u16_in(0)?,
+            u16_in(2)?,
+            u16_in(4)?,
+            read_name(msg, start + 6)?.0
+        ),
+        TYPE_TXT => {
+            // One or more <length><text> strings, served as a single value
+            let mut text = String::new();
+            let mut i = 0;
+            while i < rdata.len() {
+                let len = rdata[i] as usize;
+                let part = rdata.get(i + 1..i + 1 + len).ok_or_else(short)?;
+                text.push_str(&String::from_utf8_lossy(part));
+                i += 1 + len;
+            }
+            text
+        }
+        TYPE_CAA => {
+            let flags = *rdata.first().ok_or_else(short)?;
+            let tag_len = *rdata.get(1).ok_or_else(short)? as usize;
+            let tag = rdata.get(2..2 + tag_len).ok_or_else(short)?;
+            let value = rdata.get(2 + tag_len..).unwrap_or_default();
+            format!("{} {} \"{}\"", flags, String::from_utf8_lossy(tag), String::from_utf8_lossy(value))
+        }
+        _ => String::new(),
+    };
+    Ok(text)
+}
+
+/// Read a possibly compressed name, returning it and the position after it in the record
+fn read_name(msg: &[u8], mut pos: usize) -> Result<(String, usize), String> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    let mut jumps = 0;
+
+    loop {
+        let len = *msg.get(pos).ok_or("truncated DNS name")? as usize;
+        if len == 0 {
+            pos += 1;
+            break;
+        }
+        if len & 0xc0 == 0xc0 {
+            let next = *msg.get(pos + 1).ok_or("truncated DNS name")? as usize;
+            end.get_or_insert(pos + 2);
+            jumps += 1;
+            if jumps > 16 {
+                return Err("DNS name compression loop".into());
+            }
+            pos = ((len & 0x3f) << 8) | next;
+            continue;
+        }
+        let label = msg.get(pos + 1..pos + 1 + len).ok_or("truncated DNS name")?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += 1 + len;
+    }
+
+    Ok((labels.join("."), end.unwrap_or(pos)))
+}
@@ -0,0 +1,529 @@
+//! Zone export (BIND, JSON, CSV) and BIND zone file import
+
+use anyhow::{Context, Result};
+use serde_json::json;
+
+use super::{list_records, plan, record_json, relative_name, CfDnsRecord, ListQuery};
+use crate::cloudflare::Client;
+use crate::config::DnsRecord;
+use crate::output::{Output, PebbleError};
+
+// ============ Writing ============
+
+/// Hostname with a trailing dot, so it is not read as relative to $ORIGIN
+fn absolute(host: &str) -> String {
+    if host.ends_with('.') {
+        host.to_string()
+    } else {
+        format!("{}.", host)
+    }
+}
+
+/// TXT content as one or more quoted character-strings of at most 255 bytes
+fn quote_txt(content: &str) -> String {
+    if content.starts_with('"') {
+        return content.to_string();
+    }
+    // Split on character boundaries so no multibyte character is cut in half
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    for (i, c) in content.char_indices() {
+        if i + c.len_utf8() - start > 255 {
+            chunks.push(&content[start..i]);
+            start = i;
+        }
+    }
+    chunks.push(&content[start..]);
+
+    let escaped: Vec<String> = chunks.iter()
+        .map(|s| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    escaped.join(" ")
+}
+
+fn bind_rdata(r: &CfDnsRecord) -> String {
+    match (r.record_type.as_str(), &r.data) {
+        ("CNAME" | "NS" | "PTR", _) => absolute(&r.content),
+        ("MX", _) => format!("{} {}", r.priority.unwrap_or(0), absolute(&r.content)),
+        ("SRV", Some(d)) => format!(
+            "{} {} {} {}",
+            d.priority.unwrap_or(0),
+            d.weight.unwrap_or(0),
+            d.port.unwrap_or(0),
+            absolute(d.target.as_deref().unwrap_or(".")),
+        ),
+        ("CAA", Some(d)) => format!(
+            "{} {} \"{}\"",
+            d.flags.unwrap_or(0),
+            d.tag.as_deref().unwrap_or("issue"),
+            d.value.as_deref().unwrap_or(""),
+        ),
+        ("TXT", _) => quote_txt(&r.content),
+        _ => r.content.clone(),
+    }
+}
+
+/// Render records as an RFC 1035 zone file
+fn to_bind(domain: &str, zone_id: &str, records: &[CfDnsRecord]) -> String {
+    let mut text = format!(
+        ";; Zone: {}\n;; Zone ID: {}\n;; Exported by cf {}\n\n$ORIGIN {}\n",
+        domain,
+        zone_id,
+        env!("CARGO_PKG_VERSION"),
+        absolute(domain),
+    );
+
+    for r in records {
+        let mut line = format!(
+            "{}\t{}\tIN\t{}\t{}",
+            relative_name(domain, &r.name),
+            r.ttl,
+            r.record_type,
+            bind_rdata(r),
+        );
+
+        // Same convention as Cloudflare's own export, so the file round-trips through the dashboard
+        let mut notes = Vec::new();
+        if let Some(comment) = r.comment.as_deref().filter(|c| !c.is_empty()) {
+            notes.push(comment.replace('\n', " "));
+        }
+        if r.proxied {
+            notes.push("cf_tags=cf-proxied:true".to_string());
+        }
+        if !notes.is_empty() {
+            line.push_str(&format!(" ; {}", notes.join(" ")));
+        }
+
+        text.push_str(&line);
+        text.push('\n');
+    }
+
+    text
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn to_csv(records: &[CfDnsRecord]) -> String {
+    let mut text = String::from("type,name,content,ttl,proxied,comment\n");
+    for r in records {
+        let row = [
+            csv_field(&r.record_type),
+            csv_field(&r.name),
+            csv_field(&super::presentation_content(r)),
+            r.ttl.to_string(),
+            r.proxied.to_string(),
+            csv_field(r.comment.as_deref().unwrap_or("")),
+        ];
+        text.push_str(&row.join(","));
+        text.push('\n');
+    }
+    text
+}
+
+// ============ Parsing ============
+
+/// One logical entry after comments are stripped and parentheses joined
+struct Entry {
+    line: usize,
+    /// Entry started with whitespace, so the owner is the previous one
+    inherit_owner: bool,
+    tokens: Vec<String>,
+    comment: String,
+}
+
+/// Split zone text into logical entries
+fn tokenize(text: &str) -> std::result::Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut comment = String::new();
+    let mut token = String::new();
+    let mut in_quote = false;
+    let mut in_comment = false;
+    let mut depth = 0;
+    let mut line = 1;
+    let mut start_line = 1;
+    let mut at_line_start = true;
+    let mut inherit_owner = false;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if at_line_start && tokens.is_empty() && token.is_empty() {
+            inherit_owner = c == ' ' || c == '\t';
+            start_line = line;
+        }
+        at_line_start = false;
+
+        if in_comment {
+            if c == '\n' {
+                in_comment = false;
+            } else {
+                comment.push(c);
+                continue;
+            }
+        }
+
+        if in_quote {
+            token.push(c);
+            if c == '\\' {
+                if let Some(next) = chars.next() {
+                    token.push(next);
+                }
+            } else if c == '"' {
+                in_quote = false;
+            } else if c == '\n' {
+                return Err(format!("line {}: unterminated quoted string", line));
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_quote = true;
+                token.push(c);
+            }
+            ';' => {
+                in_comment = true;
+                if !comment.is_empty() {
+                    comment.push(' ');
+                }
+            }
+            '(' => depth += 1,
+            ')' => {
+                if depth == 0 {
+                    return Err(format!("line {}: unbalanced ')'", line));
+                }
+                depth -= 1;
+            }
+            ' ' | '\t' | '\r' | '\n' => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                if c == '\n' {
+                    line += 1;
+                    if depth == 0 {
+                        if !tokens.is_empty() {
+                            entries.push(Entry {
+                                line: start_line,
+                                inherit_owner,
+                                tokens: std::mem::take(&mut tokens),
+                                comment: comment.trim().to_string(),
+                            });
+                        }
+                        comment.clear();
+                        at_line_start = true;
+                    }
+                }
+            }
+            _ => token.push(c),
+        }
+    }
+
+    if in_quote {
+        return Err(format!("line {}: unterminated quoted string", line));
+    }
+    if depth != 0 {
+        return Err(format!("line {}: unbalanced '('", line));
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    if !tokens.is_empty() {
+        entries.push(Entry { line: start_line, inherit_owner, tokens, comment: comment.trim().to_string() });
+    }
+
+    Ok(entries)
+}
+
+/// Expand a possibly relative name against the origin (no trailing dot in the result)
+fn expand(name: &str, origin: &str) -> String {
+    if name == "@" {
+        origin.to_string()
+    } else if let Some(abs) = name.strip_suffix('.') {
+        abs.to_string()
+    } else {
+        format!("{}.{}", name, origin)
+    }
+}
+
+fn is_ttl(token: &str) -> bool {
+    let mut chars = token.chars().peekable();
+    if !chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    token.chars().all(|c| c.is_ascii_digit() || "smhdwSMHDW".contains(c))
+}
+
+/// TTL in seconds from a zone-file TTL such as 300, 1h or 1h30m
+fn parse_ttl(token: &str) -> Option<u32> {
+    if token.chars().all(|c| c.is_ascii_digit()) {
+        return token.parse().ok();
+    }
+    let mut total: u32 = 0;
+    let mut number = String::new();
+    for c in token.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 604800,
+            _ => return None,
+        };
+        total = total.checked_add(number.parse::<u32>().ok()?.checked_mul(unit)?)?;
+        number.clear();
+    }
+    number.is_empty().then_some(total)
+}
+
+/// Undo zone-file escaping of a single quoted string
+fn unquote(token: &str) -> String {
+    let inner = token.strip_prefix('"').and_then(|t| t.strip_suffix('"')).unwrap_or(token);
+    inner.replace("\\\"", "\"").replace("\\\\", "\\")
+}
+
+/// Records parsed from a zone file
+pub(super) struct ParsedZone {
+    pub origin: Option<String>,
+    pub records: Vec<DnsRecord>,
+    /// Entries that cannot be managed through the DNS records API
+    pub skipped: Vec<String>,
+}
+
+/// Parse an RFC 1035 zone file into registry-style records relative to `domain`
+pub(super) fn parse(text: &str, domain: Option<&str>) -> std::result::Result<ParsedZone, String> {
+    let mut origin: Option<String> = domain.map(|d| d.trim_end_matches('.').to_string());
+    let mut file_origin = None;
+    let mut last_owner: Option<String> = None;
+    let mut default_ttl: Option<u32> = None;
+    let mut parsed = Vec::new();
+    let mut skipped = Vec::new();
+
+    for entry in tokenize(text)? {
+        let mut tokens = entry.tokens.iter().map(String::as_str);
+        let first = tokens.clone().next().unwrap_or("");
+
+        match first.to_uppercase().as_str() {
+            "$ORIGIN" => {
+                tokens.next();
+                let value = tokens.next()
+                    .ok_or_else(|| format!("line {}: $ORIGIN needs a value", entry.line))?;
+                let value = value.trim_end_matches('.').to_string();
+                file_origin.get_or_insert_with(|| value.clone());
+                origin = Some(value);
+                continue;
+            }
+            "$TTL" => {
+                tokens.next();
+                default_ttl = tokens.next().and_then(parse_ttl);
+                continue;
+            }
+            "$INCLUDE" | "$GENERATE" => {
+                return Err(format!("line {}: {} is not supported", entry.line, first));
+            }
+            _ => {}
+        }
+
+        let Some(current_origin) = origin.clone() else {
+            return Err(format!("line {}: relative names need $ORIGIN or --domain", entry.line));
+        };
+
+        let owner = if entry.inherit_owner {
+            last_owner.clone()
+                .ok_or_else(|| format!("line {}: no previous owner name", entry.line))?
+        } else {
+            expand(tokens.next().unwrap_or("@"), &current_origin)
+        };
+        last_owner = Some(owner.clone());
+
+        // Optional TTL and class, in either order, then the type
+        let mut ttl = default_ttl;
+        let record_type = loop {
+            match tokens.next() {
+                Some(t) if is_ttl(t) => {
+                    ttl = Some(parse_ttl(t).ok_or_else(|| format!("line {}: invalid TTL '{}'", entry.line, t))?);
+                }
+                Some(t) if matches!(t.to_uppercase().as_str(), "IN" | "CH" | "HS") => continue,
+                Some(t) => break t.to_uppercase(),
+                None => return Err(format!("line {}: missing record type", entry.line)),
+            }
+        };
+        let rdata: Vec<&str> = tokens.collect();
+        let need = |n: usize| {
+            if rdata.len() < n {
+                Err(format!("line {}: {} record needs {} rdata fields", entry.line, record_type, n))
+            } else {
+                Ok(())
+            }
+        };
+
+        let zone = domain.map(str::to_string)
+            .or_else(|| file_origin.clone())
+            .unwrap_or_else(|| current_origin.clone());
+        let name = relative_name(&zone, &owner);
+
+        let content = match record_type.as_str() {
+            "A" | "AAAA" => {
+                need(1)?;
+                rdata[0].to_string()
+            }
+            "CNAME" => {
+                need(1)?;
+                expand(rdata[0], &current_origin)
+            }
+            "MX" => {
+                need(2)?;
+                format!("{} {}", rdata[0], expand(rdata[1], &current_origin))
+            }
+            "SRV" => {
+                need(4)?;
+                format!("{} {} {} {}", rdata[0], rdata[1], rdata[2], expand(rdata[3], &current_origin))
+            }
+            "TXT" => {
+                need(1)?;
+                // Multiple character-strings are one value split at 255 bytes
+                rdata.iter().map(|s| unquote(s)).collect()
+            }
+            "CAA" => {
+                need(3)?;
+                format!("{} {} {}", rdata[0], rdata[1].to_lowercase(), rdata[2..].join(" "))
+            }
+            "SOA" => {
+                skipped.push(format!("line {}: SOA is managed by Cloudflare", entry.line));
+                continue;
+            }
+            "NS" if name == "@" => {
+                skipped.push(format!("line {}: apex NS is managed by Cloudflare", entry.line));
+                continue;
+            }
+            other => {
+                skipped.push(format!("line {}: {} records are not supported", entry.line, other));
+                continue;
+            }
+        };
+
+        // Keep the human part of the comment; cf_tags carry Cloudflare's proxy status
+        let mut proxied = None;
+        let mut desc = Vec::new();
+        for word in entry.comment.split_whitespace() {
+            match word.strip_prefix("cf_tags=") {
+                Some(tags) => {
+                    for tag in tags.split(',') {
+                        match tag {
+                            "cf-proxied:true" => proxied = Some(true),
+                            "cf-proxied:false" => proxied = Some(false),
+                            _ => {}
+                        }
+                    }
+                }
+                None => desc.push(word),
+            }
+        }
+
+        parsed.push(DnsRecord { record_type, name, content, desc: desc.join(" "), proxied, ttl });
+    }
+
+    Ok(ParsedZone { origin: file_origin, records: parsed, skipped })
+}
+
+// ============ Commands ============
+
+pub async fn export(domain: &str, format: &str, output: Option<&str>, out: &Output) -> Result<()> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
+
+    out.log("info", &format!("Exporting DNS records for {} as {}", domain, format));
+
+    let records = match list_records(&client, &zone_id, &ListQuery::default()).await {
+        Ok(page) => page.items,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("dns.export")
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
+
+    let content = match format {
+        "json" => serde_json::to_string_pretty(&records.iter().map(record_json).collect::<Vec<_>>())?,
+        "csv" => to_csv(&records),
+        _ => to_bind(domain, &zone_id, &records),
+    };
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, &content)
+                .with_context(|| format!("Failed to write {}", path))?;
+            out.result(json!({
+                "success": true,
+                "domain": domain,
+                "format": format,
+                "count": records.len(),
+                "path": path
+            }));
+        }
+        None => {
+            out.result(json!({
+                "success": true,
+                "domain": domain,
+                "format": format,
+                "count": records.len(),
+                "content": content
+            }));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn import(file: &str, domain: Option<&str>, dry_run: bool, prune: bool, out: &Output) -> Result<()> {
+    let text = match std::fs::read_to_string(file) {
+        Ok(t) => t,
+        Err(e) => {
+            out.error(PebbleError::input("FILE_NOT_FOUND", &format!("Failed to read {}: {}", file, e))
+                .with_op("dns.import"));
+            return Ok(());
+        }
+    };
+
+    let zone = match parse(&text, domain) {
+        Ok(z) => z,
+        Err(msg) => {
+            out.error(PebbleError::input("PARSE_FAIL", &msg)
+                .with_op("dns.import")
+                .with_details(json!({"file": file})));
+            return Ok(());
+        }
+    };
+
+    let Some(domain) = domain.map(str::to_string).or(zone.origin.clone()) else {
+        out.error(PebbleError::input("MISSING_DOMAIN", "Zone file has no $ORIGIN; pass --domain")
+            .with_op("dns.import"));
+        return Ok(());
+    };
+
+    out.log("info", &format!("Parsed {} record(s) for {} from {}", zone.records.len(), domain, file));
+    for note in &zone.skipped {
+        out.log("warn", &format!("Skipped {}", note));
+    }
+
+    let Some(planned) = plan::compute(&domain, &zone.records, "dns.import", out).await? else {
+        return Ok(());
+    };
+
+    if dry_run {
+        plan::report(&domain, &planned, out);
+    } else {
+        plan::run(&domain, &planned, prune, "dns.import", out).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,469 @@
+//! Firewall: IP access rules and custom WAF rules per zone, with expiries tracked in registry.json
+//!
+//! Access rules (`/firewall/access_rules/rules`) match an IP, range, ASN or country;
+//! custom rules live in the zone's `http_request_firewall_custom` ruleset. Rules added
+//! with `--expires` are recorded in registry `firewall` and removed by `cf firewall gc`.
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use crate::cli::{FirewallAction, FirewallCommand};
+use crate::cloudflare::{ApiError, Client};
+use crate::config::{self, FirewallExpiry};
+use crate::output::{Output, PebbleError};
+use crate::util::{format_utc, now, parse_utc};
+
+/// Ruleset phase holding a zone's custom WAF rules
+const CUSTOM_PHASE: &str = "http_request_firewall_custom";
+
+const ACCESS_RULE: &str = "access_rule";
+const CUSTOM_RULE: &str = "custom_rule";
+
+pub async fn run(cmd: FirewallCommand, out: &Output) -> Result<()> {
+    config::load_env()?;
+
+    match cmd.action {
+        FirewallAction::List { domain } => list(&domain, out).await,
+        FirewallAction::Add { domain, ip, asn, country, expression, action, note, expires } => {
+            let target = match (ip, asn, country, expression) {
+                (_, _, _, Some(expression)) => Ok(Target::Expression(expression)),
+                (Some(ip), _, _, _) => access_target_ip(&ip),
+                (_, Some(asn), _, _) => access_target_asn(&asn),
+                (_, _, Some(country), _) => access_target_country(&country),
+                _ => Err("Pass --ip, --asn, --country or --expression".to_string()),
+            };
+            let target = match target {
+                Ok(t) => t,
+                Err(msg) => {
+                    out.error(PebbleError::input("INVALID_TARGET", &msg).with_op("firewall.add"));
+                    return Ok(());
+                }
+            };
+            add(&domain, &target, &action, note.as_deref().unwrap_or(""), expires, out).await
+        }
+        FirewallAction::Remove { domain, id } => remove(&domain, &id, out).await,
+        FirewallAction::Gc { domain, dry_run } => gc(domain.as_deref(), dry_run, out).await,
+    }
+}
+
+// ============ Targets ============
+
+/// What a new rule matches
+enum Target {
+    /// Access rule configuration: target kind (ip, ip6, ip_range, asn, country) and value
+    Access(&'static str, String),
+    Expression(String),
+}
+
+fn access_target_ip(raw: &str) -> std::result::Result<Target, String> {
+    let raw = raw.trim();
+    if let Some((addr, bits)) = raw.split_once('/') {
+        if addr.parse::<IpAddr>().is_err() || bits.parse::<u8>().is_err() {
+            return Err(format!("Not an IP range: {}", raw));
+        }
+        return Ok(Target::Access("ip_range", raw.to_string()));
+    }
+    match raw.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) => Ok(Target::Access("ip", raw.to_string())),
+        Ok(IpAddr::V6(_)) => Ok(Target::Access("ip6", raw.to_string())),
+        Err(_) => Err(format!("Not an IP address: {}", raw)),
+    }
+}
+
+fn access_target_asn(raw: &str) -> std::result::Result<Target, String> {
+    let digits = raw.trim().trim_start_matches("AS").trim_start_matches("as");
+    digits.parse::<u32>()
+        .map(|n| Target::Access("asn", format!("AS{}", n)))
+        .map_err(|_| format!("Not an ASN: {}", raw))
+}
+
+fn access_target_country(raw: &str) -> std::result::Result<Target, String> {
+    let code = raw.trim().to_ascii_uppercase();
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Country must be a two-letter code, got: {}", raw));
+    }
+    Ok(Target::Access("country", code))
+}
+
+// ============ Expiry ============
+
+fn expired(entry: &FirewallExpiry, at: u64) -> bool {
+    parse_utc(&entry.expires).is_some_and(|t| t <= at)
+}
+
+// ============ Cloudflare API ============
+
+#[derive(Debug, Deserialize)]
+struct AccessRule {
+    id: String,
+    mode: String,
+    configuration: AccessConfiguration,
+    #[serde(default)]
+    notes: String,
+    #[serde(default)]
+    created_on: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccessConfiguration {
+    target: String,
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ruleset {
+    id: String,
+    #[serde(default)]
+    rules: Vec<CustomRule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CustomRule {
+    id: String,
+    action: String,
+    expression: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    enabled: Option<bool>,
+}
+
+fn access_rules_path(zone_id: &str) -> String {
+    format!("/zones/{}/firewall/access_rules/rules", zone_id)
+}
+
+fn entrypoint_path(zone_id: &str) -> String {
+    format!("/zones/{}/rulesets/phases/{}/entrypoint", zone_id, CUSTOM_PHASE)
+}
+
+async fn list_access_rules(client: &Client, zone_id: &str) -> std::result::Result<Vec<AccessRule>, ApiError> {
+    Ok(client.get_all(&access_rules_path(zone_id), &(), None).await?.items)
+}
+
+/// The zone's custom rules ruleset; None until the first rule is added
+async fn custom_ruleset(client: &Client, zone_id: &str) -> std::result::Result<Option<Ruleset>, ApiError> {
+    match client.get(&entrypoint_path(zone_id), &()).await {
+        Ok(ruleset) => Ok(Some(ruleset)),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+async fn delete_rule(client: &Client, zone_id: &str, kind: &str, id: &str) -> std::result::Result<(), ApiError> {
+    match kind {
+        CUSTOM_RULE => {
+            let Some(ruleset) = custom_ruleset(client, zone_id).await? else {
+                return Ok(());
+            };
+            if !ruleset.rules.iter().any(|r| r.id == id) {
+                return Ok(());
+            }
+            client.delete::<Value>(&format!("/zones/{}/rulesets/{}/rules/{}", zone_id, ruleset.id, id)).await?;
+        }
+        _ => {
+            client.delete::<Value>(&format!("{}/{}", access_rules_path(zone_id), id)).await?;
+        }
+    }
+    Ok(())
+}
+
+// ============ Registry ============
+
+/// Forget a removed rule's expiry (best effort)
+fn forget(id: &str) {
+    if let Ok(mut registry) = config::load_registry() {
+        if registry.firewall.remove(id).is_some() {
+            let _ = config::save_registry(&registry);
+        }
+    }
+}
+
+// ============ Commands ============
+
+async fn list(domain: &str, out: &Output) -> Result<()> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
+
+    out.log("info", &format!("Fetching firewall rules for {}", domain));
+
+    let fetched = match list_access_rules(&client, &zone_id).await {
+        Ok(rules) => custom_ruleset(&client, &zone_id).await.map(|ruleset| (rules, ruleset)),
+        Err(e) => Err(e),
+    };
+    let (access, ruleset) = match fetched {
+        Ok(f) => f,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("firewall.list")
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
+
+    let expiries = config::load_registry().map(|r| r.firewall).unwrap_or_default();
+    let expires = |id: &str| expiries.get(id).map(|e| e.expires.clone());
+
+    let access: Vec<Value> = access.iter()
+        .map(|r| json!({
+            "id": r.id,
+            "mode": r.mode,
+            "target": r.configuration.target,
+            "value": r.configuration.value,
+            "notes": r.notes,
+            "created_on": r.created_on,
+            "expires": expires(&r.id)
+        }))
+        .collect();
+    let custom: Vec<Value> = ruleset.map(|r| r.rules).unwrap_or_default().iter()
+        .map(|r| json!({
+            "id": r.id,
+            "action": r.action,
+            "expression": r.expression,
+            "description": r.description,
+            "enabled": r.enabled.unwrap_or(true),
+            "expires": expires(&r.id)
+        }))
+        .collect();
+
+    out.result(json!({
+        "success": true,
+        "domain": domain,
+        "count": access.len() + custom.len(),
+        "access_rules": access,
+        "custom_rules": custom
+    }));
+
+    Ok(())
+}
+
+async fn add(domain: &str, target: &Target, action: &str, note: &str, expires: Option<u64>, out: &Output) -> Result<()> {
+    let (kind, valid) = match target {
+        Target::Access(..) => (ACCESS_RULE, action != "log"),
+        Target::Expression(_) => (CUSTOM_RULE, action != "allow"),
+    };
+    if !valid {
+        out.error(PebbleError::input(
+            "INVALID_ACTION",
+            &format!("{} is not available for {}s", action, kind.replace('_', " ")),
+        )
+        .with_op("firewall.add"));
+        return Ok(());
+    }
+
+    let expires = expires.map(|secs| format_utc(now() + secs));
+    // Fail before creating anything if the expiry cannot be recorded
+    let mut registry = match &expires {
+        Some(_) => match config::load_registry() {
+            Ok(r) => Some(r),
+            Err(e) => {
+                out.error(PebbleError::input("REGISTRY_REQUIRED", &format!("--expires is recorded in registry.json: {:#}", e))
+                    .with_op("firewall.add"));
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    let (client, zone_id) = Client::for_domain(domain).await?;
+    let label = match &expires {
+        Some(at) if note.is_empty() => format!("expires {}", at),
+        Some(at) => format!("{} (expires {})", note, at),
+        None => note.to_string(),
+    };
+
+    let created = match target {
+        Target::Access(target, value) => {
+            out.log("info", &format!("Adding {} rule for {} {} on {}", action, target, value, domain));
+            let mode = if action == "allow" { "whitelist" } else { action };
+            let body = json!({"mode": mode, "configuration": {"target": target, "value": value}, "notes": label});
+            client.post::<AccessRule>(&access_rules_path(&zone_id), &body).await
+                .map(|r| json!({"id": r.id, "mode": r.mode, "target": r.configuration.target, "value": r.configuration.value}))
+        }
+        Target::Expression(expression) => {
+            out.log("info", &format!("Adding custom {} rule on {}", action, domain));
+            let rule = json!({"action": action, "expression": expression, "description": label, "enabled": true});
+            let ruleset = match custom_ruleset(&client, &zone_id).await {
+                Ok(Some(ruleset)) => client.post::<Ruleset>(&format!("/zones/{}/rulesets/{}/rules", zone_id, ruleset.id), &rule).await,
+                // First custom rule: create the phase entrypoint with it
+                Ok(None) => client.put::<Ruleset>(&entrypoint_path(&zone_id), &json!({"rules": [rule]})).await,
+                Err(e) => Err(e),
+            };
+            // New rules are appended to the end of the ruleset
+            ruleset.map(|r| match r.rules.last() {
+                Some(rule) => json!({"id": rule.id, "action": rule.action, "expression": rule.expression}),
+                None => json!({"id": null}),
+            })
+        }
+    };
+
+    let mut rule = match created {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("firewall.add")
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
+
+    if let (Some(at), Some(registry), Some(id)) = (&expires, registry.as_mut(), rule["id"].as_str()) {
+        registry.firewall.insert(id.to_string(), FirewallExpiry {
+            domain: domain.to_string(),
+            kind: kind.to_string(),
+            expires: at.clone(),
+            note: note.to_string(),
+        });
+        if let Err(e) = config::save_registry(registry) {
+            out.error(PebbleError::sys("WRITE_FAIL", &format!("Rule created but expiry not recorded: {:#}", e))
+                .with_op("firewall.add")
+                .with_details(json!({"rule": rule})));
+            return Ok(());
+        }
+    }
+
+    rule["kind"] = json!(kind);
+    out.result(json!({
+        "success": true,
+        "domain": domain,
+        "rule": rule,
+        "expires": expires
+    }));
+
+    Ok(())
+}
+
+async fn remove(domain: &str, id: &str, out: &Output) -> Result<()> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
+
+    out.log("info", &format!("Removing firewall rule {} from {}", id, domain));
+
+    // Custom rules are found in one request; anything else is tried as an access rule
+    let kind = match custom_ruleset(&client, &zone_id).await {
+        Ok(Some(ruleset)) if ruleset.rules.iter().any(|r| r.id == id) => CUSTOM_RULE,
+        Ok(_) => ACCESS_RULE,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("firewall.remove")
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
+
+    match delete_rule(&client, &zone_id, kind, id).await {
+        Ok(()) => {}
+        Err(e) if e.is_not_found() => {
+            out.error(PebbleError::input("RULE_NOT_FOUND", &format!("No firewall rule {} on {}", id, domain))
+                .with_op("firewall.remove")
+                .with_details(json!({"domain": domain, "id": id})));
+            return Ok(());
+        }
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("firewall.remove")
+                .with_details(json!({"domain": domain, "id": id})));
+            return Ok(());
+        }
+    }
+    forget(id);
+
+    out.result(json!({
+        "success": true,
+        "domain": domain,
+        "id": id,
+        "kind": kind
+    }));
+
+    Ok(())
+}
+
+async fn gc(only: Option<&str>, dry_run: bool, out: &Output) -> Result<()> {
+    let mut registry = match config::load_registry() {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(PebbleError::input("REGISTRY_REQUIRED", &format!("Expiries are kept in registry.json: {:#}", e))
+                .with_op("firewall.gc"));
+            return Ok(());
+        }
+    };
+
+    let at = now();
+    let mut due: BTreeMap<String, Vec<(String, FirewallExpiry)>> = BTreeMap::new();
+    let mut pending = 0;
+    for (id, entry) in &registry.firewall {
+        if only.is_some_and(|d| d != entry.domain) {
+            continue;
+        }
+        if expired(entry, at) {
+            due.entry(entry.domain.clone()).or_default().push((id.clone(), entry.clone()));
+        } else {
+            pending += 1;
+        }
+    }
+
+    let mut removed = Vec::new();
+    let mut failed = Vec::new();
+    for (domain, entries) in &due {
+        let describe = |id: &str, e: &FirewallExpiry| json!({"id": id, "domain": domain, "kind": e.kind, "expires": e.expires, "note": e.note});
+
+        if dry_run {
+            removed.extend(entries.iter().map(|(id, e)| describe(id, e)));
+            continue;
+        }
+
+        let (client, zone_id) = match Client::for_domain(domain).await {
+            Ok(c) => c,
+            Err(err) => {
+                failed.extend(entries.iter().map(|(id, e)| {
+                    let mut item = describe(id, e);
+                    item["error"] = json!(err.to_string());
+                    item
+                }));
+                continue;
+            }
+        };
+
+        for (id, entry) in entries {
+            out.log("info", &format!("Removing expired {} {} on {}", entry.kind.replace('_', " "), id, domain));
+            match delete_rule(&client, &zone_id, &entry.kind, id).await {
+                // Already gone (e.g. removed in the dashboard): just forget it
+                Ok(()) => {}
+                Err(e) if e.is_not_found() => {}
+                Err(e) => {
+                    let mut item = describe(id, entry);
+                    item["error"] = json!(e.to_string());
+                    failed.push(item);
+                    continue;
+                }
+            }
+            registry.firewall.remove(id);
+            removed.push(describe(id, entry));
+        }
+    }
+
+    if !dry_run && !removed.is_empty() {
+        if let Err(e) = config::save_registry(&registry) {
+            out.error(PebbleError::sys("WRITE_FAIL", &format!("Failed to save registry.json: {:#}", e))
+                .with_op("firewall.gc")
+                .with_details(json!({"removed": removed})));
+            return Ok(());
+        }
+    }
+
+    if !failed.is_empty() {
+        out.error(PebbleError::ext("FIREWALL_GC_FAILED", &format!("{} expired rule(s) could not be removed", failed.len()))
+            .with_op("firewall.gc")
+            .with_details(json!({"removed": removed, "failed": failed})));
+        return Ok(());
+    }
+
+    out.result(json!({
+        "success": true,
+        "dry_run": dry_run,
+        "removed": removed,
+        "pending": pending
+    }));
+
+    Ok(())
+}
@@ -1,7 +1,13 @@
 //! Command implementations
 
 pub mod dns;
+pub mod zone;
+pub mod cache;
+pub mod firewall;
+pub mod redirect;
+pub mod tunnel;
 pub mod caddy;
+pub mod origin_cert;
 pub mod service;
 pub mod registry;
 pub mod r2;
@@ -0,0 +1,178 @@
+//! Origin CA certificates: issue with a locally generated key and install on a Caddy server (via SSH)
+//!
+//! Proxied domains in Full (strict) mode need a certificate Cloudflare trusts on
+//! the origin. The private key never leaves this machine except over SSH.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::cli::{OriginCertAction, OriginCertCommand};
+use crate::cloudflare::Client;
+use crate::commands::caddy::{caddy_quote, shell_quote, ssh_exec, ssh_write};
+use crate::config;
+use crate::output::{Output, PebbleError};
+
+pub async fn run(cmd: OriginCertCommand, out: &Output) -> Result<()> {
+    config::load_env()?;
+
+    match cmd.action {
+        OriginCertAction::Issue { hostnames, server, validity, key_type, dir } => {
+            issue(&hostnames, &server, validity, &key_type, &dir, out).await
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OriginCert {
+    id: String,
+    certificate: String,
+    #[serde(default)]
+    expires_on: Option<String>,
+}
+
+/// Scratch directory for the key and CSR, removed when dropped
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new() -> Result<Self> {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let path = std::env::temp_dir().join(format!("cf-origin-cert-{}-{}", std::process::id(), nanos));
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&path).with_context(|| format!("Cannot create {}", path.display()))?;
+        Ok(Self(path))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Generate a private key and CSR with openssl; returns (key PEM, CSR PEM)
+fn generate_key_and_csr(scratch: &Scratch, common_name: &str, key_type: &str) -> Result<(String, String), String> {
+    let key_path = scratch.path().join("origin.key");
+    let csr_path = scratch.path().join("origin.csr");
+
+    let mut command = Command::new("openssl");
+    command.args(["req", "-new", "-nodes", "-subj"]).arg(format!("/CN={}", common_name));
+    match key_type {
+        "ecc" => command.args(["-newkey", "ec", "-pkeyopt", "ec_paramgen_curve:prime256v1"]),
+        _ => command.args(["-newkey", "rsa:2048"]),
+    };
+    command.arg("-keyout").arg(&key_path).arg("-out").arg(&csr_path);
+
+    let output = command.output().map_err(|e| format!("Cannot run openssl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("openssl failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    let key = std::fs::read_to_string(&key_path).map_err(|e| e.to_string())?;
+    let csr = std::fs::read_to_string(&csr_path).map_err(|e| e.to_string())?;
+    Ok((key, csr))
+}
+
+/// File name for a certificate: its first hostname, with a wildcard spelled out
+fn file_stem(hostname: &str) -> String {
+    match hostname.strip_prefix("*.") {
+        Some(rest) => format!("wildcard.{}", rest),
+        None => hostname.to_string(),
+    }
+}
+
+async fn issue(hostnames: &[String], server: &str, validity: u32, key_type: &str, dir: &str, out: &Output) -> Result<()> {
+    let hostnames: Vec<String> = hostnames.iter()
+        .map(|h| h.trim().trim_end_matches('.').to_ascii_lowercase())
+        .filter(|h| !h.is_empty())
+        .collect();
+    let Some(primary) = hostnames.first() else {
+        out.error(PebbleError::input("NO_HOSTNAMES", "Pass at least one hostname with --hostnames")
+            .with_op("origin-cert.issue"));
+        return Ok(());
+    };
+
+    // The certificate API is account-wide; the token is the one for the hostname's zone
+    let (client, _, zone) = Client::for_host(primary).await?;
+
+    let scratch = Scratch::new()?;
+    out.log("info", &format!("Generating {} key and CSR for {}", key_type.to_uppercase(), primary));
+    let (key, csr) = match generate_key_and_csr(&scratch, primary, key_type) {
+        Ok(pair) => pair,
+        Err(msg) => {
+            out.error(PebbleError::sys("OPENSSL_FAILED", &msg).with_op("origin-cert.issue"));
+            return Ok(());
+        }
+    };
+
+    out.log("info", &format!("Requesting Origin CA certificate for {}", hostnames.join(", ")));
+    let body = json!({
+        "hostnames": hostnames,
+        "requested_validity": validity,
+        "request_type": if key_type == "ecc" { "origin-ecc" } else { "origin-rsa" },
+        "csr": csr
+    });
+    let cert: OriginCert = match client.post("/certificates", &body).await {
+        Ok(c) => c,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("origin-cert.issue")
+                .with_details(json!({"zone": zone, "hostnames": hostnames})));
+            return Ok(());
+        }
+    };
+
+    let dir = dir.trim_end_matches('/');
+    let stem = file_stem(primary);
+    let cert_path = format!("{}/{}.pem", dir, stem);
+    let key_path = format!("{}/{}.key", dir, stem);
+
+    out.log("info", &format!("Installing certificate on {}", server));
+    // Caddy runs as the caddy user on packaged installs; elsewhere root keeps ownership
+    let installed = ssh_write(server, &key_path, key.as_bytes(), "600")
+        .and_then(|_| ssh_write(server, &cert_path, cert.certificate.as_bytes(), "644"))
+        .and_then(|_| ssh_exec(server, &format!(
+            "chmod 750 {dir} && (chown caddy:caddy {dir} {} {} 2>/dev/null || true)",
+            shell_quote(&cert_path), shell_quote(&key_path), dir = shell_quote(dir)
+        )));
+    if let Err(e) = installed {
+        out.error(PebbleError::net("SSH_FAILED", &format!("Certificate issued but not installed: {}", e))
+            .with_op("origin-cert.issue")
+            .with_details(json!({
+                "server": server,
+                "certificate_id": cert.id,
+                "hint": "The private key was not kept; revoke the certificate in the dashboard and issue again"
+            })));
+        return Ok(());
+    }
+
+    out.result(json!({
+        "success": true,
+        "id": cert.id,
+        "hostnames": hostnames,
+        "expires_on": cert.expires_on,
+        "key_type": key_type,
+        "validity_days": validity,
+        "server": server,
+        "cert_path": cert_path,
+        "key_path": key_path,
+        "caddy_tls": format!("tls {} {}", caddy_quote(&cert_path), caddy_quote(&key_path)),
+        "message": format!(
+            "Installed. Existing Caddyfile site blocks are not changed; use it with: cf caddy add --server {} --domain {} --upstream <addr> --tls-cert {} --tls-key {}",
+            server, primary.trim_start_matches("*."), cert_path, key_path
+        )
+    }));
+
+    Ok(())
+}
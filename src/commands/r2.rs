@@ -4,15 +4,42 @@ use anyhow::{Result, Context};
 use s3::creds::Credentials;
 use s3::bucket::Bucket;
 use s3::Region;
+use s3::request::ResponseDataStream;
 use serde_json::json;
 use std::path::Path;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio_stream::StreamExt;
 
-use crate::cli::{R2Command, R2Action};
+use crate::cli::{ByteRange, MultipartArgs, R2Command, R2Action};
+use crate::commands::cache;
+use crate::config;
 use crate::output::{Output, PebbleError};
+use crate::util::{format_utc, now};
+
+mod multipart;
+mod sync;
 
 pub async fn run(cmd: R2Command, out: &Output) -> Result<()> {
     match cmd.action {
-        R2Action::Upload { file, key, public } => upload(&file, key.as_deref(), public, out).await,
+        R2Action::Upload { file, key, public, purge, multipart, resume, abort } => {
+            if abort {
+                return abort_upload(&file, key.as_deref(), out).await;
+            }
+            upload(&file, key.as_deref(), public, purge, &multipart, resume, out).await
+        }
+        R2Action::Download { key, out: dest, range } => download(&key, dest.as_deref(), range, out).await,
+        R2Action::Sync { dir, prefix, delete, allow_bucket_root, dry_run, concurrency, multipart } => {
+            if delete && prefix.trim_matches('/').is_empty() && !allow_bucket_root {
+                out.error(PebbleError::input(
+                    "CONFIRMATION_REQUIRED",
+                    "--delete with an empty prefix removes every object in the bucket missing from the directory; pass --allow-bucket-root to confirm",
+                )
+                .with_op("r2.sync"));
+                return Ok(());
+            }
+            sync::sync(&dir, &prefix, delete, dry_run, concurrency, &multipart, out).await
+        }
+        R2Action::Presign { key, method, expires } => presign(&key, &method, expires, out).await,
         R2Action::List { prefix, limit } => list(&prefix, limit, out).await,
         R2Action::Delete { key } => delete(&key, out).await,
         R2Action::Info { key } => info(&key, out).await,
@@ -52,6 +79,13 @@ fn get_bucket() -> Result<Bucket> {
     Ok(bucket)
 }
 
+/// Whether a public URL is on the shared r2.dev domain rather than a custom domain
+fn is_r2_dev(url: &str) -> bool {
+    reqwest::Url::parse(url).ok()
+        .and_then(|u| u.host_str().map(|h| h.eq_ignore_ascii_case("r2.dev") || h.to_ascii_lowercase().ends_with(".r2.dev")))
+        .unwrap_or(false)
+}
+
 /// Get public URL base from environment
 fn get_public_url() -> String {
     std::env::var("CLOUDFLARE_R2_PUBLIC_URL")
@@ -64,39 +98,62 @@ fn get_folder_prefix() -> String {
         .unwrap_or_else(|_| "uploads/".to_string())
 }
 
-async fn upload(file_path: &str, custom_key: Option<&str>, public: bool, out: &Output) -> Result<()> {
+/// Guess a file's content type from its extension
+fn content_type(path: &Path) -> String {
+    mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .to_string()
+}
+
+/// Upload a file in a single PUT; it is read into memory, so only below the multipart threshold
+async fn put_file(bucket: &Bucket, path: &Path, key: &str, content_type: &str) -> std::result::Result<(), PebbleError> {
+    let content = tokio::fs::read(path).await
+        .map_err(|e| PebbleError::sys("READ_FAIL", &format!("Failed to read {}: {}", path.display(), e)))?;
+
+    match bucket.put_object_with_content_type(key, &content, content_type).await {
+        Ok(response) if response.status_code() == 200 => Ok(()),
+        Ok(response) => {
+            let status = response.status_code();
+            Err(PebbleError::ext("UPLOAD_FAILED", &format!("Upload failed with status: {}", status))
+                .with_details(json!({"status": status, "key": key})))
+        }
+        Err(e) => Err(PebbleError::net("UPLOAD_FAILED", &format!("Upload failed: {}", e))
+            .with_details(json!({"file": path.display().to_string(), "key": key}))),
+    }
+}
+
+/// Object key for an upload: `--key`, or the file name under the folder prefix
+fn upload_key(file_path: &str, custom_key: Option<&str>) -> String {
+    match custom_key {
+        Some(k) => k.to_string(),
+        None => {
+            let filename = Path::new(file_path).file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown");
+            format!("{}{}", get_folder_prefix(), filename)
+        }
+    }
+}
+
+async fn upload(file_path: &str, custom_key: Option<&str>, public: bool, purge: bool, multipart: &MultipartArgs, resume: bool, out: &Output) -> Result<()> {
     out.log("info", &format!("Uploading file: {}", file_path));
 
     let path = Path::new(file_path);
 
     // Check file exists
-    if !path.exists() {
+    if !path.is_file() {
         out.error(PebbleError::input("FILE_NOT_FOUND", &format!("File not found: {}", file_path))
             .with_op("r2.upload"));
         return Ok(());
     }
 
-    // Read file
-    let content = tokio::fs::read(path).await
-        .context("Failed to read file")?;
-
-    let file_size = content.len();
+    let file_size = tokio::fs::metadata(path).await
+        .context("Failed to read file")?
+        .len();
 
-    // Determine object key
-    let key = match custom_key {
-        Some(k) => k.to_string(),
-        None => {
-            let filename = path.file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown");
-            format!("{}{}", get_folder_prefix(), filename)
-        }
-    };
+    let key = upload_key(file_path, custom_key);
 
-    // Guess content type
-    let content_type = mime_guess::from_path(path)
-        .first_or_octet_stream()
-        .to_string();
+    let content_type = content_type(path);
 
     out.log("info", &format!("Key: {}, Content-Type: {}, Size: {} bytes", key, content_type, file_size));
 
@@ -110,39 +167,105 @@ async fn upload(file_path: &str, custom_key: Option<&str>, public: bool, out: &O
         }
     };
 
-    // Upload
-    match bucket.put_object_with_content_type(&key, &content, &content_type).await {
-        Ok(response) => {
-            let status = response.status_code();
+    // An unfinished upload of this key is continued with --resume, otherwise aborted
+    let checkpoint = match multipart::resumable(&bucket, &key, path, resume, out).await {
+        Ok(c) => c,
+        Err(e) => {
+            out.error(e.with_op("r2.upload"));
+            return Ok(());
+        }
+    };
 
-            if status == 200 {
-                let public_url = if public {
-                    format!("{}/{}", get_public_url(), key)
-                } else {
-                    String::new()
-                };
+    // Upload: one PUT for small files, parallel parts streamed from disk above the threshold
+    let parts = if checkpoint.is_some() || file_size >= multipart.multipart_threshold {
+        match multipart::upload(&bucket, path, &key, &content_type, multipart, checkpoint, out).await {
+            Ok(uploaded) => Some(uploaded),
+            Err(e) => {
+                out.error(e.with_op("r2.upload"));
+                return Ok(());
+            }
+        }
+    } else {
+        if let Err(e) = put_file(&bucket, path, &key, &content_type).await {
+            out.error(e.with_op("r2.upload"));
+            return Ok(());
+        }
+        None
+    };
 
-                out.result(json!({
-                    "success": true,
-                    "key": key,
-                    "size": file_size,
-                    "content_type": content_type,
-                    "public_url": public_url,
-                    "bucket": bucket.name()
-                }));
-            } else {
-                out.error(PebbleError::ext("UPLOAD_FAILED", &format!("Upload failed with status: {}", status))
-                    .with_op("r2.upload")
-                    .with_details(json!({"status": status, "key": key})));
+    let public_url = if public {
+        format!("{}/{}", get_public_url(), key)
+    } else {
+        String::new()
+    };
+
+    let mut result = json!({
+        "success": true,
+        "key": key,
+        "size": file_size,
+        "content_type": content_type,
+        "public_url": public_url,
+        "bucket": bucket.name(),
+        "parts": parts.as_ref().map(|p| p.parts),
+        "resumed_parts": parts.as_ref().map(|p| p.resumed)
+    });
+
+    if purge && is_r2_dev(&public_url) {
+        // r2.dev URLs are not in any of the account's zones, so there is nothing to purge
+        out.log("warn", &format!("Skipping purge: {} is served from r2.dev, not a Cloudflare zone", public_url));
+    } else if purge && !public_url.is_empty() {
+        // Cloudflare credentials usually live in .env; R2 settings may come from the shell
+        config::load_env().ok();
+        out.log("info", &format!("Purging cache for {}", public_url));
+        match cache::purge_urls(std::slice::from_ref(&public_url)).await {
+            Ok(summary) => result["purge"] = summary,
+            Err(e) => {
+                // The upload itself succeeded; say so alongside the purge failure
+                out.error(e.with_op("r2.upload").with_details(json!({"uploaded": result})));
+                return Ok(());
             }
         }
+    }
+
+    if purge && public_url.is_empty() {
+        out.log("warn", "No public URL to purge (--public false)");
+    }
+
+    out.result(result);
+
+    Ok(())
+}
+
+async fn abort_upload(file_path: &str, custom_key: Option<&str>, out: &Output) -> Result<()> {
+    let key = upload_key(file_path, custom_key);
+    out.log("info", &format!("Aborting unfinished upload of {}", key));
+
+    let bucket = match get_bucket() {
+        Ok(b) => b,
         Err(e) => {
-            out.error(PebbleError::net("UPLOAD_FAILED", &format!("Upload failed: {}", e))
-                .with_op("r2.upload")
-                .with_details(json!({"file": file_path, "key": key})));
+            out.error(PebbleError::sys("CONFIG_ERROR", &format!("Failed to configure R2: {}", e))
+                .with_op("r2.upload"));
+            return Ok(());
         }
+    };
+
+    let Some((checkpoint, aborted)) = multipart::abort(&bucket, &key).await else {
+        out.error(PebbleError::input("NO_CHECKPOINT", &format!("No unfinished upload of {} to abort", key))
+            .with_op("r2.upload")
+            .with_details(json!({"key": key})));
+        return Ok(());
+    };
+    if !aborted {
+        out.log("warn", &format!("Upload {} could not be aborted (it may have expired); checkpoint removed", checkpoint.upload_id));
     }
 
+    out.result(json!({
+        "success": true,
+        "key": key,
+        "upload_id": checkpoint.upload_id,
+        "aborted": aborted
+    }));
+
     Ok(())
 }
 
@@ -284,3 +407,232 @@ async fn info(key: &str, out: &Output) -> Result<()> {
 
     Ok(())
 }
+
+/// Presigned (SigV4) URL for a GET or PUT of one object, signed locally with the R2 keys
+async fn presign(key: &str, method: &str, expires: u32, out: &Output) -> Result<()> {
+    let bucket = match get_bucket() {
+        Ok(b) => b,
+        Err(e) => {
+            out.error(PebbleError::sys("CONFIG_ERROR", &format!("Failed to configure R2: {}", e))
+                .with_op("r2.presign"));
+            return Ok(());
+        }
+    };
+
+    let signed = match method {
+        "put" => bucket.presign_put(key, expires, None).await,
+        _ => bucket.presign_get(key, expires, None).await,
+    };
+    let url = match signed {
+        Ok(url) => url,
+        Err(e) => {
+            out.error(PebbleError::sys("PRESIGN_FAILED", &format!("Failed to presign URL: {}", e))
+                .with_op("r2.presign")
+                .with_details(json!({"key": key, "method": method})));
+            return Ok(());
+        }
+    };
+
+    out.result(json!({
+        "success": true,
+        "key": key,
+        "method": method.to_uppercase(),
+        "url": url,
+        "expires_in": expires,
+        "expires_at": format_utc(now() + u64::from(expires))
+    }));
+
+    Ok(())
+}
+
+/// Inclusive first and last byte of a range within an object of `size` bytes
+fn resolve_range(range: ByteRange, size: u64) -> Option<(u64, u64)> {
+    match range {
+        ByteRange::From(start, _) if start >= size => None,
+        ByteRange::From(start, end) => Some((start, end.map_or(size - 1, |e| e.min(size - 1)))),
+        ByteRange::Last(_) if size == 0 => None,
+        ByteRange::Last(n) => Some((size.saturating_sub(n), size - 1)),
+    }
+}
+
+/// Whether an ETag is the MD5 of the whole object (multipart ETags are not)
+fn is_md5_etag(etag: &str) -> bool {
+    etag.len() == 32 && etag.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Copy a response body to `writer` chunk by chunk; returns bytes written and their MD5
+async fn stream_to<W: AsyncWrite + Unpin>(body: &mut ResponseDataStream, writer: &mut W) -> Result<(u64, String)> {
+    let mut written = 0u64;
+    let mut digest = md5::Context::new();
+    while let Some(chunk) = body.bytes().next().await {
+        let chunk = chunk.context("Download interrupted")?;
+        writer.write_all(&chunk).await.context("Failed to write")?;
+        digest.consume(&chunk);
+        written += chunk.len() as u64;
+    }
+    writer.flush().await.context("Failed to write")?;
+    Ok((written, format!("{:x}", digest.compute())))
+}
+
+async fn download(key: &str, dest: Option<&str>, range: Option<ByteRange>, out: &Output) -> Result<()> {
+    let to_stdout = dest == Some("-");
+    // The object owns stdout: keep events, errors and the result on stderr
+    let stderr_out = out.to_stderr();
+    let out = if to_stdout { &stderr_out } else { out };
+
+    let path = match dest {
+        Some(d) => d.to_string(),
+        None => key.rsplit('/').next().unwrap_or_default().to_string(),
+    };
+    if path.is_empty() {
+        out.error(PebbleError::input("INVALID_OUT", &format!("Cannot name a file after {}; pass --out", key))
+            .with_op("r2.download"));
+        return Ok(());
+    }
+
+    out.log("info", &format!("Downloading {} to {}", key, if to_stdout { "stdout" } else { &path }));
+
+    let mut bucket = match get_bucket() {
+        Ok(b) => b,
+        Err(e) => {
+            out.error(PebbleError::sys("CONFIG_ERROR", &format!("Failed to configure R2: {}", e))
+                .with_op("r2.download"));
+            return Ok(());
+        }
+    };
+
+    let head = match bucket.head_object(key).await {
+        Ok((head, 200)) => head,
+        Ok((_, 404)) => {
+            out.error(PebbleError::input("NOT_FOUND", &format!("No object {}", key))
+                .with_op("r2.download")
+                .with_details(json!({"key": key})));
+            return Ok(());
+        }
+        Ok((_, status)) => {
+            out.error(PebbleError::ext("DOWNLOAD_FAILED", &format!("HEAD failed with status: {}", status))
+                .with_op("r2.download")
+                .with_details(json!({"status": status, "key": key})));
+            return Ok(());
+        }
+        Err(e) => {
+            out.error(PebbleError::net("DOWNLOAD_FAILED", &format!("Failed to get object info: {}", e))
+                .with_op("r2.download")
+                .with_details(json!({"key": key})));
+            return Ok(());
+        }
+    };
+
+    let size = head.content_length.unwrap_or(0).max(0) as u64;
+    let etag = head.e_tag.unwrap_or_default();
+    let span = match range {
+        Some(r) => match resolve_range(r, size) {
+            Some(span) => Some(span),
+            None => {
+                out.error(PebbleError::input("INVALID_RANGE", &format!("Range is outside the object ({} bytes)", size))
+                    .with_op("r2.download")
+                    .with_details(json!({"key": key, "size": size})));
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+    let expected = span.map_or(size, |(start, end)| end - start + 1);
+
+    // Fail rather than mix two versions if the object is replaced mid-download
+    if !etag.is_empty() {
+        bucket.add_header("If-Match", &etag);
+    }
+    if let Some((start, end)) = span {
+        bucket.add_header("Range", &format!("bytes={}-{}", start, end));
+    }
+
+    let mut body = match bucket.get_object_stream(key).await {
+        Ok(body) if body.status_code == 200 || body.status_code == 206 => body,
+        Ok(body) => {
+            let (code, message) = match body.status_code {
+                412 => ("OBJECT_CHANGED", "Object changed since it was inspected; try again".to_string()),
+                404 => ("NOT_FOUND", format!("No object {}", key)),
+                status => ("DOWNLOAD_FAILED", format!("Download failed with status: {}", status)),
+            };
+            out.error(PebbleError::ext(code, &message)
+                .with_op("r2.download")
+                .with_details(json!({"status": body.status_code, "key": key})));
+            return Ok(());
+        }
+        Err(e) => {
+            out.error(PebbleError::net("DOWNLOAD_FAILED", &format!("Download failed: {}", e))
+                .with_op("r2.download")
+                .with_details(json!({"key": key})));
+            return Ok(());
+        }
+    };
+
+    // Files are written next to their destination and renamed once verified
+    let part = format!("{}.part", path);
+    let streamed = if to_stdout {
+        stream_to(&mut body, &mut tokio::io::stdout()).await
+    } else {
+        match tokio::fs::File::create(&part).await {
+            Ok(mut file) => stream_to(&mut body, &mut file).await,
+            Err(e) => Err(anyhow::Error::new(e).context(format!("Cannot create {}", part))),
+        }
+    };
+    let discard = || {
+        if !to_stdout {
+            let _ = std::fs::remove_file(&part);
+        }
+    };
+
+    let (bytes, md5) = match streamed {
+        Ok(s) => s,
+        Err(e) => {
+            discard();
+            out.error(PebbleError::net("DOWNLOAD_FAILED", &format!("{:#}", e))
+                .with_op("r2.download")
+                .with_details(json!({"key": key, "path": path})));
+            return Ok(());
+        }
+    };
+
+    if bytes != expected {
+        discard();
+        out.error(PebbleError::ext("SIZE_MISMATCH", &format!("Expected {} bytes, received {}", expected, bytes))
+            .with_op("r2.download")
+            .with_details(json!({"key": key, "expected": expected, "received": bytes})));
+        return Ok(());
+    }
+
+    let plain_etag = etag.trim_matches('"');
+    let etag_verified = span.is_none() && is_md5_etag(plain_etag);
+    if etag_verified && !plain_etag.eq_ignore_ascii_case(&md5) {
+        discard();
+        out.error(PebbleError::ext("ETAG_MISMATCH", &format!("Content MD5 {} does not match ETag {}", md5, plain_etag))
+            .with_op("r2.download")
+            .with_details(json!({"key": key, "etag": etag, "md5": md5})));
+        return Ok(());
+    }
+
+    if !to_stdout {
+        if let Err(e) = std::fs::rename(&part, &path) {
+            discard();
+            out.error(PebbleError::sys("WRITE_FAIL", &format!("Cannot move download to {}: {}", path, e))
+                .with_op("r2.download"));
+            return Ok(());
+        }
+    }
+
+    let result = json!({
+        "success": true,
+        "key": key,
+        "path": path,
+        "bytes": bytes,
+        "size": size,
+        "range": span.map(|(start, end)| json!({"start": start, "end": end})),
+        "etag": etag,
+        "etag_verified": etag_verified
+    });
+    out.result(result);
+
+    Ok(())
+}
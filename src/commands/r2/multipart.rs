@@ -0,0 +1,360 @@
+//! Multipart uploads: parts streamed from disk in parallel, with a local checkpoint to resume from
+//!
+//! The checkpoint (`.cf/uploads/<hash>.json` in the project dir) records the upload ID
+//! and each finished part's ETag as soon as it lands, so `--resume` only sends what is missing.
+
+use s3::bucket::Bucket;
+use s3::command::{Command as S3Command, Multipart as S3Multipart};
+use s3::request::tokio_backend::HyperRequest;
+use s3::request::Request;
+use s3::serde_types::Part;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::cli::MultipartArgs;
+use crate::config;
+use crate::output::{Output, PebbleError};
+
+/// S3 allows at most this many parts per upload
+const MAX_PARTS: u64 = 10_000;
+
+/// Retries after the first attempt for a failed part
+const PART_RETRIES: u32 = 3;
+
+/// First backoff delay for a part; doubles on each retry
+const PART_BACKOFF_MS: u64 = 200;
+
+// ============ Checkpoints ============
+
+/// Progress of one multipart upload, saved after every part
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Checkpoint {
+    bucket: String,
+    key: String,
+    /// Canonical path, size and mtime of the source file, to tell if it changed
+    file: String,
+    size: u64,
+    modified: u64,
+    content_type: String,
+    part_size: u64,
+    pub(super) upload_id: String,
+    parts: Vec<CheckpointPart>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CheckpointPart {
+    number: u32,
+    etag: String,
+}
+
+/// Identity of a source file: canonical path, size and mtime (seconds)
+fn fingerprint(file: &Path) -> std::io::Result<(String, u64, u64)> {
+    let meta = std::fs::metadata(file)?;
+    let modified = meta.modified()?.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    Ok((file.canonicalize()?.display().to_string(), meta.len(), modified))
+}
+
+impl Checkpoint {
+    /// Checkpoint file for an object; one upload per bucket and key at a time
+    pub(super) fn path(bucket: &str, key: &str) -> PathBuf {
+        let name = format!("{:x}.json", md5::compute(format!("{}/{}", bucket, key)));
+        config::project_dir().join(".cf").join("uploads").join(name)
+    }
+
+    pub(super) fn load(bucket: &str, key: &str) -> Option<Self> {
+        let text = std::fs::read_to_string(Self::path(bucket, key)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Write via a temp file so an interrupted save never leaves a torn checkpoint
+    fn save(&self) -> std::io::Result<()> {
+        let path = Self::path(&self.bucket, &self.key);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(tmp, path)
+    }
+
+    pub(super) fn remove(&self) {
+        let _ = std::fs::remove_file(Self::path(&self.bucket, &self.key));
+    }
+
+    /// Why this checkpoint cannot continue an upload of `file`, if it cannot
+    fn mismatch(&self, file: &Path) -> Option<&'static str> {
+        match fingerprint(file) {
+            Ok((path, size, modified)) if path == self.file && size == self.size && modified == self.modified => None,
+            Ok((path, ..)) if path != self.file => Some("it was started from a different file"),
+            _ => Some("the file changed since it was started"),
+        }
+    }
+
+    fn done(&self, number: u32) -> bool {
+        self.parts.iter().any(|p| p.number == number)
+    }
+
+    fn part_len(&self, number: u32) -> u64 {
+        self.part_size.min(self.size - (number as u64 - 1) * self.part_size)
+    }
+}
+
+/// Abort the checkpointed upload of `key` (if any) and forget it; returns the checkpoint
+pub(super) async fn abort(bucket: &Bucket, key: &str) -> Option<(Checkpoint, bool)> {
+    let checkpoint = Checkpoint::load(&bucket.name(), key)?;
+    let aborted = bucket.abort_upload(key, &checkpoint.upload_id).await.is_ok();
+    checkpoint.remove();
+    Some((checkpoint, aborted))
+}
+
+/// The checkpoint to continue from when resuming an upload of `file` to `key`.
+/// A checkpoint that cannot be continued (file changed, upload expired, or not
+/// resuming) has its upload aborted and is removed, so no orphaned parts linger.
+pub(super) async fn resumable(bucket: &Bucket, key: &str, file: &Path, resume: bool, out: &Output) -> Result<Option<Checkpoint>, PebbleError> {
+    let Some(checkpoint) = Checkpoint::load(&bucket.name(), key) else {
+        if resume {
+            out.log("info", &format!("No checkpoint for {}; starting a new upload", key));
+        }
+        return Ok(None);
+    };
+
+    let stale = match checkpoint.mismatch(file) {
+        Some(reason) => Some(reason),
+        None if !resume => Some("--resume was not given"),
+        None => {
+            let listed = bucket.list_multiparts_uploads(Some(key), None).await.map_err(|e| {
+                PebbleError::net("UPLOAD_FAILED", &format!("Cannot list multipart uploads: {}", e))
+                    .with_details(json!({"key": key, "upload_id": checkpoint.upload_id}))
+            })?;
+            let open = listed.iter().flat_map(|l| &l.uploads).any(|u| u.id == checkpoint.upload_id && u.key == key);
+            (!open).then_some("the upload no longer exists")
+        }
+    };
+
+    match stale {
+        None => {
+            out.log("info", &format!(
+                "Resuming upload {}: {} of {} part(s) already uploaded",
+                checkpoint.upload_id,
+                checkpoint.parts.len(),
+                checkpoint.size.div_ceil(checkpoint.part_size).max(1)
+            ));
+            Ok(Some(checkpoint))
+        }
+        Some(reason) => {
+            out.log("warn", &format!("Discarding unfinished upload {} of {}: {}", checkpoint.upload_id, key, reason));
+            let _ = bucket.abort_upload(key, &checkpoint.upload_id).await;
+            checkpoint.remove();
+            Ok(None)
+        }
+    }
+}
+
+// ============ Parts ============
+
+/// Why a part upload failed, and whether trying again may help
+struct PartError {
+    retryable: bool,
+    message: String,
+}
+
+/// Upload one part. rust-s3's `put_multipart_chunk` aborts the whole upload on any
+/// error status, so the request is sent directly and failures are left to the caller.
+async fn put_part(bucket: &Bucket, key: &str, upload_id: &str, number: u32, chunk: &[u8], content_type: &str) -> Result<Part, PartError> {
+    let command = S3Command::PutObject {
+        content: chunk,
+        multipart: Some(S3Multipart::new(number, upload_id)),
+        content_type,
+    };
+    let sent = match HyperRequest::new(bucket, key, command).await {
+        Ok(request) => request.response_data(true).await,
+        Err(e) => Err(e),
+    };
+    match sent {
+        Ok(response) if (200..300).contains(&response.status_code()) => Ok(Part {
+            etag: response.as_str().unwrap_or_default().to_string(),
+            part_number: number,
+        }),
+        Ok(response) => Err(PartError {
+            retryable: response.status_code() == 429 || response.status_code() >= 500,
+            message: format!("part {} failed with status: {}", number, response.status_code()),
+        }),
+        Err(e) => Err(PartError { retryable: true, message: format!("part {} failed: {}", number, e) }),
+    }
+}
+
+/// Read part `number` (1-based) of the file and upload it, retrying transient failures
+async fn upload_part(bucket: &Bucket, file: &Path, key: &str, upload_id: &str, number: u32, part_size: u64, content_type: &str) -> Result<Part, String> {
+    let offset = (number as u64 - 1) * part_size;
+    let mut chunk = Vec::new();
+    let read = async {
+        let mut f = tokio::fs::File::open(file).await?;
+        f.seek(SeekFrom::Start(offset)).await?;
+        f.take(part_size).read_to_end(&mut chunk).await
+    };
+    read.await.map_err(|e| format!("Cannot read part {} of {}: {}", number, file.display(), e))?;
+
+    let mut attempt = 0;
+    loop {
+        match put_part(bucket, key, upload_id, number, &chunk, content_type).await {
+            Ok(part) => return Ok(part),
+            Err(e) if e.retryable && attempt < PART_RETRIES => {
+                tokio::time::sleep(Duration::from_millis(PART_BACKOFF_MS << attempt)).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.message),
+        }
+    }
+}
+
+// ============ Upload ============
+
+/// A finished multipart upload
+pub(super) struct Uploaded {
+    pub(super) parts: u32,
+    /// Parts taken from the checkpoint instead of sent again
+    pub(super) resumed: u32,
+}
+
+/// Upload `file` in parts, `part_concurrency` at a time, and complete the upload,
+/// continuing from `checkpoint` if given. On failure the upload and its checkpoint
+/// are kept for `--resume`.
+pub(super) async fn upload(
+    bucket: &Bucket,
+    file: &Path,
+    key: &str,
+    content_type: &str,
+    opts: &MultipartArgs,
+    checkpoint: Option<Checkpoint>,
+    out: &Output,
+) -> Result<Uploaded, PebbleError> {
+    let mut checkpoint = match checkpoint {
+        Some(c) => c,
+        None => start(bucket, file, key, content_type, opts, out).await?,
+    };
+    let (size, part_size, upload_id) = (checkpoint.size, checkpoint.part_size, checkpoint.upload_id.clone());
+    let count = size.div_ceil(part_size).max(1) as u32;
+    let resumed = checkpoint.parts.len() as u32;
+    let checkpoint_path = Checkpoint::path(&checkpoint.bucket, key);
+
+    out.log("info", &format!("Multipart upload {}: {} part(s) of {} bytes, {} at a time", upload_id, count, part_size, opts.part_concurrency));
+
+    let bucket = Arc::new(bucket.clone());
+    let semaphore = Arc::new(Semaphore::new(opts.part_concurrency));
+    let mut tasks = JoinSet::new();
+    let mut failed: Option<String> = None;
+    let mut sent: u64 = checkpoint.parts.iter().map(|p| checkpoint.part_len(p.number)).sum();
+
+    let mut finish = |joined: (u32, Result<Part, String>), checkpoint: &mut Checkpoint, failed: &mut Option<String>| {
+        let (number, outcome) = joined;
+        match outcome {
+            Ok(part) => {
+                sent += checkpoint.part_len(number);
+                checkpoint.parts.push(CheckpointPart { number, etag: part.etag });
+                if let Err(e) = checkpoint.save() {
+                    out.log("warn", &format!("Cannot save checkpoint: {}", e));
+                }
+                out.progress((sent * 100 / size.max(1)) as u8, &format!("part {}/{} uploaded ({} of {} bytes)", checkpoint.parts.len(), count, sent, size));
+            }
+            Err(e) => {
+                failed.get_or_insert(e);
+            }
+        }
+    };
+
+    let pending: Vec<u32> = (1..=count).filter(|n| !checkpoint.done(*n)).collect();
+    for number in pending {
+        let permit = semaphore.clone().acquire_owned().await.map_err(|e| PebbleError::sys("UPLOAD_FAILED", &e.to_string()))?;
+        while let Some(joined) = tasks.try_join_next() {
+            finish(joined.map_err(|e| PebbleError::sys("UPLOAD_FAILED", &e.to_string()))?, &mut checkpoint, &mut failed);
+        }
+        // Stop starting parts once one has failed for good
+        if failed.is_some() {
+            break;
+        }
+
+        let (bucket, file, key, upload_id, content_type) =
+            (Arc::clone(&bucket), file.to_path_buf(), key.to_string(), upload_id.clone(), content_type.to_string());
+        tasks.spawn(async move {
+            let outcome = upload_part(&bucket, &file, &key, &upload_id, number, part_size, &content_type).await;
+            drop(permit);
+            (number, outcome)
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        finish(joined.map_err(|e| PebbleError::sys("UPLOAD_FAILED", &e.to_string()))?, &mut checkpoint, &mut failed);
+    }
+
+    let completed = match failed {
+        Some(e) => Err(e),
+        None => {
+            let mut parts: Vec<Part> = checkpoint.parts.iter()
+                .map(|p| Part { part_number: p.number, etag: p.etag.clone() })
+                .collect();
+            parts.sort_by_key(|p| p.part_number);
+            match bucket.complete_multipart_upload(key, &upload_id, parts).await {
+                Ok(r) if r.status_code() == 200 => Ok(()),
+                Ok(r) => Err(format!("Completing the upload failed with status: {}", r.status_code())),
+                Err(e) => Err(format!("Completing the upload failed: {}", e)),
+            }
+        }
+    };
+
+    if let Err(message) = completed {
+        return Err(PebbleError::net("UPLOAD_FAILED", &message)
+            .with_details(json!({
+                "key": key,
+                "upload_id": upload_id,
+                "parts_uploaded": checkpoint.parts.len(),
+                "parts_total": count,
+                "checkpoint": checkpoint_path.display().to_string(),
+                "hint": format!("Run the same command with --resume to continue, or --abort to discard the {} uploaded part(s)", checkpoint.parts.len())
+            })));
+    }
+
+    checkpoint.remove();
+    Ok(Uploaded { parts: count, resumed })
+}
+
+/// Start a new multipart upload and record it in a checkpoint
+async fn start(bucket: &Bucket, file: &Path, key: &str, content_type: &str, opts: &MultipartArgs, out: &Output) -> Result<Checkpoint, PebbleError> {
+    let (canonical, size, modified) = fingerprint(file)
+        .map_err(|e| PebbleError::sys("READ_FAIL", &format!("Cannot read {}: {}", file.display(), e)))?;
+
+    // Grow parts rather than exceed the part limit
+    let part_size = opts.part_size.max(size.div_ceil(MAX_PARTS));
+    if part_size != opts.part_size {
+        out.log("warn", &format!("Part size raised to {} bytes to stay within {} parts", part_size, MAX_PARTS));
+    }
+
+    let upload_id = match bucket.initiate_multipart_upload(key, content_type).await {
+        Ok(r) => r.upload_id,
+        Err(e) => {
+            return Err(PebbleError::net("UPLOAD_FAILED", &format!("Cannot start multipart upload: {}", e))
+                .with_details(json!({"key": key})));
+        }
+    };
+
+    let checkpoint = Checkpoint {
+        bucket: bucket.name(),
+        key: key.to_string(),
+        file: canonical,
+        size,
+        modified,
+        content_type: content_type.to_string(),
+        part_size,
+        upload_id,
+        parts: Vec::new(),
+    };
+    if let Err(e) = checkpoint.save() {
+        out.log("warn", &format!("Cannot save checkpoint; this upload will not be resumable: {}", e));
+    }
+    Ok(checkpoint)
+}
@@ -0,0 +1,295 @@
+//! Directory sync: upload new and changed files under a prefix, optionally deleting the rest
+//!
+//! Files are compared by size first, then by ETag: a plain MD5 for single-PUT objects, or
+//! the multipart form (MD5 of the part MD5s, `-N`) recomputed locally with a matching part size.
+
+use anyhow::Result;
+use s3::bucket::Bucket;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use super::{content_type, get_bucket, multipart, put_file};
+use crate::cli::MultipartArgs;
+use crate::output::{Output, PebbleError};
+
+/// A file under the local directory and the key it syncs to
+struct LocalFile {
+    path: PathBuf,
+    key: String,
+    size: u64,
+}
+
+/// Collect regular files below `dir`, keyed by their `/`-separated path under `prefix`.
+/// Symlinked files are followed; symlinked directories are not, to avoid cycles.
+fn walk(dir: &Path, rel: &str, prefix: &str, files: &mut Vec<LocalFile>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let rel = if rel.is_empty() { name } else { format!("{}/{}", rel, name) };
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            walk(&path, &rel, prefix, files)?;
+        } else if let Ok(meta) = std::fs::metadata(&path) {
+            if meta.is_file() {
+                files.push(LocalFile { key: format!("{}{}", prefix, rel), path, size: meta.len() });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// MD5 of each `part_size` chunk of a file (one digest for a file that fits in one part)
+fn part_digests(file: &Path, part_size: u64) -> std::io::Result<Vec<md5::Digest>> {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(file)?);
+    let mut buf = vec![0; 1 << 16];
+    let mut digests = Vec::new();
+    loop {
+        let mut digest = md5::Context::new();
+        let mut part = (&mut reader).take(part_size);
+        let mut read = 0;
+        loop {
+            let n = part.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            digest.consume(&buf[..n]);
+            read += n as u64;
+        }
+        if read == 0 && !digests.is_empty() {
+            break;
+        }
+        digests.push(digest.compute());
+        if read < part_size {
+            break;
+        }
+    }
+    Ok(digests)
+}
+
+/// Whether a local file has the content an object's ETag describes
+fn same_content(file: &LocalFile, etag: &str, part_size: u64) -> std::io::Result<bool> {
+    let etag = etag.trim_matches('"').to_ascii_lowercase();
+    let Some((hash, parts)) = etag.split_once('-') else {
+        let digests = part_digests(&file.path, file.size.max(1))?;
+        return Ok(digests.first().is_some_and(|d| format!("{:x}", d) == etag));
+    };
+    let Ok(parts) = parts.parse::<u64>() else {
+        return Ok(false);
+    };
+
+    // The part size is not recorded: try ours, then the smallest whole MiB giving that many parts
+    let mib = 1 << 20;
+    let smallest = file.size.div_ceil(parts.max(1)).div_ceil(mib) * mib;
+    let mut candidates = vec![part_size, smallest];
+    candidates.dedup();
+    for size in candidates.into_iter().filter(|&p| p > 0 && file.size.div_ceil(p) == parts) {
+        let digests = part_digests(&file.path, size)?;
+        let joined: Vec<u8> = digests.iter().flat_map(|d| d.0).collect();
+        if format!("{:x}", md5::compute(joined)) == hash {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Upload one file the way `cf r2 upload` would, resuming a checkpointed upload of it
+async fn upload_one(bucket: &Bucket, file: &LocalFile, opts: &MultipartArgs, out: &Output) -> std::result::Result<(), PebbleError> {
+    let content_type = content_type(&file.path);
+    let checkpoint = multipart::resumable(bucket, &file.key, &file.path, true, out).await?;
+    if checkpoint.is_some() || file.size >= opts.multipart_threshold {
+        multipart::upload(bucket, &file.path, &file.key, &content_type, opts, checkpoint, out).await.map(|_| ())
+    } else {
+        put_file(bucket, &file.path, &file.key, &content_type).await
+    }
+}
+
+pub(super) async fn sync(
+    dir: &str,
+    prefix: &str,
+    delete: bool,
+    dry_run: bool,
+    concurrency: usize,
+    opts: &MultipartArgs,
+    out: &Output,
+) -> Result<()> {
+    let root = Path::new(dir);
+    if !root.is_dir() {
+        out.error(PebbleError::input("DIR_NOT_FOUND", &format!("Directory not found: {}", dir))
+            .with_op("r2.sync"));
+        return Ok(());
+    }
+
+    let prefix = match prefix.trim_start_matches('/') {
+        "" => String::new(),
+        p if p.ends_with('/') => p.to_string(),
+        p => format!("{}/", p),
+    };
+
+    let mut files = Vec::new();
+    if let Err(e) = walk(root, "", &prefix, &mut files) {
+        out.error(PebbleError::sys("READ_FAIL", &format!("Cannot read {}: {}", dir, e))
+            .with_op("r2.sync"));
+        return Ok(());
+    }
+    files.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let bucket = match get_bucket() {
+        Ok(b) => b,
+        Err(e) => {
+            out.error(PebbleError::sys("CONFIG_ERROR", &format!("Failed to configure R2: {}", e))
+                .with_op("r2.sync"));
+            return Ok(());
+        }
+    };
+
+    out.log("info", &format!("Comparing {} local file(s) with '{}'", files.len(), prefix));
+
+    let remote: BTreeMap<String, (u64, String)> = match bucket.list(prefix.clone(), None).await {
+        Ok(pages) => pages.into_iter()
+            .flat_map(|page| page.contents)
+            .map(|o| (o.key, (o.size, o.e_tag.unwrap_or_default())))
+            .collect(),
+        Err(e) => {
+            out.error(PebbleError::net("LIST_FAILED", &format!("Failed to list objects: {}", e))
+                .with_op("r2.sync")
+                .with_details(json!({"prefix": prefix})));
+            return Ok(());
+        }
+    };
+
+    // Plan: what to upload (and why), what to delete, what is already in sync
+    let local_keys: HashSet<String> = files.iter().map(|f| f.key.clone()).collect();
+    let mut uploads: Vec<(LocalFile, &str)> = Vec::new();
+    let mut unchanged = 0;
+    for file in files {
+        let reason = match remote.get(&file.key) {
+            None => Some("new"),
+            Some((size, _)) if *size != file.size => Some("size changed"),
+            Some((_, etag)) => match same_content(&file, etag, opts.part_size) {
+                Ok(true) => None,
+                Ok(false) => Some("content changed"),
+                Err(e) => {
+                    out.error(PebbleError::sys("READ_FAIL", &format!("Cannot read {}: {}", file.path.display(), e))
+                        .with_op("r2.sync"));
+                    return Ok(());
+                }
+            },
+        };
+        match reason {
+            Some(reason) => uploads.push((file, reason)),
+            None => unchanged += 1,
+        }
+    }
+    let extra: Vec<(&String, u64)> = remote.iter()
+        .filter(|(key, _)| !local_keys.contains(key.as_str()))
+        .map(|(key, (size, _))| (key, *size))
+        .collect();
+
+    let mut plan: Vec<Value> = uploads.iter()
+        .map(|(f, reason)| json!({"action": "upload", "key": f.key, "path": f.path.display().to_string(), "size": f.size, "reason": reason}))
+        .collect();
+    plan.extend(extra.iter().map(|(key, size)| json!({
+        "action": if delete { "delete" } else { "keep" },
+        "key": key,
+        "size": size,
+        "reason": "not in local directory"
+    })));
+    let summary = json!({
+        "upload": uploads.len(),
+        "delete": if delete { extra.len() } else { 0 },
+        "remote_only": if delete { 0 } else { extra.len() },
+        "unchanged": unchanged
+    });
+
+    if dry_run {
+        out.result(json!({
+            "success": true,
+            "dry_run": true,
+            "dir": dir,
+            "prefix": prefix,
+            "summary": summary,
+            "plan": plan
+        }));
+        return Ok(());
+    }
+
+    out.log("info", &format!("Uploading {} file(s), {} at a time", uploads.len(), concurrency));
+
+    let bucket = Arc::new(bucket);
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut tasks = JoinSet::new();
+    let total = uploads.len() + if delete { extra.len() } else { 0 };
+    let mut done = 0;
+    let mut uploaded = Vec::new();
+    let mut failed = Vec::new();
+
+    let mut finish = |(key, outcome): (String, std::result::Result<(), PebbleError>), uploaded: &mut Vec<String>, failed: &mut Vec<Value>| {
+        done += 1;
+        let status = if outcome.is_ok() { "uploaded" } else { "failed" };
+        out.progress((done * 100 / total.max(1)) as u8, &format!("{} {}", status, key));
+        match outcome {
+            Ok(()) => uploaded.push(key),
+            Err(e) => failed.push(json!({"key": key, "error": e.message})),
+        }
+    };
+
+    for (file, _) in uploads {
+        let permit = semaphore.clone().acquire_owned().await?;
+        while let Some(joined) = tasks.try_join_next() {
+            finish(joined?, &mut uploaded, &mut failed);
+        }
+
+        let (bucket, opts, task_out) = (Arc::clone(&bucket), opts.clone(), out.clone());
+        tasks.spawn(async move {
+            let outcome = upload_one(&bucket, &file, &opts, &task_out).await;
+            drop(permit);
+            (file.key, outcome)
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        finish(joined?, &mut uploaded, &mut failed);
+    }
+    uploaded.sort();
+
+    let mut deleted = Vec::new();
+    if delete {
+        for (key, _) in &extra {
+            let outcome = match bucket.delete_object(key.as_str()).await {
+                Ok(r) if r.status_code() == 204 || r.status_code() == 200 => Ok(()),
+                Ok(r) => Err(format!("Delete failed with status: {}", r.status_code())),
+                Err(e) => Err(format!("Failed to delete object: {}", e)),
+            };
+            done += 1;
+            let status = if outcome.is_ok() { "deleted" } else { "failed" };
+            out.progress((done * 100 / total.max(1)) as u8, &format!("{} {}", status, key));
+            match outcome {
+                Ok(()) => deleted.push(key.to_string()),
+                Err(e) => failed.push(json!({"key": key, "error": e})),
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        out.error(PebbleError::ext("SYNC_FAILED", &format!("{} of {} change(s) failed", failed.len(), total))
+            .with_op("r2.sync")
+            .with_details(json!({"summary": summary, "uploaded": uploaded, "deleted": deleted, "failed": failed})));
+        return Ok(());
+    }
+
+    out.result(json!({
+        "success": true,
+        "dry_run": false,
+        "dir": dir,
+        "prefix": prefix,
+        "summary": summary,
+        "uploaded": uploaded,
+        "deleted": deleted
+    }));
+
+    Ok(())
+}
@@ -0,0 +1,450 @@
+//! Redirects: Cloudflare single redirect rules (the `http_request_dynamic_redirect` phase)
+//!
+//! Each redirect matches one host and exact path and sends a static target URL.
+//! `import` syncs the phase from a CSV kept in version control next to registry.json.
+
+use anyhow::Result;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::cli::{parse_redirect_status, RedirectAction, RedirectCommand};
+use crate::cloudflare::{ApiError, Client};
+use crate::config;
+use crate::output::{Output, PebbleError};
+use crate::util::split_csv_line;
+
+/// Ruleset phase holding single redirects
+const REDIRECT_PHASE: &str = "http_request_dynamic_redirect";
+
+pub async fn run(cmd: RedirectCommand, out: &Output) -> Result<()> {
+    config::load_env()?;
+
+    match cmd.action {
+        RedirectAction::List { domain } => list(&domain, out).await,
+        RedirectAction::Add { domain, from, to, status, preserve_query, note } => {
+            let redirect = match Redirect::new(&domain, &from, &to, status, preserve_query) {
+                Ok(r) => r,
+                Err(msg) => {
+                    out.error(PebbleError::input("INVALID_REDIRECT", &msg).with_op("redirect.add"));
+                    return Ok(());
+                }
+            };
+            add(&domain, &redirect, note.as_deref(), out).await
+        }
+        RedirectAction::Remove { domain, id, from } => remove(&domain, id.as_deref(), from.as_deref(), out).await,
+        RedirectAction::Import { domain, file, prune, dry_run } => import(&domain, &file, prune, dry_run, out).await,
+    }
+}
+
+// ============ Redirects ============
+
+/// One desired redirect
+#[derive(Debug, Clone, PartialEq)]
+struct Redirect {
+    host: String,
+    path: String,
+    to: String,
+    status: u16,
+    preserve_query: bool,
+}
+
+impl Redirect {
+    fn new(domain: &str, from: &str, to: &str, status: u16, preserve_query: bool) -> std::result::Result<Self, String> {
+        let (host, path) = parse_from(domain, from)?;
+        let target = Url::parse(to.trim()).map_err(|_| format!("Target must be an absolute URL, got: {}", to))?;
+        if !matches!(target.scheme(), "http" | "https") {
+            return Err(format!("Target must be an http(s) URL, got: {}", to));
+        }
+        Ok(Self { host, path, to: to.trim().to_string(), status, preserve_query })
+    }
+
+    fn from(&self) -> String {
+        format!("{}{}", self.host, self.path)
+    }
+
+    fn expression(&self) -> String {
+        expression(&self.host, &self.path)
+    }
+
+    /// The rule as sent to the rulesets API, keeping `id` for rules that already exist
+    fn rule(&self, id: Option<String>, description: Option<&str>) -> RedirectRule {
+        RedirectRule {
+            id,
+            action: "redirect".to_string(),
+            expression: self.expression(),
+            description: description.map(String::from).unwrap_or_else(|| format!("{} -> {}", self.from(), self.to)),
+            enabled: Some(true),
+            action_parameters: json!({
+                "from_value": {
+                    "status_code": self.status,
+                    "target_url": {"value": self.to},
+                    "preserve_query_string": self.preserve_query
+                }
+            }),
+        }
+    }
+
+    /// Whether an existing rule already redirects the same way
+    fn matches(&self, rule: &RedirectRule) -> bool {
+        let from = &rule.action_parameters["from_value"];
+        rule.expression == self.expression()
+            && from["target_url"]["value"] == self.to.as_str()
+            && from["status_code"] == self.status
+            && from["preserve_query_string"].as_bool().unwrap_or(false) == self.preserve_query
+    }
+}
+
+/// Split `--from` into host and path; a bare path is on the domain itself
+fn parse_from(domain: &str, from: &str) -> std::result::Result<(String, String), String> {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    let from = from.trim();
+    let bare = from.trim_start_matches("https://").trim_start_matches("http://");
+
+    let (host, path) = match bare.find('/') {
+        Some(0) => (domain.clone(), bare.to_string()),
+        Some(i) => (bare[..i].to_ascii_lowercase(), bare[i..].to_string()),
+        None => (bare.to_ascii_lowercase(), "/".to_string()),
+    };
+    if host != domain && !host.ends_with(&format!(".{}", domain)) {
+        return Err(format!("{} is not on {}", from, domain));
+    }
+    if path.contains(['?', '#']) {
+        return Err(format!("--from matches a path only (no query or fragment), got: {}", from));
+    }
+    Ok((host, path))
+}
+
+/// Rules language string literal
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn expression(host: &str, path: &str) -> String {
+    format!("(http.host eq {} and http.request.uri.path eq {})", quote(host), quote(path))
+}
+
+/// Host and path of an expression written by `expression`; None for hand-made rules
+fn parse_expression(expr: &str) -> Option<(String, String)> {
+    let rest = expr.strip_prefix("(http.host eq ")?;
+    let (host, rest) = read_quoted(rest)?;
+    let rest = rest.strip_prefix(" and http.request.uri.path eq ")?;
+    let (path, rest) = read_quoted(rest)?;
+    (rest == ")").then_some((host, path))
+}
+
+/// Read a leading string literal, returning it unescaped and the remaining input
+fn read_quoted(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => value.push(chars.next()?.1),
+            '"' => return Some((value, &s[i + 2..])),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+// ============ Cloudflare API ============
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RedirectRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    action: String,
+    expression: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    enabled: Option<bool>,
+    #[serde(default)]
+    action_parameters: Value,
+}
+
+impl RedirectRule {
+    fn from(&self) -> Option<String> {
+        parse_expression(&self.expression).map(|(host, path)| format!("{}{}", host, path))
+    }
+
+    fn summary(&self) -> Value {
+        let from = &self.action_parameters["from_value"];
+        json!({
+            "id": self.id,
+            "from": self.from(),
+            "to": from["target_url"]["value"],
+            "status": from["status_code"],
+            "preserve_query": from["preserve_query_string"].as_bool().unwrap_or(false),
+            "enabled": self.enabled.unwrap_or(true),
+            "description": self.description,
+            "expression": self.expression
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Ruleset {
+    id: String,
+    #[serde(default)]
+    rules: Vec<RedirectRule>,
+}
+
+fn entrypoint_path(zone_id: &str) -> String {
+    format!("/zones/{}/rulesets/phases/{}/entrypoint", zone_id, REDIRECT_PHASE)
+}
+
+/// The zone's redirect ruleset; None until the first redirect is added
+async fn redirect_ruleset(client: &Client, zone_id: &str) -> std::result::Result<Option<Ruleset>, ApiError> {
+    match client.get(&entrypoint_path(zone_id), &()).await {
+        Ok(ruleset) => Ok(Some(ruleset)),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replace every rule in the phase (creating the entrypoint if needed)
+async fn put_rules(client: &Client, zone_id: &str, rules: &[RedirectRule]) -> std::result::Result<Ruleset, ApiError> {
+    client.put(&entrypoint_path(zone_id), &json!({"rules": rules})).await
+}
+
+/// Fetch the ruleset for a command, reporting failures on `out`
+async fn fetch(client: &Client, zone_id: &str, domain: &str, op: &str, out: &Output) -> Option<Vec<RedirectRule>> {
+    match redirect_ruleset(client, zone_id).await {
+        Ok(ruleset) => Some(ruleset.map(|r| r.rules).unwrap_or_default()),
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op(op)
+                .with_details(json!({"domain": domain})));
+            None
+        }
+    }
+}
+
+// ============ CSV ============
+
+/// Parse redirects from CSV with a header row (from,to[,status][,preserve_query])
+fn parse_csv(domain: &str, text: &str) -> std::result::Result<Vec<Redirect>, String> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty() && !l.starts_with('#'));
+    let header: Vec<String> = match lines.next() {
+        Some(h) => split_csv_line(h).into_iter().map(|c| c.trim().to_lowercase()).collect(),
+        None => return Ok(Vec::new()),
+    };
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let (Some(from_col), Some(to_col)) = (column("from"), column("to")) else {
+        return Err("CSV header must name at least the from and to columns".to_string());
+    };
+    let (status_col, query_col) = (column("status"), column("preserve_query"));
+
+    let mut redirects: Vec<Redirect> = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let row = i + 2;
+        let fields = split_csv_line(line);
+        let field = |col: Option<usize>| col.and_then(|c| fields.get(c)).map(|v| v.trim()).filter(|v| !v.is_empty());
+
+        let from = field(Some(from_col)).ok_or_else(|| format!("row {}: from is empty", row))?;
+        let to = field(Some(to_col)).ok_or_else(|| format!("row {}: to is empty", row))?;
+        let status = match field(status_col) {
+            Some(s) => parse_redirect_status(s).map_err(|e| format!("row {}: {}", row, e))?,
+            None => 301,
+        };
+        let preserve_query = field(query_col).is_some_and(|v| v.eq_ignore_ascii_case("true"));
+
+        let redirect = Redirect::new(domain, from, to, status, preserve_query).map_err(|e| format!("row {}: {}", row, e))?;
+        if redirects.iter().any(|r| r.expression() == redirect.expression()) {
+            return Err(format!("row {}: {} is listed more than once", row, redirect.from()));
+        }
+        redirects.push(redirect);
+    }
+    Ok(redirects)
+}
+
+// ============ Commands ============
+
+async fn list(domain: &str, out: &Output) -> Result<()> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
+
+    out.log("info", &format!("Fetching redirects for {}", domain));
+
+    let Some(rules) = fetch(&client, &zone_id, domain, "redirect.list", out).await else {
+        return Ok(());
+    };
+
+    out.result(json!({
+        "success": true,
+        "domain": domain,
+        "count": rules.len(),
+        "redirects": rules.iter().map(RedirectRule::summary).collect::<Vec<_>>()
+    }));
+
+    Ok(())
+}
+
+async fn add(domain: &str, redirect: &Redirect, note: Option<&str>, out: &Output) -> Result<()> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
+
+    let ruleset = match redirect_ruleset(&client, &zone_id).await {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("redirect.add")
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
+
+    if let Some(existing) = ruleset.as_ref().and_then(|r| r.rules.iter().find(|r| r.expression == redirect.expression())) {
+        out.error(PebbleError::input("REDIRECT_EXISTS", &format!("{} already redirects; remove it first or use import", redirect.from()))
+            .with_op("redirect.add")
+            .with_details(json!({"existing": existing.summary()})));
+        return Ok(());
+    }
+
+    out.log("info", &format!("Redirecting {} -> {} ({})", redirect.from(), redirect.to, redirect.status));
+
+    let rule = redirect.rule(None, note);
+    let created = match &ruleset {
+        Some(ruleset) => client.post::<Ruleset>(&format!("/zones/{}/rulesets/{}/rules", zone_id, ruleset.id), &rule).await,
+        None => put_rules(&client, &zone_id, &[rule]).await,
+    };
+    // New rules are appended to the end of the ruleset
+    let created = match created {
+        Ok(r) => r.rules.into_iter().last(),
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("redirect.add")
+                .with_details(json!({"domain": domain, "from": redirect.from()})));
+            return Ok(());
+        }
+    };
+
+    let mut result = created.map(|r| r.summary()).unwrap_or_default();
+    result["success"] = json!(true);
+    result["domain"] = json!(domain);
+    out.result(result);
+
+    Ok(())
+}
+
+async fn remove(domain: &str, id: Option<&str>, from: Option<&str>, out: &Output) -> Result<()> {
+    let expression = match from.map(|f| parse_from(domain, f)) {
+        Some(Ok((host, path))) => Some(expression(&host, &path)),
+        Some(Err(msg)) => {
+            out.error(PebbleError::input("INVALID_REDIRECT", &msg).with_op("redirect.remove"));
+            return Ok(());
+        }
+        None => None,
+    };
+
+    let (client, zone_id) = Client::for_domain(domain).await?;
+    let ruleset = match redirect_ruleset(&client, &zone_id).await {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("redirect.remove")
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
+
+    let found = ruleset.as_ref().and_then(|ruleset| {
+        ruleset.rules.iter()
+            .find(|r| id.is_some_and(|id| r.id.as_deref() == Some(id)) || expression.as_ref().is_some_and(|e| *e == r.expression))
+            .map(|rule| (ruleset.id.clone(), rule.clone()))
+    });
+    let Some((ruleset_id, rule)) = found else {
+        out.error(PebbleError::input("REDIRECT_NOT_FOUND", &format!("No redirect for {} on {}", id.or(from).unwrap_or(""), domain))
+            .with_op("redirect.remove")
+            .with_details(json!({"domain": domain})));
+        return Ok(());
+    };
+
+    let rule_id = rule.id.clone().unwrap_or_default();
+    out.log("info", &format!("Removing redirect {}", rule.from().unwrap_or_else(|| rule_id.clone())));
+
+    if let Err(e) = client.delete::<Value>(&format!("/zones/{}/rulesets/{}/rules/{}", zone_id, ruleset_id, rule_id)).await {
+        out.error(e.to_pebble()
+            .with_op("redirect.remove")
+            .with_details(json!({"domain": domain, "id": rule_id})));
+        return Ok(());
+    }
+
+    let mut result = rule.summary();
+    result["success"] = json!(true);
+    result["domain"] = json!(domain);
+    out.result(result);
+
+    Ok(())
+}
+
+async fn import(domain: &str, file: &str, prune: bool, dry_run: bool, out: &Output) -> Result<()> {
+    let desired = match std::fs::read_to_string(file).map_err(|e| format!("Cannot read {}: {}", file, e))
+        .and_then(|text| parse_csv(domain, &text))
+    {
+        Ok(d) => d,
+        Err(msg) => {
+            out.error(PebbleError::input("INVALID_REDIRECTS", &msg)
+                .with_op("redirect.import")
+                .with_details(json!({"file": file})));
+            return Ok(());
+        }
+    };
+
+    let (client, zone_id) = Client::for_domain(domain).await?;
+    let Some(current) = fetch(&client, &zone_id, domain, "redirect.import", out).await else {
+        return Ok(());
+    };
+
+    // Keep existing rules in order (updated in place), then append new ones
+    let mut rules = Vec::new();
+    let mut changes = Vec::new();
+    let (mut unchanged, mut deleted) = (0, 0);
+    for rule in &current {
+        match desired.iter().find(|d| d.expression() == rule.expression) {
+            Some(want) if want.matches(rule) => {
+                unchanged += 1;
+                rules.push(rule.clone());
+            }
+            Some(want) => {
+                let updated = want.rule(rule.id.clone(), None);
+                changes.push(json!({"action": "update", "from": want.from(), "before": rule.summary(), "after": updated.summary()}));
+                rules.push(updated);
+            }
+            None if prune => {
+                deleted += 1;
+                changes.push(json!({"action": "delete", "from": rule.from(), "before": rule.summary()}));
+            }
+            None => rules.push(rule.clone()),
+        }
+    }
+    for want in desired.iter().filter(|d| !current.iter().any(|r| r.expression == d.expression())) {
+        let created = want.rule(None, None);
+        changes.push(json!({"action": "create", "from": want.from(), "after": created.summary()}));
+        rules.push(created);
+    }
+
+    let created = changes.iter().filter(|c| c["action"] == "create").count();
+    let updated = changes.len() - created - deleted;
+    let summary = json!({"create": created, "update": updated, "delete": deleted, "unchanged": unchanged});
+
+    if !dry_run && !changes.is_empty() {
+        out.log("info", &format!("Applying {} redirect change(s) to {}", changes.len(), domain));
+        if let Err(e) = put_rules(&client, &zone_id, &rules).await {
+            out.error(e.to_pebble()
+                .with_op("redirect.import")
+                .with_details(json!({"domain": domain, "summary": summary})));
+            return Ok(());
+        }
+    }
+
+    out.result(json!({
+        "success": true,
+        "domain": domain,
+        "dry_run": dry_run,
+        "in_sync": changes.is_empty(),
+        "summary": summary,
+        "changes": changes
+    }));
+
+    Ok(())
+}
@@ -4,7 +4,9 @@ use anyhow::Result;
 use serde_json::json;
 
 use crate::cli::{RegistryCommand, RegistryAction};
-use crate::config::{self, DnsRecord};
+use crate::cloudflare::ApiError;
+use crate::commands::dns;
+use crate::config::{self, DnsRecord, DomainConfig};
 use crate::output::{Output, PebbleError};
 
 pub async fn run(cmd: RegistryCommand, out: &Output) -> Result<()> {
@@ -14,6 +16,7 @@ pub async fn run(cmd: RegistryCommand, out: &Output) -> Result<()> {
             add(&domain, &name, &ip, &desc, out).await
         }
         RegistryAction::Stats => stats(out).await,
+        RegistryAction::Import { domain } => import(&domain, out).await,
     }
 }
 
@@ -98,6 +101,8 @@ async fn add(domain: &str, name: &str, ip: &str, desc: &str, out: &Output) -> Re
         name: name.to_string(),
         content: ip.to_string(),
         desc: desc.to_string(),
+        proxied: None,
+        ttl: None,
     };
 
     domain_config.records.push(new_record);
@@ -174,6 +179,128 @@ async fn stats(out: &Output) -> Result<()> {
     Ok(())
 }
 
+async fn import(domain: &str, out: &Output) -> Result<()> {
+    config::load_env()?;
+
+    out.log("info", &format!("Importing Cloudflare records for {} into registry", domain));
+
+    let mut registry = match config::load_registry() {
+        Ok(r) => r,
+        Err(e) => {
+            out.error(PebbleError::input("PARSE_FAIL", &format!("Failed to load registry.json: {}", e))
+                .with_op("registry.import"));
+            return Ok(());
+        }
+    };
+
+    let (zone_id, live) = match dns::fetch_registry_records(domain).await {
+        Ok(r) => r,
+        Err(e) => match e.downcast_ref::<ApiError>() {
+            Some(api) => {
+                out.error(api.to_pebble()
+                    .with_op("registry.import")
+                    .with_details(json!({"domain": domain})));
+                return Ok(());
+            }
+            None => return Err(e),
+        },
+    };
+
+    let domain_config = registry.domains
+        .entry(domain.to_string())
+        .or_insert_with(|| DomainConfig { zone_id: zone_id.clone(), records: Vec::new() });
+    domain_config.zone_id = zone_id.clone();
+
+    let same_slot = |r: &DnsRecord, l: &DnsRecord| {
+        r.record_type.eq_ignore_ascii_case(&l.record_type)
+            && dns::relative_name(domain, &r.name).eq_ignore_ascii_case(&l.name)
+    };
+
+    let mut claimed = vec![false; domain_config.records.len()];
+    let mut unmatched = Vec::new();
+    let (mut added, mut updated, mut unchanged) = (0, 0, 0);
+
+    // Exact matches keep their entry; an empty desc is filled from the comment and
+    // proxy status and TTL follow the live record
+    for l in live {
+        let wanted = dns::normalize_content(&l.record_type, &l.content);
+        let found = domain_config.records.iter().enumerate().position(|(i, r)| {
+            !claimed[i] && same_slot(r, &l) && dns::normalize_content(&l.record_type, &r.content) == wanted
+        });
+        match found {
+            Some(i) => {
+                claimed[i] = true;
+                let r = &mut domain_config.records[i];
+                let mut changed = r.proxied != l.proxied || r.ttl != l.ttl;
+                r.proxied = l.proxied;
+                r.ttl = l.ttl;
+                if r.desc.is_empty() && !l.desc.is_empty() {
+                    r.desc = l.desc.clone();
+                    changed = true;
+                }
+                if changed {
+                    updated += 1;
+                } else {
+                    unchanged += 1;
+                }
+            }
+            None => unmatched.push(l),
+        }
+    }
+
+    // Remaining live records take over an unclaimed entry for the same name/type, or are appended
+    for l in unmatched {
+        let found = domain_config.records.iter().enumerate()
+            .position(|(i, r)| !claimed[i] && same_slot(r, &l));
+        match found {
+            Some(i) => {
+                claimed[i] = true;
+                let r = &mut domain_config.records[i];
+                r.content = l.content;
+                r.proxied = l.proxied;
+                r.ttl = l.ttl;
+                if r.desc.is_empty() {
+                    r.desc = l.desc;
+                }
+                updated += 1;
+            }
+            None => {
+                domain_config.records.push(l);
+                claimed.push(true);
+                added += 1;
+            }
+        }
+    }
+
+    let registry_only: Vec<_> = domain_config.records.iter().zip(&claimed)
+        .filter(|(r, c)| !**c && r.record_type != "comment")
+        .map(|(r, _)| json!({"type": r.record_type, "name": r.name, "content": r.content}))
+        .collect();
+
+    registry.updated = chrono_lite_date();
+
+    match config::save_registry(&registry) {
+        Ok(_) => {
+            out.result(json!({
+                "success": true,
+                "domain": domain,
+                "zone_id": zone_id,
+                "added": added,
+                "updated": updated,
+                "unchanged": unchanged,
+                "registry_only": registry_only,
+                "path": config::project_dir().join("registry.json")
+            }));
+        }
+        Err(e) => {
+            out.error(PebbleError::sys("WRITE_FAIL", &format!("Failed to save registry.json: {}", e))
+                .with_op("registry.import"));
+        }
+    }
+
+    Ok(())
+}
+
 /// Simple date function (avoid chrono dependency)
 fn chrono_lite_date() -> String {
     use std::process::Command;
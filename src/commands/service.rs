@@ -89,7 +89,7 @@ async fn health(url: &str, timeout_secs: u64, out: &Output) -> Result<()> {
         Ok(resp) => {
             let elapsed = start.elapsed();
             let status = resp.status().as_u16();
-            let healthy = status >= 200 && status < 400;
+            let healthy = (200..400).contains(&status);
 
             // Try to get response body
             let body = resp.text().await.unwrap_or_default();
@@ -0,0 +1,421 @@
+//! Cloudflare Tunnel commands: tunnels, ingress rules and the proxied CNAMEs routed to them
+//!
+//! Routes are recorded in registry.json `tunnels` (hostname -> tunnel ID) so
+//! `cf dns plan/apply` leaves tunnel CNAMEs alone.
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::cli::{TunnelAction, TunnelCommand};
+use crate::cloudflare::{ApiError, Client};
+use crate::commands::dns;
+use crate::config;
+use crate::output::{Output, PebbleError};
+
+/// Env var naming the account that owns the tunnels (otherwise taken from the zone)
+const ACCOUNT_ID_ENV: &str = "CLOUDFLARE_ACCOUNT_ID";
+
+/// Rule appended when a config has no catch-all; cloudflared requires one
+const CATCH_ALL: &str = "http_status:404";
+
+pub async fn run(cmd: TunnelCommand, out: &Output) -> Result<()> {
+    config::load_env()?;
+
+    match cmd.action {
+        TunnelAction::List { domain } => list(&domain, out).await,
+        TunnelAction::Create { domain, name } => create(&domain, &name, out).await,
+        TunnelAction::Route { domain, tunnel, name, service } => route(&domain, &tunnel, &name, &service, out).await,
+        TunnelAction::Ingress { domain, tunnel, remove } => ingress(&domain, &tunnel, remove.as_deref(), out).await,
+    }
+}
+
+// ============ Cloudflare API ============
+
+#[derive(Debug, Deserialize)]
+struct CfTunnel {
+    id: String,
+    name: String,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    created_at: Option<String>,
+    #[serde(default)]
+    connections: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ZoneInfo {
+    account: AccountRef,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountRef {
+    id: String,
+}
+
+/// Client, zone and account a tunnel command works against
+struct Account {
+    client: Client,
+    zone_id: String,
+    account_id: String,
+}
+
+impl Account {
+    fn tunnels_path(&self) -> String {
+        format!("/accounts/{}/cfd_tunnel", self.account_id)
+    }
+
+    fn tunnel_path(&self, tunnel_id: &str) -> String {
+        format!("/accounts/{}/cfd_tunnel/{}", self.account_id, tunnel_id)
+    }
+}
+
+/// Resolve the account for a domain's credentials, reporting failures on `out`
+async fn account(domain: &str, op: &str, out: &Output) -> Result<Option<Account>> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
+
+    let account_id = match std::env::var(ACCOUNT_ID_ENV).ok().filter(|v| !v.trim().is_empty()) {
+        Some(id) => id,
+        None => match client.get::<ZoneInfo>(&format!("/zones/{}", zone_id), &()).await {
+            Ok(zone) => zone.account.id,
+            Err(e) => {
+                out.error(e.to_pebble()
+                    .with_op(op)
+                    .with_details(json!({"domain": domain})));
+                return Ok(None);
+            }
+        },
+    };
+
+    Ok(Some(Account { client, zone_id, account_id }))
+}
+
+async fn list_tunnels(account: &Account) -> std::result::Result<Vec<CfTunnel>, ApiError> {
+    let page = account.client.get_all(&account.tunnels_path(), &[("is_deleted", "false")], None).await?;
+    Ok(page.items)
+}
+
+/// Find a live tunnel by ID or name
+async fn find_tunnel(account: &Account, name_or_id: &str) -> std::result::Result<Option<CfTunnel>, ApiError> {
+    Ok(list_tunnels(account).await?
+        .into_iter()
+        .find(|t| t.id == name_or_id || t.name == name_or_id))
+}
+
+/// Look up the tunnel for a command, reporting a missing one as an input error
+async fn require_tunnel(account: &Account, name_or_id: &str, op: &str, out: &Output) -> Option<CfTunnel> {
+    match find_tunnel(account, name_or_id).await {
+        Ok(Some(t)) => Some(t),
+        Ok(None) => {
+            out.error(PebbleError::input("TUNNEL_NOT_FOUND", &format!("No tunnel named or with ID {}", name_or_id))
+                .with_op(op)
+                .with_details(json!({"tunnel": name_or_id})));
+            None
+        }
+        Err(e) => {
+            out.error(e.to_pebble().with_op(op));
+            None
+        }
+    }
+}
+
+/// A tunnel's remote configuration (`{}` when none has been pushed yet)
+async fn get_config(account: &Account, tunnel_id: &str) -> std::result::Result<Value, ApiError> {
+    let result: Value = account.client.get(&format!("{}/configurations", account.tunnel_path(tunnel_id)), &()).await?;
+    Ok(match &result["config"] {
+        Value::Object(_) => result["config"].clone(),
+        _ => json!({}),
+    })
+}
+
+async fn put_config(account: &Account, tunnel_id: &str, config: &Value) -> std::result::Result<Value, ApiError> {
+    account.client.put(&format!("{}/configurations", account.tunnel_path(tunnel_id)), &json!({"config": config})).await
+}
+
+/// CNAME target that routes a hostname into a tunnel
+fn tunnel_target(tunnel_id: &str) -> String {
+    format!("{}.cfargotunnel.com", tunnel_id)
+}
+
+// ============ Ingress Rules ============
+
+fn rules(config: &Value) -> Vec<Value> {
+    config["ingress"].as_array().cloned().unwrap_or_default()
+}
+
+/// Route `hostname` to `service`, replacing any rule for it and keeping the catch-all last
+fn upsert_rule(config: &mut Value, hostname: &str, service: &str) {
+    let mut rules = rules(config);
+    rules.retain(|r| !r["hostname"].as_str().is_some_and(|h| h.eq_ignore_ascii_case(hostname)));
+
+    let catch_all = rules.iter().position(|r| r.get("hostname").is_none()).unwrap_or(rules.len());
+    rules.insert(catch_all, json!({"hostname": hostname, "service": service}));
+    if !rules.iter().any(|r| r.get("hostname").is_none()) {
+        rules.push(json!({"service": CATCH_ALL}));
+    }
+    config["ingress"] = json!(rules);
+}
+
+/// Drop the rule for `hostname`; returns whether there was one
+fn remove_rule(config: &mut Value, hostname: &str) -> bool {
+    let mut rules = rules(config);
+    let before = rules.len();
+    rules.retain(|r| !r["hostname"].as_str().is_some_and(|h| h.eq_ignore_ascii_case(hostname)));
+    let removed = rules.len() != before;
+    config["ingress"] = json!(rules);
+    removed
+}
+
+/// Services cloudflared accepts: a URL (http, https, tcp, ssh, rdp, ...), unix socket or built-in
+fn valid_service(service: &str) -> bool {
+    service.split_once("://").is_some_and(|(scheme, rest)| !scheme.is_empty() && !rest.is_empty())
+        || service.starts_with("unix:")
+        || service.starts_with("http_status:")
+        || service == "hello_world"
+}
+
+// ============ Registry ============
+
+/// Record or forget a route in registry.json (best effort; no registry means nothing to record)
+fn record_route(fqdn: &str, tunnel_id: Option<&str>) -> bool {
+    let Ok(mut registry) = config::load_registry() else {
+        return false;
+    };
+    match tunnel_id {
+        Some(id) => {
+            registry.tunnels.insert(fqdn.to_string(), id.to_string());
+        }
+        None => {
+            registry.tunnels.remove(fqdn);
+        }
+    }
+    config::save_registry(&registry).is_ok()
+}
+
+// ============ Commands ============
+
+async fn list(domain: &str, out: &Output) -> Result<()> {
+    let Some(account) = account(domain, "tunnel.list", out).await? else {
+        return Ok(());
+    };
+
+    out.log("info", "Fetching tunnels");
+
+    let tunnels = match list_tunnels(&account).await {
+        Ok(t) => t,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("tunnel.list")
+                .with_details(json!({"account_id": account.account_id})));
+            return Ok(());
+        }
+    };
+
+    let routes = config::load_registry().map(|r| r.tunnels).unwrap_or_default();
+
+    let tunnels: Vec<Value> = tunnels.iter()
+        .map(|t| {
+            let mut hostnames: Vec<&String> = routes.iter().filter(|(_, id)| **id == t.id).map(|(h, _)| h).collect();
+            hostnames.sort();
+            json!({
+                "id": t.id,
+                "name": t.name,
+                "status": t.status,
+                "created_at": t.created_at,
+                "connections": t.connections.len(),
+                "routes": hostnames
+            })
+        })
+        .collect();
+
+    out.result(json!({
+        "success": true,
+        "account_id": account.account_id,
+        "count": tunnels.len(),
+        "tunnels": tunnels
+    }));
+
+    Ok(())
+}
+
+async fn create(domain: &str, name: &str, out: &Output) -> Result<()> {
+    let Some(account) = account(domain, "tunnel.create", out).await? else {
+        return Ok(());
+    };
+
+    match find_tunnel(&account, name).await {
+        Ok(Some(existing)) => {
+            out.error(PebbleError::input("TUNNEL_EXISTS", &format!("Tunnel {} already exists", name))
+                .with_op("tunnel.create")
+                .with_details(json!({"id": existing.id, "name": existing.name})));
+            return Ok(());
+        }
+        Ok(None) => {}
+        Err(e) => {
+            out.error(e.to_pebble().with_op("tunnel.create"));
+            return Ok(());
+        }
+    }
+
+    out.log("info", &format!("Creating tunnel {}", name));
+
+    // Remotely managed: ingress lives in Cloudflare, so the connector only needs the token
+    let body = json!({"name": name, "config_src": "cloudflare"});
+    let tunnel: CfTunnel = match account.client.post(&account.tunnels_path(), &body).await {
+        Ok(t) => t,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("tunnel.create")
+                .with_details(json!({"name": name})));
+            return Ok(());
+        }
+    };
+
+    let token: String = match account.client.get(&format!("{}/token", account.tunnel_path(&tunnel.id)), &()).await {
+        Ok(t) => t,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("tunnel.create")
+                .with_details(json!({"id": tunnel.id, "name": tunnel.name})));
+            return Ok(());
+        }
+    };
+
+    out.result(json!({
+        "success": true,
+        "id": tunnel.id,
+        "name": tunnel.name,
+        "token": token,
+        "run_command": format!("cloudflared tunnel run --token {}", token)
+    }));
+
+    Ok(())
+}
+
+async fn route(domain: &str, tunnel: &str, name: &str, service: &str, out: &Output) -> Result<()> {
+    if !valid_service(service) {
+        out.error(PebbleError::input(
+            "INVALID_SERVICE",
+            &format!("Service must be a URL like http://localhost:3000, unix:/path, http_status:404 or hello_world, got: {}", service),
+        )
+        .with_op("tunnel.route"));
+        return Ok(());
+    }
+
+    let Some(account) = account(domain, "tunnel.route", out).await? else {
+        return Ok(());
+    };
+    let Some(tunnel) = require_tunnel(&account, tunnel, "tunnel.route", out).await else {
+        return Ok(());
+    };
+    let fqdn = dns::fqdn(domain, name);
+
+    out.log("info", &format!("Routing {} -> {} via tunnel {}", fqdn, service, tunnel.name));
+
+    let mut config = match get_config(&account, &tunnel.id).await {
+        Ok(c) => c,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("tunnel.route")
+                .with_details(json!({"tunnel_id": tunnel.id})));
+            return Ok(());
+        }
+    };
+    upsert_rule(&mut config, &fqdn, service);
+
+    if let Err(e) = put_config(&account, &tunnel.id, &config).await {
+        out.error(e.to_pebble()
+            .with_op("tunnel.route")
+            .with_details(json!({"tunnel_id": tunnel.id, "fqdn": fqdn})));
+        return Ok(());
+    }
+
+    let comment = format!("tunnel {}", tunnel.name);
+    let record = match dns::ensure_proxied_cname(&account.client, &account.zone_id, domain, name, &tunnel_target(&tunnel.id), &comment).await {
+        Ok(r) => r,
+        Err(e) => {
+            // The ingress rule is in place; only the DNS side is missing
+            out.error(e.with_op("tunnel.route").with_details(json!({"tunnel_id": tunnel.id, "ingress_updated": true})));
+            return Ok(());
+        }
+    };
+
+    let recorded = record_route(&fqdn, Some(&tunnel.id));
+    if !recorded {
+        out.log("warn", "registry.json not found or not writable; route not recorded");
+    }
+
+    out.result(json!({
+        "success": true,
+        "fqdn": fqdn,
+        "service": service,
+        "tunnel": {"id": tunnel.id, "name": tunnel.name},
+        "dns": record,
+        "ingress": rules(&config),
+        "registry": recorded
+    }));
+
+    Ok(())
+}
+
+async fn ingress(domain: &str, tunnel: &str, remove: Option<&str>, out: &Output) -> Result<()> {
+    let Some(account) = account(domain, "tunnel.ingress", out).await? else {
+        return Ok(());
+    };
+    let Some(tunnel) = require_tunnel(&account, tunnel, "tunnel.ingress", out).await else {
+        return Ok(());
+    };
+
+    let mut config = match get_config(&account, &tunnel.id).await {
+        Ok(c) => c,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("tunnel.ingress")
+                .with_details(json!({"tunnel_id": tunnel.id})));
+            return Ok(());
+        }
+    };
+
+    let Some(name) = remove else {
+        out.result(json!({
+            "success": true,
+            "tunnel": {"id": tunnel.id, "name": tunnel.name},
+            "ingress": rules(&config)
+        }));
+        return Ok(());
+    };
+
+    let fqdn = dns::fqdn(domain, name);
+    out.log("info", &format!("Removing route {} from tunnel {}", fqdn, tunnel.name));
+
+    let removed = remove_rule(&mut config, &fqdn);
+    if removed {
+        if let Err(e) = put_config(&account, &tunnel.id, &config).await {
+            out.error(e.to_pebble()
+                .with_op("tunnel.ingress")
+                .with_details(json!({"tunnel_id": tunnel.id, "fqdn": fqdn})));
+            return Ok(());
+        }
+    }
+
+    let dns_deleted = match dns::delete_cname_to(&account.client, &account.zone_id, &fqdn, &tunnel_target(&tunnel.id)).await {
+        Ok(d) => d,
+        Err(e) => {
+            out.error(e.with_op("tunnel.ingress").with_details(json!({"ingress_removed": removed})));
+            return Ok(());
+        }
+    };
+    record_route(&fqdn, None);
+
+    out.result(json!({
+        "success": true,
+        "fqdn": fqdn,
+        "tunnel": {"id": tunnel.id, "name": tunnel.name},
+        "removed": removed,
+        "dns_deleted": dns_deleted,
+        "ingress": rules(&config)
+    }));
+
+    Ok(())
+}
@@ -0,0 +1,299 @@
+//! Zone settings: SSL mode, Always Use HTTPS, minimum TLS version, HSTS and the rest of `/zones/:id/settings`
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::cli::{ZoneAction, ZoneCommand, ZoneSettingsAction};
+use crate::cloudflare::{ApiError, Client};
+use crate::config;
+use crate::output::{Output, PebbleError};
+
+/// Desired setting values keyed by Cloudflare setting ID
+type Profile = BTreeMap<String, Value>;
+
+pub async fn run(cmd: ZoneCommand, out: &Output) -> Result<()> {
+    config::load_env()?;
+
+    match cmd.action {
+        ZoneAction::Settings { action } => match action {
+            ZoneSettingsAction::Get { domain, settings } => get(&domain, &settings, out).await,
+            ZoneSettingsAction::Set {
+                domain, ssl, always_https, min_tls, hsts_max_age, hsts_subdomains, hsts_preload, values, dry_run,
+            } => {
+                let mut profile = Profile::new();
+                if let Some(ssl) = ssl {
+                    profile.insert("ssl".into(), json!(ssl));
+                }
+                if let Some(always_https) = always_https {
+                    profile.insert("always_use_https".into(), json!(always_https));
+                }
+                if let Some(min_tls) = min_tls {
+                    profile.insert("min_tls_version".into(), json!(min_tls));
+                }
+                if let Some(max_age) = hsts_max_age {
+                    profile.insert("security_header".into(), json!({
+                        "strict_transport_security": {
+                            "enabled": max_age > 0,
+                            "max_age": max_age,
+                            "include_subdomains": hsts_subdomains,
+                            "preload": hsts_preload
+                        }
+                    }));
+                }
+                for pair in &values {
+                    let Some((id, value)) = pair.split_once('=').filter(|(id, _)| !id.is_empty()) else {
+                        out.error(PebbleError::input("INVALID_SETTING", &format!("Expected SETTING=VALUE, got: {}", pair))
+                            .with_op("zone.settings.set"));
+                        return Ok(());
+                    };
+                    profile.insert(id.to_string(), parse_value(value));
+                }
+                enforce(&domain, &profile, dry_run, "zone.settings.set", out).await
+            }
+            ZoneSettingsAction::Apply { domain, profile, dry_run } => {
+                let desired = match load_profile(&profile) {
+                    Ok(p) => p,
+                    Err(msg) => {
+                        out.error(PebbleError::input("INVALID_PROFILE", &msg)
+                            .with_op("zone.settings.apply")
+                            .with_details(json!({"profile": profile})));
+                        return Ok(());
+                    }
+                };
+                enforce(&domain, &desired, dry_run, "zone.settings.apply", out).await
+            }
+        },
+    }
+}
+
+// ============ Cloudflare API ============
+
+#[derive(Debug, Deserialize)]
+struct CfSetting {
+    id: String,
+    value: Value,
+    /// Absent means editable; plan-locked settings report false
+    #[serde(default)]
+    editable: Option<bool>,
+}
+
+impl CfSetting {
+    fn editable(&self) -> bool {
+        self.editable != Some(false)
+    }
+}
+
+async fn fetch_settings(client: &Client, zone_id: &str) -> std::result::Result<BTreeMap<String, CfSetting>, ApiError> {
+    let settings: Vec<CfSetting> = client.get(&format!("/zones/{}/settings", zone_id), &()).await?;
+    Ok(settings.into_iter().map(|s| (s.id.clone(), s)).collect())
+}
+
+async fn patch_setting(client: &Client, zone_id: &str, id: &str, value: &Value) -> std::result::Result<CfSetting, ApiError> {
+    client.patch(&format!("/zones/{}/settings/{}", zone_id, id), &json!({"value": value})).await
+}
+
+// ============ Values ============
+
+/// Command-line value: JSON for objects and arrays, otherwise a plain string
+fn parse_value(raw: &str) -> Value {
+    let raw = raw.trim();
+    if raw.starts_with('{') || raw.starts_with('[') {
+        if let Ok(v) = serde_json::from_str(raw) {
+            return v;
+        }
+    }
+    json!(raw)
+}
+
+/// Give a string value the JSON type of the current one (e.g. "14400" for a numeric TTL)
+fn coerce(current: &Value, desired: &Value) -> Value {
+    let Value::String(s) = desired else {
+        return desired.clone();
+    };
+    match current {
+        Value::Number(_) => s.parse::<u64>().map(|n| json!(n)).unwrap_or_else(|_| desired.clone()),
+        Value::Bool(_) => s.parse::<bool>().map(|b| json!(b)).unwrap_or_else(|_| desired.clone()),
+        _ => desired.clone(),
+    }
+}
+
+/// Whether `current` already has every field `desired` specifies
+fn satisfies(current: &Value, desired: &Value) -> bool {
+    match (current, desired) {
+        (Value::Object(have), Value::Object(want)) => want.iter()
+            .all(|(k, v)| have.get(k).is_some_and(|h| satisfies(h, v))),
+        _ => current == desired,
+    }
+}
+
+/// `current` with the fields of `desired` laid over it, so partial objects keep the rest
+fn merge(current: &Value, desired: &Value) -> Value {
+    match (current, desired) {
+        (Value::Object(have), Value::Object(want)) => {
+            let mut merged = have.clone();
+            for (k, v) in want {
+                let value = merge(have.get(k).unwrap_or(&Value::Null), v);
+                merged.insert(k.clone(), value);
+            }
+            Value::Object(merged)
+        }
+        _ => desired.clone(),
+    }
+}
+
+fn load_profile(path: &str) -> std::result::Result<Profile, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read profile {}: {}", path, e))?;
+    serde_json::from_str(&text)
+        .map_err(|e| format!("Profile {} must be a JSON object of setting IDs to values: {}", path, e))
+}
+
+// ============ Commands ============
+
+async fn get(domain: &str, only: &[String], out: &Output) -> Result<()> {
+    let (client, zone_id) = Client::for_domain(domain).await?;
+
+    out.log("info", &format!("Fetching zone settings for {}", domain));
+
+    let settings = match fetch_settings(&client, &zone_id).await {
+        Ok(s) => s,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op("zone.settings.get")
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
+
+    let unknown: Vec<&String> = only.iter().filter(|id| !settings.contains_key(*id)).collect();
+    if !unknown.is_empty() {
+        out.error(unknown_settings(&unknown, &settings).with_op("zone.settings.get"));
+        return Ok(());
+    }
+
+    let values: BTreeMap<&String, &Value> = settings.iter()
+        .filter(|(id, _)| only.is_empty() || only.contains(id))
+        .map(|(id, s)| (id, &s.value))
+        .collect();
+
+    out.result(json!({
+        "success": true,
+        "domain": domain,
+        "count": values.len(),
+        "settings": values
+    }));
+
+    Ok(())
+}
+
+fn unknown_settings(unknown: &[&String], settings: &BTreeMap<String, CfSetting>) -> PebbleError {
+    PebbleError::input(
+        "UNKNOWN_SETTING",
+        &format!("Zone has no setting named {}", unknown.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ")),
+    )
+    .with_details(json!({"unknown": unknown, "available": settings.keys().collect::<Vec<_>>()}))
+}
+
+/// Diff `desired` against the zone and update the settings that drifted (unless `dry_run`)
+async fn enforce(domain: &str, desired: &Profile, dry_run: bool, op: &str, out: &Output) -> Result<()> {
+    if desired.is_empty() {
+        out.error(PebbleError::input("NO_CHANGES", "No settings given").with_op(op));
+        return Ok(());
+    }
+
+    let (client, zone_id) = Client::for_domain(domain).await?;
+    let current = match fetch_settings(&client, &zone_id).await {
+        Ok(s) => s,
+        Err(e) => {
+            out.error(e.to_pebble()
+                .with_op(op)
+                .with_details(json!({"domain": domain})));
+            return Ok(());
+        }
+    };
+
+    // Reject typos before changing anything
+    let unknown: Vec<&String> = desired.keys().filter(|id| !current.contains_key(*id)).collect();
+    if !unknown.is_empty() {
+        out.error(unknown_settings(&unknown, &current).with_op(op));
+        return Ok(());
+    }
+
+    let mut results = Vec::new();
+    let (mut in_sync, mut drift, mut updated, mut failed) = (0, 0, 0, 0);
+
+    for (id, want) in desired {
+        let setting = &current[id];
+        let want = coerce(&setting.value, want);
+
+        if satisfies(&setting.value, &want) {
+            in_sync += 1;
+            results.push(json!({"setting": id, "status": "in_sync", "current": setting.value}));
+            continue;
+        }
+
+        drift += 1;
+        let target = merge(&setting.value, &want);
+
+        if dry_run {
+            results.push(json!({
+                "setting": id,
+                "status": "drift",
+                "current": setting.value,
+                "desired": target,
+                "editable": setting.editable()
+            }));
+            continue;
+        }
+        if !setting.editable() {
+            failed += 1;
+            results.push(json!({
+                "setting": id,
+                "status": "failed",
+                "current": setting.value,
+                "desired": target,
+                "error": "Setting is not editable on this zone's plan"
+            }));
+            continue;
+        }
+
+        out.log("info", &format!("Updating {} on {}", id, domain));
+        match patch_setting(&client, &zone_id, id, &target).await {
+            Ok(after) => {
+                updated += 1;
+                results.push(json!({"setting": id, "status": "updated", "before": setting.value, "after": after.value}));
+            }
+            Err(e) => {
+                failed += 1;
+                results.push(json!({
+                    "setting": id,
+                    "status": "failed",
+                    "current": setting.value,
+                    "desired": target,
+                    "error": e.to_string()
+                }));
+            }
+        }
+    }
+
+    let summary = json!({"in_sync": in_sync, "drift": drift, "updated": updated, "failed": failed});
+
+    if failed > 0 {
+        out.error(PebbleError::ext("ZONE_SETTINGS_FAILED", &format!("{} of {} setting(s) could not be updated", failed, drift))
+            .with_op(op)
+            .with_details(json!({"domain": domain, "summary": summary, "settings": results})));
+        return Ok(());
+    }
+
+    out.result(json!({
+        "success": true,
+        "domain": domain,
+        "dry_run": dry_run,
+        "in_sync": drift == 0,
+        "summary": summary,
+        "settings": results
+    }));
+
+    Ok(())
+}
@@ -5,6 +5,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+use crate::cloudflare::Client;
+
 /// Project directory (where .env and registry.json are)
 pub fn project_dir() -> PathBuf {
     // Check CF_PROJECT_DIR env first
@@ -23,29 +25,107 @@ pub fn load_env() -> Result<()> {
     Ok(())
 }
 
+/// Credential problems that should surface as auth/input errors rather than internal ones
+#[derive(Debug, thiserror::Error)]
+pub enum CredentialsError {
+    #[error("Missing API token for {domain}. Set {token_env} (or CLOUDFLARE_API_TOKEN) in your .env file.")]
+    MissingToken { domain: String, token_env: String },
+
+    #[error("Zone not found for {domain}: the API token cannot see it or it is not on Cloudflare")]
+    ZoneNotFound { domain: String },
+
+    #[error("{legacy} is no longer read because first-label names collide across domains; rename it to {replacement} in your .env file.")]
+    LegacyEnv { legacy: String, replacement: String },
+}
+
+/// Convert a domain to its env var slug (e.g., "example.org" -> "EXAMPLE_ORG")
+pub fn domain_slug(domain: &str) -> String {
+    domain
+        .trim_end_matches('.')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+/// Reject a legacy first-label variable (CLOUDFLARE_EXAMPLE_<KEY>) that is set
+/// while its full-domain replacement is not, instead of silently ignoring it
+fn check_legacy_env(domain: &str, slug: &str, key: &str) -> std::result::Result<(), CredentialsError> {
+    let first_label = domain.split('.').next().unwrap_or(domain).to_uppercase();
+    if first_label == slug {
+        return Ok(());
+    }
+    let legacy = format!("CLOUDFLARE_{}_{}", first_label, key);
+    let replacement = format!("CLOUDFLARE_{}_{}", slug, key);
+    if std::env::var(&replacement).is_err() && std::env::var(&legacy).is_ok() {
+        return Err(CredentialsError::LegacyEnv { legacy, replacement });
+    }
+    Ok(())
+}
+
 /// Get Cloudflare credentials for a domain
 ///
-/// Converts domain name to environment variable names.
-/// Example: example.com -> CLOUDFLARE_EXAMPLE_ZONE_ID, CLOUDFLARE_EXAMPLE_API_TOKEN
-pub fn get_cf_credentials(domain: &str) -> Result<(String, String)> {
-    // Convert domain to env var name slug (e.g., "example.com" -> "EXAMPLE")
-    let domain_slug = domain
-        .split('.')
-        .next()
-        .unwrap_or(domain)
-        .to_uppercase();
-
-    let zone_env = format!("CLOUDFLARE_{}_ZONE_ID", domain_slug);
-    let token_env = format!("CLOUDFLARE_{}_API_TOKEN", domain_slug);
-
-    let zone_id = std::env::var(&zone_env)
-        .with_context(|| format!("Missing env var: {}. Add it to your .env file.", zone_env))?;
+/// The API token comes from CLOUDFLARE_<SLUG>_API_TOKEN, falling back to
+/// CLOUDFLARE_API_TOKEN. The zone ID comes from CLOUDFLARE_<SLUG>_ZONE_ID,
+/// then registry.json, and is otherwise looked up via the API and cached in
+/// registry.json. Legacy first-label names (CLOUDFLARE_EXAMPLE_API_TOKEN,
+/// CLOUDFLARE_EXAMPLE_ZONE_ID) are an error so they cannot apply to the wrong domain.
+/// Example: example.org -> CLOUDFLARE_EXAMPLE_ORG_ZONE_ID, CLOUDFLARE_EXAMPLE_ORG_API_TOKEN
+pub async fn get_cf_credentials(domain: &str) -> Result<(String, String)> {
+    let slug = domain_slug(domain);
+    check_legacy_env(domain, &slug, "API_TOKEN")?;
+    check_legacy_env(domain, &slug, "ZONE_ID")?;
+
+    let token_env = format!("CLOUDFLARE_{}_API_TOKEN", slug);
     let api_token = std::env::var(&token_env)
-        .with_context(|| format!("Missing env var: {}. Add it to your .env file.", token_env))?;
+        .or_else(|_| std::env::var("CLOUDFLARE_API_TOKEN"))
+        .map_err(|_| CredentialsError::MissingToken { domain: domain.to_string(), token_env })?;
+
+    if let Ok(zone_id) = std::env::var(format!("CLOUDFLARE_{}_ZONE_ID", slug)) {
+        return Ok((zone_id, api_token));
+    }
+
+    let cached = load_registry().ok()
+        .and_then(|r| r.domains.get(domain).map(|c| c.zone_id.clone()))
+        .filter(|id| !id.is_empty());
+    if let Some(zone_id) = cached {
+        return Ok((zone_id, api_token));
+    }
+
+    let zone_id = lookup_zone_id(domain, &api_token).await?;
+    cache_zone_id(domain, &zone_id);
 
     Ok((zone_id, api_token))
 }
 
+#[derive(Debug, Deserialize)]
+struct ZoneRef {
+    id: String,
+}
+
+/// Find a zone's ID by name with GET /zones?name=
+async fn lookup_zone_id(domain: &str, api_token: &str) -> Result<String> {
+    let zones: Vec<ZoneRef> = Client::new(api_token)
+        .get("/zones", &[("name", domain)])
+        .await?;
+
+    match zones.into_iter().next() {
+        Some(zone) => Ok(zone.id),
+        None => Err(CredentialsError::ZoneNotFound { domain: domain.to_string() }.into()),
+    }
+}
+
+/// Remember a looked-up zone ID in registry.json (best effort; no registry means no cache)
+fn cache_zone_id(domain: &str, zone_id: &str) {
+    let Ok(mut registry) = load_registry() else { return };
+
+    registry.domains
+        .entry(domain.to_string())
+        .or_insert_with(|| DomainConfig { zone_id: String::new(), records: Vec::new() })
+        .zone_id = zone_id.to_string();
+
+    let _ = save_registry(&registry);
+}
+
 // ============ Registry Types ============
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -56,6 +136,9 @@ pub struct Registry {
     pub servers: HashMap<String, ServerInfo>,
     #[serde(default)]
     pub tunnels: HashMap<String, String>,
+    /// Temporary firewall rules by rule ID, removed by `cf firewall gc` once expired
+    #[serde(default)]
+    pub firewall: HashMap<String, FirewallExpiry>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -74,6 +157,23 @@ pub struct DnsRecord {
     pub content: String,
     #[serde(default)]
     pub desc: String,
+    /// None = not managed here, the live value is kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+    /// Seconds, 1 = auto; None = not managed here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirewallExpiry {
+    pub domain: String,
+    /// "access_rule" or "custom_rule"
+    pub kind: String,
+    /// UTC, e.g. 2024-06-01T12:00:00Z
+    pub expires: String,
+    #[serde(default)]
+    pub note: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -2,10 +2,15 @@
 //!
 //! Pebble Spec v1.1 compliant
 
+// The manifest's json! literal outgrows the default macro recursion limit
+#![recursion_limit = "256"]
+
 mod cli;
+mod cloudflare;
 mod commands;
 mod config;
 mod output;
+mod util;
 
 use clap::Parser;
 use cli::{Cli, Commands};
@@ -34,6 +39,12 @@ async fn main() {
 
     let result = match command {
         Commands::Dns(cmd) => commands::dns::run(cmd, &out).await,
+        Commands::Zone(cmd) => commands::zone::run(cmd, &out).await,
+        Commands::Cache(cmd) => commands::cache::run(cmd, &out).await,
+        Commands::Firewall(cmd) => commands::firewall::run(cmd, &out).await,
+        Commands::Redirect(cmd) => commands::redirect::run(cmd, &out).await,
+        Commands::Tunnel(cmd) => commands::tunnel::run(cmd, &out).await,
+        Commands::OriginCert(cmd) => commands::origin_cert::run(cmd, &out).await,
         Commands::Caddy(cmd) => commands::caddy::run(cmd, &out).await,
         Commands::Service(cmd) => commands::service::run(cmd, &out).await,
         Commands::Registry(cmd) => commands::registry::run(cmd, &out).await,
@@ -41,6 +52,6 @@ async fn main() {
     };
 
     if let Err(e) = result {
-        out.error(PebbleError::sys("INTERNAL", &e.to_string()));
+        out.error(PebbleError::from(&e));
     }
 }
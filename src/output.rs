@@ -18,29 +18,46 @@ struct Event<T: Serialize> {
     payload: T,
 }
 
-fn emit<T: Serialize>(event_type: &str, payload: T) {
+fn emit<T: Serialize>(event_type: &str, payload: T, to_stderr: bool) {
     let event = Event {
         v: SCHEMA_VERSION,
         event_type: event_type.to_string(),
         payload,
     };
-    println!("{}", serde_json::to_string(&event).unwrap());
+    let line = serde_json::to_string(&event).unwrap();
+    if to_stderr {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
 }
 
 /// Output handler
+#[derive(Clone)]
 pub struct Output {
     agent_mode: bool,
+    /// Send events and results to stderr because stdout carries data
+    stderr_only: bool,
 }
 
 impl Output {
     pub fn new(agent_mode: bool) -> Self {
-        Self { agent_mode }
+        Self { agent_mode, stderr_only: false }
+    }
+
+    /// Same output mode, but with every event on stderr (for commands writing data to stdout)
+    pub fn to_stderr(&self) -> Self {
+        Self { stderr_only: true, ..self.clone() }
+    }
+
+    fn emit<T: Serialize>(&self, event_type: &str, payload: T) {
+        emit(event_type, payload, self.stderr_only);
     }
 
     /// Log message (stderr for human, JSON Lines for agent)
     pub fn log(&self, level: &str, message: &str) {
         if self.agent_mode {
-            emit("log", json!({"level": level, "message": message}));
+            self.emit("log", json!({"level": level, "message": message}));
         } else {
             eprintln!("[{}] {}", level.to_uppercase(), message);
         }
@@ -49,16 +66,18 @@ impl Output {
     /// Progress update
     pub fn progress(&self, percent: u8, message: &str) {
         if self.agent_mode {
-            emit("progress", json!({"percent": percent, "message": message}));
+            self.emit("progress", json!({"percent": percent, "message": message}));
         } else {
             eprintln!("[{:3}%] {}", percent, message);
         }
     }
 
-    /// Final result (always JSON to stdout)
+    /// Final result (always JSON to stdout, unless stdout carries data)
     pub fn result<T: Serialize>(&self, data: T) {
         if self.agent_mode {
-            emit("result", data);
+            self.emit("result", data);
+        } else if self.stderr_only {
+            eprintln!("{}", serde_json::to_string_pretty(&data).unwrap());
         } else {
             // Human mode: pretty print to stdout
             println!("{}", serde_json::to_string_pretty(&data).unwrap());
@@ -68,7 +87,7 @@ impl Output {
     /// Error output (Pebble Spec v1.1)
     pub fn error(&self, err: PebbleError) {
         if self.agent_mode {
-            emit("error", &err);
+            self.emit("error", &err);
         } else {
             eprintln!("Error [{}][{}]: {}", err.cat, err.code, err.message.as_deref().unwrap_or(""));
             if err.retryable {
@@ -198,9 +217,13 @@ impl PebbleError {
         self
     }
 
-    /// Add details
+    /// Add details (object fields are merged into existing details)
     pub fn with_details<T: Serialize>(mut self, details: T) -> Self {
-        self.details = Some(serde_json::to_value(details).unwrap());
+        let details = serde_json::to_value(details).unwrap();
+        match (self.details.as_mut(), details) {
+            (Some(Value::Object(existing)), Value::Object(extra)) => existing.extend(extra),
+            (_, details) => self.details = Some(details),
+        }
         self
     }
 
@@ -215,6 +238,27 @@ impl PebbleError {
     }
 }
 
+/// Map a command failure to its Pebble error: credential and zone lookup
+/// problems are auth/input errors, API failures keep their category, and
+/// anything else is internal
+impl From<&anyhow::Error> for PebbleError {
+    fn from(e: &anyhow::Error) -> Self {
+        use crate::config::CredentialsError;
+
+        match e.downcast_ref::<CredentialsError>() {
+            Some(CredentialsError::MissingToken { .. }) => PebbleError::auth("MISSING_TOKEN", &e.to_string()),
+            Some(CredentialsError::ZoneNotFound { domain }) => PebbleError::input("ZONE_NOT_FOUND", &e.to_string())
+                .with_details(json!({"domain": domain})),
+            Some(CredentialsError::LegacyEnv { legacy, replacement }) => PebbleError::input("LEGACY_ENV_VAR", &e.to_string())
+                .with_details(json!({"legacy": legacy, "replacement": replacement})),
+            None => match e.downcast_ref::<crate::cloudflare::ApiError>() {
+                Some(api) => api.to_pebble(),
+                None => PebbleError::sys("INTERNAL", &e.to_string()),
+            },
+        }
+    }
+}
+
 /// Print manifest (--manifest) - Pebble Spec v1.1
 pub fn print_manifest() {
     let manifest = json!({
@@ -230,47 +274,360 @@ pub fn print_manifest() {
             "agent": true,
             "interactive": false,
             "streaming": false,
-            "resume": false
+            "resume": true
         },
         "actions": [
             {
                 "id": "dns.list",
-                "summary": "List DNS records",
+                "summary": "List DNS records (all pages, with server-side filters)",
                 "args": [],
                 "options": [
-                    {"name": "domain", "short": "d", "type": "string", "required": true}
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "type", "short": "t", "type": "string"},
+                    {"name": "name_contains", "type": "string"},
+                    {"name": "content", "type": "string"},
+                    {"name": "proxied", "type": "bool"},
+                    {"name": "comment", "type": "string"},
+                    {"name": "tag", "type": "string"},
+                    {"name": "sort", "type": "string", "enum": ["type", "name", "content", "ttl", "proxied"]},
+                    {"name": "direction", "type": "string", "default": "asc", "enum": ["asc", "desc"]},
+                    {"name": "limit", "short": "l", "type": "integer"}
                 ]
             },
             {
                 "id": "dns.get",
-                "summary": "Get DNS record",
+                "summary": "Get all DNS records at a name",
                 "args": [],
                 "options": [
                     {"name": "domain", "short": "d", "type": "string", "required": true},
-                    {"name": "name", "short": "n", "type": "string", "required": true}
+                    {"name": "name", "short": "n", "type": "string", "required": true},
+                    {"name": "type", "short": "t", "type": "string"}
                 ]
             },
             {
                 "id": "dns.create",
-                "summary": "Create DNS A record",
+                "summary": "Create DNS record (A, AAAA, CNAME, TXT, MX, SRV, CAA)",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "name", "short": "n", "type": "string", "required": true},
+                    {"name": "type", "short": "t", "type": "string", "default": "A",
+                     "enum": ["A", "AAAA", "CNAME", "TXT", "MX", "SRV", "CAA"]},
+                    {"name": "content", "short": "c", "type": "string", "required": true, "aliases": ["ip"]},
+                    {"name": "priority", "type": "integer"},
+                    {"name": "weight", "type": "integer"},
+                    {"name": "port", "type": "integer"},
+                    {"name": "desc", "type": "string", "aliases": ["comment"]},
+                    {"name": "ttl", "type": "integer"},
+                    {"name": "tag", "type": "string[]"},
+                    {"name": "proxied", "type": "bool"},
+                    {"name": "upsert", "type": "bool", "default": false},
+                    {"name": "wait", "type": "bool", "default": false},
+                    {"name": "wait_timeout", "type": "integer", "default": 120},
+                    {"name": "resolvers", "type": "string[]"}
+                ]
+            },
+            {
+                "id": "dns.update",
+                "summary": "Update DNS record in place",
                 "args": [],
                 "options": [
                     {"name": "domain", "short": "d", "type": "string", "required": true},
                     {"name": "name", "short": "n", "type": "string", "required": true},
-                    {"name": "ip", "short": "i", "type": "string", "required": true},
-                    {"name": "desc", "type": "string", "default": ""},
-                    {"name": "proxied", "type": "bool", "default": false}
+                    {"name": "type", "short": "t", "type": "string"},
+                    {"name": "id", "type": "string"},
+                    {"name": "content", "short": "c", "type": "string", "aliases": ["ip"]},
+                    {"name": "priority", "type": "integer"},
+                    {"name": "weight", "type": "integer"},
+                    {"name": "port", "type": "integer"},
+                    {"name": "proxied", "type": "bool"},
+                    {"name": "ttl", "type": "integer"},
+                    {"name": "comment", "type": "string"},
+                    {"name": "tag", "type": "string[]"},
+                    {"name": "untag", "type": "string[]"},
+                    {"name": "wait", "type": "bool", "default": false},
+                    {"name": "wait_timeout", "type": "integer", "default": 120},
+                    {"name": "resolvers", "type": "string[]"}
                 ]
             },
             {
                 "id": "dns.delete",
-                "summary": "Delete DNS record",
+                "summary": "Delete DNS record (--type, --content or --id when several share the name)",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "name", "short": "n", "type": "string", "required": true},
+                    {"name": "type", "short": "t", "type": "string"},
+                    {"name": "content", "short": "c", "type": "string", "aliases": ["ip"]},
+                    {"name": "id", "type": "string"},
+                    {"name": "wait", "type": "bool", "default": false},
+                    {"name": "wait_timeout", "type": "integer", "default": 120},
+                    {"name": "resolvers", "type": "string[]"}
+                ]
+            },
+            {
+                "id": "dns.verify",
+                "summary": "Poll resolvers until they serve (or stop serving) a record",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "name", "short": "n", "type": "string", "required": true},
+                    {"name": "type", "short": "t", "type": "string", "default": "A",
+                     "enum": ["A", "AAAA", "CNAME", "TXT", "MX", "SRV", "CAA"]},
+                    {"name": "content", "short": "c", "type": "string", "aliases": ["ip"]},
+                    {"name": "absent", "type": "bool", "default": false},
+                    {"name": "timeout", "type": "integer", "default": 120},
+                    {"name": "interval", "type": "integer", "default": 5},
+                    {"name": "resolvers", "type": "string[]"}
+                ]
+            },
+            {
+                "id": "dns.batch",
+                "summary": "Run create/update/delete operations from a JSON or CSV file",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "file", "short": "f", "type": "string", "required": true},
+                    {"name": "atomic", "type": "bool", "default": false},
+                    {"name": "concurrency", "type": "integer", "default": 4}
+                ]
+            },
+            {
+                "id": "dns.ddns",
+                "summary": "Point an A/AAAA record at this host's public IP, updating only on change",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "name", "short": "n", "type": "string", "required": true},
+                    {"name": "family", "type": "string", "default": "ipv4", "enum": ["ipv4", "ipv6", "both"]},
+                    {"name": "echo-url", "type": "string", "default": "https://cloudflare.com/cdn-cgi/trace"},
+                    {"name": "interface", "type": "string"},
+                    {"name": "record-id", "type": "string"},
+                    {"name": "proxied", "type": "bool", "default": false},
+                    {"name": "daemon", "type": "bool", "default": false},
+                    {"name": "interval", "type": "integer", "default": 300}
+                ]
+            },
+            {
+                "id": "dns.plan",
+                "summary": "Diff registry.json against live DNS records",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true}
+                ]
+            },
+            {
+                "id": "dns.apply",
+                "summary": "Push registry.json records to Cloudflare",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "prune", "type": "bool", "default": false}
+                ]
+            },
+            {
+                "id": "dns.export",
+                "summary": "Export zone as BIND, JSON or CSV",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "format", "short": "f", "type": "string", "default": "bind", "enum": ["bind", "json", "csv"]},
+                    {"name": "out", "short": "o", "type": "string"}
+                ]
+            },
+            {
+                "id": "dns.import",
+                "summary": "Import records from a BIND zone file",
+                "args": [],
+                "options": [
+                    {"name": "file", "short": "f", "type": "string", "required": true},
+                    {"name": "domain", "short": "d", "type": "string"},
+                    {"name": "dry_run", "type": "bool", "default": false},
+                    {"name": "prune", "type": "bool", "default": false}
+                ]
+            },
+            {
+                "id": "zone.settings.get",
+                "summary": "Show zone settings (SSL mode, Always Use HTTPS, min TLS, HSTS, ...)",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "setting", "short": "s", "type": "string[]"}
+                ]
+            },
+            {
+                "id": "zone.settings.set",
+                "summary": "Change zone settings, updating only those that differ",
+                "args": [{"name": "values", "type": "string[]", "description": "SETTING=VALUE pairs"}],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "ssl", "type": "string", "enum": ["off", "flexible", "full", "strict"]},
+                    {"name": "always_https", "type": "string", "enum": ["on", "off"]},
+                    {"name": "min_tls", "type": "string", "enum": ["1.0", "1.1", "1.2", "1.3"]},
+                    {"name": "hsts_max_age", "type": "integer"},
+                    {"name": "hsts_subdomains", "type": "bool", "default": false},
+                    {"name": "hsts_preload", "type": "bool", "default": false},
+                    {"name": "dry_run", "type": "bool", "default": false}
+                ]
+            },
+            {
+                "id": "zone.settings.apply",
+                "summary": "Diff zone settings against a JSON profile, report drift and fix it",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "profile", "short": "p", "type": "string", "required": true},
+                    {"name": "dry_run", "type": "bool", "default": false}
+                ]
+            },
+            {
+                "id": "cache.purge",
+                "summary": "Purge Cloudflare's cache by URL, prefix, host, tag or everything",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "urls", "type": "string[]"},
+                    {"name": "prefixes", "type": "string[]"},
+                    {"name": "hosts", "type": "string[]"},
+                    {"name": "tags", "type": "string[]"},
+                    {"name": "everything", "type": "bool", "default": false},
+                    {"name": "yes", "type": "bool", "default": false}
+                ]
+            },
+            {
+                "id": "firewall.list",
+                "summary": "List IP access rules and custom WAF rules with recorded expiries",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true}
+                ]
+            },
+            {
+                "id": "firewall.add",
+                "summary": "Block/challenge an IP, range, ASN or country, or add a custom WAF rule expression",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "ip", "type": "string"},
+                    {"name": "asn", "type": "string"},
+                    {"name": "country", "type": "string"},
+                    {"name": "expression", "type": "string"},
+                    {"name": "action", "type": "string", "default": "block",
+                     "enum": ["block", "challenge", "js_challenge", "managed_challenge", "allow", "log"]},
+                    {"name": "note", "type": "string"},
+                    {"name": "expires", "type": "string"}
+                ]
+            },
+            {
+                "id": "firewall.remove",
+                "summary": "Remove an IP access rule or custom WAF rule by ID",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "id", "type": "string", "required": true}
+                ]
+            },
+            {
+                "id": "firewall.gc",
+                "summary": "Remove rules whose --expires (recorded in registry.json) has passed",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string"},
+                    {"name": "dry_run", "type": "bool", "default": false}
+                ]
+            },
+            {
+                "id": "redirect.list",
+                "summary": "List the zone's single redirect rules",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true}
+                ]
+            },
+            {
+                "id": "redirect.add",
+                "summary": "Redirect a path (optionally on a subdomain) to an absolute URL",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "from", "type": "string", "required": true},
+                    {"name": "to", "type": "string", "required": true},
+                    {"name": "status", "type": "integer", "default": 301, "enum": [301, 302, 307, 308]},
+                    {"name": "preserve_query", "type": "bool", "default": false},
+                    {"name": "note", "type": "string"}
+                ]
+            },
+            {
+                "id": "redirect.remove",
+                "summary": "Remove a redirect by rule ID or --from path",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "id", "type": "string"},
+                    {"name": "from", "type": "string"}
+                ]
+            },
+            {
+                "id": "redirect.import",
+                "summary": "Sync redirects from a CSV file (from,to[,status][,preserve_query])",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "file", "short": "f", "type": "string", "required": true},
+                    {"name": "prune", "type": "bool", "default": false},
+                    {"name": "dry_run", "type": "bool", "default": false}
+                ]
+            },
+            {
+                "id": "tunnel.list",
+                "summary": "List Cloudflare Tunnels and the hostnames routed to them",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true}
+                ]
+            },
+            {
+                "id": "tunnel.create",
+                "summary": "Create a remotely managed tunnel and return its connector token",
                 "args": [],
                 "options": [
                     {"name": "domain", "short": "d", "type": "string", "required": true},
                     {"name": "name", "short": "n", "type": "string", "required": true}
                 ]
             },
+            {
+                "id": "tunnel.route",
+                "summary": "Route a hostname through a tunnel (ingress rule + proxied CNAME, recorded in registry tunnels)",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "tunnel", "short": "t", "type": "string", "required": true},
+                    {"name": "name", "short": "n", "type": "string", "required": true},
+                    {"name": "service", "short": "s", "type": "string", "required": true}
+                ]
+            },
+            {
+                "id": "tunnel.ingress",
+                "summary": "Show a tunnel's ingress rules or remove a route",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true},
+                    {"name": "tunnel", "short": "t", "type": "string", "required": true},
+                    {"name": "remove", "type": "string"}
+                ]
+            },
+            {
+                "id": "origin-cert.issue",
+                "summary": "Issue an Origin CA certificate (key and CSR generated locally) and install the files on a Caddy server over SSH; the Caddyfile is not edited",
+                "args": [],
+                "options": [
+                    {"name": "hostnames", "type": "string[]", "required": true},
+                    {"name": "server", "short": "s", "type": "string", "required": true},
+                    {"name": "validity", "type": "integer", "default": 5475, "enum": [7, 30, 90, 365, 730, 1095, 5475]},
+                    {"name": "key_type", "type": "string", "default": "rsa", "enum": ["rsa", "ecc"]},
+                    {"name": "dir", "type": "string", "default": "/etc/caddy/certs"}
+                ]
+            },
             {
                 "id": "caddy.add",
                 "summary": "Add Caddy reverse proxy",
@@ -278,7 +635,9 @@ pub fn print_manifest() {
                 "options": [
                     {"name": "server", "short": "s", "type": "string", "required": true},
                     {"name": "domain", "short": "d", "type": "string", "required": true},
-                    {"name": "upstream", "short": "u", "type": "string", "required": true}
+                    {"name": "upstream", "short": "u", "type": "string", "required": true},
+                    {"name": "tls_cert", "type": "string"},
+                    {"name": "tls_key", "type": "string"}
                 ]
             },
             {
@@ -289,7 +648,9 @@ pub fn print_manifest() {
                     {"name": "server", "short": "s", "type": "string", "required": true},
                     {"name": "domain", "short": "d", "type": "string", "required": true},
                     {"name": "upstreams", "short": "u", "type": "string", "required": true},
-                    {"name": "health_uri", "type": "string", "default": "/health"}
+                    {"name": "health_uri", "type": "string", "default": "/health"},
+                    {"name": "tls_cert", "type": "string"},
+                    {"name": "tls_key", "type": "string"}
                 ]
             },
             {
@@ -329,20 +690,28 @@ pub fn print_manifest() {
                 "summary": "Validate registry.json",
                 "args": [],
                 "options": []
+            },
+            {
+                "id": "registry.import",
+                "summary": "Import live Cloudflare records into registry.json",
+                "args": [],
+                "options": [
+                    {"name": "domain", "short": "d", "type": "string", "required": true}
+                ]
             }
         ],
         "permissions": {
             "network": true,
-            "network_domains": ["api.cloudflare.com"],
+            "network_domains": ["api.cloudflare.com", "cloudflare.com", "1.1.1.1", "8.8.8.8"],
             "filesystem": {
                 "read": ["$CF_PROJECT_DIR"],
-                "write": ["$CF_PROJECT_DIR/registry.json"]
+                "write": ["$CF_PROJECT_DIR/registry.json", "$CF_PROJECT_DIR/.cf/uploads"]
             },
             "env_vars": [
-                "CLOUDFLARE_TAP365_API_TOKEN",
-                "CLOUDFLARE_TAP365_ZONE_ID",
-                "CLOUDFLARE_AIHANG365_API_TOKEN",
-                "CLOUDFLARE_AIHANG365_ZONE_ID"
+                "CLOUDFLARE_API_TOKEN",
+                "CF_API_BASE",
+                "CLOUDFLARE_<DOMAIN_SLUG>_API_TOKEN",
+                "CLOUDFLARE_<DOMAIN_SLUG>_ZONE_ID"
             ]
         },
         "limits": {
@@ -0,0 +1,103 @@
+//! End-to-end tests for `cf cache purge` and `cf r2 upload --purge`
+
+mod common;
+
+use common::{Project, DOMAIN, PUBLIC_URL};
+use serde_json::json;
+
+fn purge_bodies(p: &Project) -> Vec<serde_json::Value> {
+    p.mock.requests_with("POST").into_iter()
+        .filter(|r| r.path.ends_with("/purge_cache"))
+        .filter_map(|r| r.body)
+        .collect()
+}
+
+#[test]
+fn purge_sends_one_request_per_kind() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&[
+        "cache", "purge", "--domain", DOMAIN,
+        "--urls", "https://example.test/app.js,https://www.example.test/app.css",
+        "--prefixes", "https://example.test/assets/",
+        "--tags", "release-42",
+    ]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["requests"], 3);
+    assert_eq!(purge_bodies(&p), [
+        json!({"files": ["https://example.test/app.js", "https://www.example.test/app.css"]}),
+        json!({"prefixes": ["example.test/assets/"]}),
+        json!({"tags": ["release-42"]}),
+    ]);
+}
+
+#[test]
+fn purge_chunks_long_url_lists() {
+    let p = Project::with_zone();
+    let urls: Vec<String> = (0..45).map(|i| format!("https://example.test/{}.png", i)).collect();
+
+    let run = p.cf(&["cache", "purge", "--domain", DOMAIN, "--urls", &urls.join(",")]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let bodies = purge_bodies(&p);
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[0]["files"].as_array().unwrap().len(), 30);
+    assert_eq!(bodies[1]["files"].as_array().unwrap().len(), 15);
+}
+
+#[test]
+fn purge_rejects_urls_outside_the_zone() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&["cache", "purge", "--domain", DOMAIN, "--urls", "https://other.example/app.js"]);
+
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "INVALID_PURGE_TARGET");
+    assert!(purge_bodies(&p).is_empty());
+}
+
+#[test]
+fn purge_everything_needs_confirmation() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&["cache", "purge", "--domain", DOMAIN, "--everything"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "CONFIRMATION_REQUIRED");
+    assert!(purge_bodies(&p).is_empty());
+
+    let run = p.cf(&["cache", "purge", "--domain", DOMAIN, "--everything", "--yes"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(purge_bodies(&p), [json!({"purge_everything": true})]);
+}
+
+#[test]
+fn r2_upload_purges_the_public_url() {
+    let p = Project::with_zone();
+    std::fs::write(p.file("app.js"), "console.log(1)").unwrap();
+
+    let run = p.cf(&["r2", "upload", "--file", "app.js", "--key", "assets/app.js", "--purge"]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let url = format!("{}/assets/app.js", PUBLIC_URL);
+    assert_eq!(run.result()["public_url"], url.as_str());
+    assert_eq!(run.result()["purge"]["domain"], DOMAIN);
+    assert_eq!(p.mock.object("assets/app.js").as_deref(), Some(&b"console.log(1)"[..]));
+    assert_eq!(purge_bodies(&p), [json!({"files": [url]})]);
+}
+
+#[test]
+fn r2_upload_skips_purge_for_r2_dev_urls() {
+    let p = Project::with_zone();
+    std::fs::write(p.file("app.js"), "console.log(1)").unwrap();
+
+    let run = p.cf_with_env(
+        &["r2", "upload", "--file", "app.js", "--key", "assets/app.js", "--purge"],
+        &[("CLOUDFLARE_R2_PUBLIC_URL", "https://pub-0123456789abcdef.r2.dev")],
+    );
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(run.result().get("purge").is_none());
+    assert!(p.mock.requests().iter().all(|r| r.path != "/zones"));
+    assert!(purge_bodies(&p).is_empty());
+}
@@ -0,0 +1,1169 @@
+//! Test harness: an in-process fake of the Cloudflare API and a runner for the `cf` binary
+//!
+//! The fake serves `/zones` and its `dns_records`, `settings` and `purge_cache`
+//! endpoints over plain HTTP on 127.0.0.1, keeps state in memory and answers
+//! with Cloudflare's response envelope and error codes. The same records are
+//! served over UDP DNS for propagation checks, and a minimal S3 endpoint stands
+//! in for R2. `Project` points `cf` at it through a temporary project directory
+//! whose `.env` sets `CF_API_BASE`.
+
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub const TOKEN: &str = "test-token";
+pub const DOMAIN: &str = "example.test";
+pub const ZONE_ID: &str = "023e105f4ecef8ad9ca31a8372d0c353";
+pub const ACCOUNT_ID: &str = "01a7362d577a6c3019a474fd6f485823";
+
+/// R2 bucket served by the mock's S3 endpoint, and the public URL in front of it
+pub const BUCKET: &str = "test-bucket";
+pub const PUBLIC_URL: &str = "https://cdn.example.test";
+
+/// Certificate body returned by the mock Origin CA
+pub const ORIGIN_CERT: &str = "-----BEGIN CERTIFICATE-----\nMIIEmockoriginca\n-----END CERTIFICATE-----\n";
+
+/// Timestamps the mock stamps on created and patched records
+pub const CREATED_ON: &str = "2024-01-01T00:00:00.000000Z";
+pub const MODIFIED_ON: &str = "2024-06-01T12:00:00.000000Z";
+
+// ============ Mock Cloudflare API ============
+
+/// One request as seen by the mock
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub body: Option<Value>,
+    /// Raw request body (S3 object uploads are not JSON)
+    pub bytes: Vec<u8>,
+    /// Headers with lowercased names
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.query.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    zones: Vec<(String, String)>,
+    records: Vec<Value>,
+    next_id: u64,
+    requests: Vec<Request>,
+    /// Canned failures returned (in order) before normal handling resumes
+    failures: VecDeque<(u16, Option<u64>)>,
+    /// DNS queries still to be answered as if nothing had propagated yet
+    dns_lag: usize,
+    /// Address reported by the `/cdn-cgi/trace` echo endpoint
+    public_ip: String,
+    /// Zone settings as `{id, value, editable}`
+    settings: Vec<Value>,
+    /// R2 objects by key
+    objects: BTreeMap<String, Vec<u8>>,
+    /// ETags of objects assembled from parts (others use the MD5 of their body)
+    etags: BTreeMap<String, String>,
+    /// Open multipart uploads by upload ID: key and parts by number
+    uploads: BTreeMap<String, (String, BTreeMap<u32, Vec<u8>>)>,
+    /// Part numbers whose next upload attempt fails with a 500
+    failing_parts: Vec<u32>,
+    /// Object keys whose DELETE is refused with a 403
+    protected_objects: Vec<String>,
+    /// Cloudflare Tunnels as `{id, name, status, ...}`
+    tunnels: Vec<Value>,
+    /// Remote tunnel configurations by tunnel ID
+    tunnel_configs: BTreeMap<String, Value>,
+    /// IP access rules as `{id, mode, configuration, notes}`
+    access_rules: Vec<Value>,
+    /// Zone entrypoint rulesets by phase, once created
+    rulesets: BTreeMap<String, Value>,
+}
+
+pub struct MockCloudflare {
+    addr: String,
+    dns_addr: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockCloudflare {
+    /// Start a mock serving one zone, `example.test`
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        let addr = listener.local_addr().unwrap().to_string();
+        let state = Arc::new(Mutex::new(State {
+            zones: vec![(ZONE_ID.to_string(), DOMAIN.to_string())],
+            next_id: 1,
+            public_ip: "203.0.113.10".to_string(),
+            settings: default_settings(),
+            ..Default::default()
+        }));
+
+        let shared = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = Arc::clone(&shared);
+                thread::spawn(move || handle_connection(stream, &state));
+            }
+        });
+
+        let (socket, tcp) = bind_dns_pair();
+        let dns_addr = socket.local_addr().unwrap().to_string();
+        let shared = Arc::clone(&state);
+        thread::spawn(move || serve_dns(socket, &shared));
+        let shared = Arc::clone(&state);
+        thread::spawn(move || serve_dns_tcp(tcp, &shared));
+
+        Self { addr, dns_addr, state }
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}/client/v4", self.addr)
+    }
+
+    /// S3 endpoint for the R2 bucket
+    pub fn s3_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Stored R2 object
+    pub fn object(&self, key: &str) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().objects.get(key).cloned()
+    }
+
+    /// Keys of all stored R2 objects, sorted
+    pub fn object_keys(&self) -> Vec<String> {
+        self.state.lock().unwrap().objects.keys().cloned().collect()
+    }
+
+    /// Store an R2 object directly
+    pub fn put_object(&self, key: &str, body: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        state.etags.remove(key);
+        state.objects.insert(key.to_string(), body.to_vec());
+    }
+
+    /// Multipart uploads started but neither completed nor aborted
+    pub fn open_uploads(&self) -> usize {
+        self.state.lock().unwrap().uploads.len()
+    }
+
+    /// Fail the next upload attempt of this part number with a 500
+    pub fn fail_part(&self, number: u32) {
+        self.state.lock().unwrap().failing_parts.push(number);
+    }
+
+    /// Refuse every DELETE of this object key with a 403
+    pub fn protect_object(&self, key: &str) {
+        self.state.lock().unwrap().protected_objects.push(key.to_string());
+    }
+
+    /// Echo endpoint in Cloudflare trace format, for `--echo-url`
+    pub fn echo_url(&self) -> String {
+        format!("http://{}/cdn-cgi/trace", self.addr)
+    }
+
+    /// Change the address the echo endpoint reports
+    pub fn set_public_ip(&self, ip: &str) {
+        self.state.lock().unwrap().public_ip = ip.to_string();
+    }
+
+    /// Address of the DNS responder, for `--resolvers`
+    pub fn dns_addr(&self) -> &str {
+        &self.dns_addr
+    }
+
+    /// Answer the next `queries` DNS queries with NXDOMAIN
+    pub fn lag_dns(&self, queries: usize) {
+        self.state.lock().unwrap().dns_lag = queries;
+    }
+
+    /// Add a record directly, bypassing validation; `name` is relative to the zone
+    pub fn seed(&self, record_type: &str, name: &str, content: &str) -> String {
+        self.seed_with(json!({"type": record_type, "name": name, "content": content}))
+    }
+
+    /// Add a record from a full API body (relative `name`)
+    pub fn seed_with(&self, body: Value) -> String {
+        let mut state = self.state.lock().unwrap();
+        let record = new_record(&mut state, DOMAIN, &body);
+        let id = record["id"].as_str().unwrap().to_string();
+        state.records.push(record);
+        id
+    }
+
+    pub fn records(&self) -> Vec<Value> {
+        self.state.lock().unwrap().records.clone()
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Requests with the given method, e.g. "POST"
+    pub fn requests_with(&self, method: &str) -> Vec<Request> {
+        self.requests().into_iter().filter(|r| r.method == method).collect()
+    }
+
+    /// IP access rules on the zone
+    pub fn access_rules(&self) -> Vec<Value> {
+        self.state.lock().unwrap().access_rules.clone()
+    }
+
+    /// Rules of the zone's entrypoint ruleset for a phase
+    pub fn phase_rules(&self, phase: &str) -> Vec<Value> {
+        let state = self.state.lock().unwrap();
+        state.rulesets.get(phase)
+            .and_then(|r| r["rules"].as_array().cloned())
+            .unwrap_or_default()
+    }
+
+    /// Rules of the zone's custom WAF ruleset
+    pub fn custom_rules(&self) -> Vec<Value> {
+        self.phase_rules("http_request_firewall_custom")
+    }
+
+    /// The zone's single redirect rules
+    pub fn redirect_rules(&self) -> Vec<Value> {
+        self.phase_rules("http_request_dynamic_redirect")
+    }
+
+    /// Ingress rules of a tunnel's remote configuration
+    pub fn ingress(&self, tunnel_id: &str) -> Vec<Value> {
+        let state = self.state.lock().unwrap();
+        state.tunnel_configs.get(tunnel_id)
+            .and_then(|c| c["ingress"].as_array().cloned())
+            .unwrap_or_default()
+    }
+
+    /// Current value of a zone setting
+    pub fn setting(&self, id: &str) -> Value {
+        let state = self.state.lock().unwrap();
+        state.settings.iter().find(|s| s["id"] == id).map(|s| s["value"].clone()).unwrap_or(Value::Null)
+    }
+
+    /// Answer the next request with this HTTP status (and optional Retry-After)
+    pub fn fail_next(&self, status: u16, retry_after: Option<u64>) {
+        self.state.lock().unwrap().failures.push_back((status, retry_after));
+    }
+}
+
+fn handle_connection(stream: TcpStream, state: &Mutex<State>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+        return;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let target = parts.next().unwrap_or("").to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    let header = |name: &str| headers.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone());
+    let content_length = header("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+    let authorization = header("authorization").unwrap_or_default();
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok();
+
+    let (path, query) = match target.split_once('?') {
+        Some((p, q)) => (p.to_string(), parse_query(q)),
+        None => (target, Vec::new()),
+    };
+    let request = Request {
+        method,
+        path: path.trim_start_matches("/client/v4").to_string(),
+        query,
+        body: serde_json::from_slice(&body).ok(),
+        bytes: body,
+        headers,
+    };
+
+    let head = request.method == "HEAD";
+    let (status, retry_after, content_type, extra, body) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request.clone());
+
+        if let Some((status, retry_after)) = state.failures.pop_front() {
+            let body = failure(10000 + status as i32, "Injected failure").to_string();
+            (status, retry_after, "application/json", Vec::new(), body.into_bytes())
+        } else if request.path == "/cdn-cgi/trace" {
+            // Unauthenticated, like the real trace endpoint
+            let body = format!("fl=1\nh=mock\nip={}\nts=0\n", state.public_ip);
+            (200, None, "text/plain", Vec::new(), body.into_bytes())
+        } else if let Some(key) = request.path.strip_prefix(&format!("/{}/", BUCKET)) {
+            // S3 requests carry SigV4 signatures, which the mock does not check
+            let (status, headers, body) = s3_route(&mut state, &request, key);
+            (status, None, "application/xml", headers, body)
+        } else if authorization != format!("Bearer {}", TOKEN) {
+            let body = failure(10000, "Authentication error").to_string();
+            (403, None, "application/json", Vec::new(), body.into_bytes())
+        } else {
+            let (status, payload) = route(&mut state, &request);
+            (status, None, "application/json", Vec::new(), payload.to_string().into_bytes())
+        }
+    };
+
+    let mut response = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    if let Some(s) = retry_after {
+        response.push_str(&format!("Retry-After: {}\r\n", s));
+    }
+    for (name, value) in &extra {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    let mut response = response.into_bytes();
+    // HEAD answers carry the object's length but no body
+    if !head {
+        response.extend_from_slice(&body);
+    }
+    stream.write_all(&response).ok();
+}
+
+fn parse_query(q: &str) -> Vec<(String, String)> {
+    q.split('&')
+        .filter(|p| !p.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    Err(_) => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn success(result: Value) -> Value {
+    json!({"success": true, "errors": [], "messages": [], "result": result})
+}
+
+fn failure(code: i32, message: &str) -> Value {
+    json!({"success": false, "errors": [{"code": code, "message": message}], "messages": [], "result": null})
+}
+
+fn route(state: &mut State, req: &Request) -> (u16, Value) {
+    let segments: Vec<&str> = req.path.trim_matches('/').split('/').collect();
+
+    match (req.method.as_str(), segments.as_slice()) {
+        ("GET", ["zones"]) => {
+            let zones: Vec<Value> = state.zones.iter()
+                .filter(|(_, name)| req.param("name").is_none_or(|n| n == name))
+                .map(|(id, name)| json!({"id": id, "name": name, "status": "active"}))
+                .collect();
+            (200, success(json!(zones)))
+        }
+        ("GET", ["zones", zone_id]) => match state.zones.iter().find(|(id, _)| id == zone_id) {
+            Some((id, name)) => (200, success(json!({"id": id, "name": name, "status": "active", "account": {"id": ACCOUNT_ID}}))),
+            None => (404, failure(7003, "Could not route to /zones, perhaps your object identifier is invalid?")),
+        },
+        (_, ["zones", zone_id, "dns_records" | "settings" | "purge_cache" | "firewall" | "rulesets", ..]) if !state.zones.iter().any(|(id, _)| id == zone_id) => {
+            (404, failure(7003, "Could not route to /zones, perhaps your object identifier is invalid?"))
+        }
+        ("GET", ["zones", _, "dns_records"]) => list_records(state, req),
+        ("GET", ["zones", _, "dns_records", record_id]) => match state.records.iter().find(|r| r["id"] == *record_id) {
+            Some(record) => (200, success(record.clone())),
+            None => (404, failure(81044, "Record does not exist.")),
+        },
+        ("POST", ["zones", zone_id, "dns_records"]) => {
+            let zone = zone_name(state, zone_id);
+            let body = req.body.clone().unwrap_or_default();
+            let record = new_record(state, &zone, &body);
+
+            let duplicate = state.records.iter().any(|r| {
+                r["type"] == record["type"]
+                    && r["name"] == record["name"]
+                    && r["content"] == record["content"]
+            });
+            if duplicate {
+                return (400, failure(81057, "Record already exists."));
+            }
+            // A CNAME cannot share its name with any other record
+            let cname_conflict = state.records.iter().any(|r| {
+                r["name"] == record["name"] && (r["type"] == "CNAME" || record["type"] == "CNAME")
+            });
+            if cname_conflict {
+                return (400, failure(81053, "An A, AAAA, or CNAME record with that host already exists."));
+            }
+
+            state.records.push(record.clone());
+            (200, success(record))
+        }
+        ("PATCH", ["zones", zone_id, "dns_records", record_id]) => {
+            let zone = zone_name(state, zone_id);
+            let Some(record) = state.records.iter_mut().find(|r| r["id"] == *record_id) else {
+                return (404, failure(81044, "Record does not exist."));
+            };
+            if let Some(Value::Object(fields)) = &req.body {
+                for (k, v) in fields {
+                    record[k.as_str()] = match k.as_str() {
+                        "name" => json!(absolute_name(&zone, v.as_str().unwrap_or("@"))),
+                        _ => v.clone(),
+                    };
+                }
+            }
+            record["modified_on"] = json!(MODIFIED_ON);
+            sync_content(record);
+            (200, success(record.clone()))
+        }
+        ("DELETE", ["zones", _, "dns_records", record_id]) => {
+            let before = state.records.len();
+            state.records.retain(|r| r["id"] != *record_id);
+            if state.records.len() == before {
+                return (404, failure(81044, "Record does not exist."));
+            }
+            (200, success(json!({"id": record_id})))
+        }
+        ("POST", ["zones", _, "purge_cache"]) => {
+            let id = format!("{:032x}", state.next_id);
+            state.next_id += 1;
+            (200, success(json!({"id": id})))
+        }
+        ("GET", ["zones", _, "settings"]) => (200, success(json!(state.settings))),
+        ("PATCH", ["zones", _, "settings", setting_id]) => {
+            let Some(setting) = state.settings.iter_mut().find(|s| s["id"] == *setting_id) else {
+                return (400, failure(1003, "Invalid or missing zone setting"));
+            };
+            if setting["editable"] == false {
+                return (400, failure(1007, "This setting is not editable on your plan"));
+            }
+            setting["value"] = req.body.as_ref().map(|b| b["value"].clone()).unwrap_or_default();
+            setting["modified_on"] = json!(MODIFIED_ON);
+            (200, success(setting.clone()))
+        }
+        ("GET", ["zones", _, "firewall", "access_rules", "rules"]) => (200, success(json!(state.access_rules))),
+        ("POST", ["zones", _, "firewall", "access_rules", "rules"]) => {
+            let body = req.body.clone().unwrap_or_default();
+            let rule = json!({
+                "id": format!("{:032x}", state.next_id),
+                "mode": body["mode"],
+                "configuration": body["configuration"],
+                "notes": body["notes"],
+                "created_on": CREATED_ON
+            });
+            state.next_id += 1;
+            state.access_rules.push(rule.clone());
+            (200, success(rule))
+        }
+        ("DELETE", ["zones", _, "firewall", "access_rules", "rules", rule_id]) => {
+            let before = state.access_rules.len();
+            state.access_rules.retain(|r| r["id"] != *rule_id);
+            if state.access_rules.len() == before {
+                return (404, failure(10001, "Access rule not found"));
+            }
+            (200, success(json!({"id": rule_id})))
+        }
+        ("GET", ["zones", _, "rulesets", "phases", phase, "entrypoint"]) => match state.rulesets.get(*phase) {
+            Some(ruleset) => (200, success(ruleset.clone())),
+            None => (404, failure(10003, &format!("Could not find entrypoint ruleset in the {} phase", phase))),
+        },
+        ("PUT", ["zones", _, "rulesets", "phases", phase, "entrypoint"]) => {
+            let id = match state.rulesets.get(*phase) {
+                Some(existing) => existing["id"].clone(),
+                None => {
+                    state.next_id += 1;
+                    json!(format!("{:032x}", state.next_id - 1))
+                }
+            };
+            // Rules sent with an ID keep it; new ones get one
+            let mut rules = req.body.as_ref().and_then(|b| b["rules"].as_array().cloned()).unwrap_or_default();
+            for rule in rules.iter_mut().filter(|r| r.get("id").is_none()) {
+                rule["id"] = json!(format!("{:032x}", state.next_id));
+                state.next_id += 1;
+            }
+            let ruleset = json!({"id": id, "phase": phase, "rules": rules});
+            state.rulesets.insert(phase.to_string(), ruleset.clone());
+            (200, success(ruleset))
+        }
+        ("POST", ["zones", _, "rulesets", ruleset_id, "rules"]) => {
+            let id = format!("{:032x}", state.next_id);
+            state.next_id += 1;
+            let Some(ruleset) = state.rulesets.values_mut().find(|r| r["id"] == *ruleset_id) else {
+                return (404, failure(10003, "Ruleset not found"));
+            };
+            let mut rule = req.body.clone().unwrap_or_default();
+            rule["id"] = json!(id);
+            ruleset["rules"].as_array_mut().unwrap().push(rule);
+            (200, success(ruleset.clone()))
+        }
+        ("DELETE", ["zones", _, "rulesets", ruleset_id, "rules", rule_id]) => {
+            let Some(ruleset) = state.rulesets.values_mut().find(|r| r["id"] == *ruleset_id) else {
+                return (404, failure(10003, "Ruleset not found"));
+            };
+            let rules = ruleset["rules"].as_array_mut().unwrap();
+            let before = rules.len();
+            rules.retain(|r| r["id"] != *rule_id);
+            if rules.len() == before {
+                return (404, failure(10004, "Rule not found"));
+            }
+            (200, success(ruleset.clone()))
+        }
+        ("POST", ["certificates"]) => {
+            let body = req.body.clone().unwrap_or_default();
+            if !body["csr"].as_str().unwrap_or("").starts_with("-----BEGIN CERTIFICATE REQUEST-----") {
+                return (400, failure(1010, "Invalid CSR"));
+            }
+            let id = format!("{:040x}", state.next_id);
+            state.next_id += 1;
+            (200, success(json!({
+                "id": id,
+                "certificate": ORIGIN_CERT,
+                "hostnames": body["hostnames"],
+                "request_type": body["request_type"],
+                "requested_validity": body["requested_validity"],
+                "expires_on": "2039-01-01 00:00:00 +0000 UTC",
+                "csr": body["csr"]
+            })))
+        }
+        (_, ["accounts", account_id, "cfd_tunnel", ..]) if *account_id != ACCOUNT_ID => {
+            (403, failure(9109, "Unauthorized to access requested resource"))
+        }
+        ("GET", ["accounts", _, "cfd_tunnel"]) => (200, success(json!(state.tunnels))),
+        ("POST", ["accounts", _, "cfd_tunnel"]) => {
+            let name = req.body.as_ref().and_then(|b| b["name"].as_str()).unwrap_or("").to_string();
+            if state.tunnels.iter().any(|t| t["name"] == name) {
+                return (409, failure(1013, "You already have a tunnel with this name"));
+            }
+            let id = format!("c1744f8b-faa1-48a4-9e5c-{:012x}", state.next_id);
+            state.next_id += 1;
+            let tunnel = json!({"id": id, "name": name, "status": "inactive", "created_at": CREATED_ON, "connections": []});
+            state.tunnels.push(tunnel.clone());
+            (200, success(tunnel))
+        }
+        ("GET", ["accounts", _, "cfd_tunnel", tunnel_id, ..]) if !state.tunnels.iter().any(|t| t["id"] == *tunnel_id) => {
+            (404, failure(1003, "Tunnel not found"))
+        }
+        ("GET", ["accounts", _, "cfd_tunnel", tunnel_id, "token"]) => (200, success(json!(format!("token-{}", tunnel_id)))),
+        ("GET", ["accounts", _, "cfd_tunnel", tunnel_id, "configurations"]) => {
+            let config = state.tunnel_configs.get(*tunnel_id).cloned().unwrap_or(Value::Null);
+            (200, success(json!({"tunnel_id": tunnel_id, "version": 0, "config": config})))
+        }
+        ("PUT", ["accounts", _, "cfd_tunnel", tunnel_id, "configurations"]) => {
+            let config = req.body.as_ref().map(|b| b["config"].clone()).unwrap_or_default();
+            state.tunnel_configs.insert(tunnel_id.to_string(), config.clone());
+            (200, success(json!({"tunnel_id": tunnel_id, "version": 1, "config": config})))
+        }
+        _ => (404, failure(7000, "No route for that URI")),
+    }
+}
+
+/// S3 response: status, extra headers and body
+type S3Response = (u16, Vec<(String, String)>, Vec<u8>);
+
+fn s3_error(status: u16, code: &str) -> S3Response {
+    (status, Vec::new(), format!("<Error><Code>{}</Code></Error>", code).into_bytes())
+}
+
+/// Quoted ETag of a single-part object: the MD5 of its body
+fn etag(body: &[u8]) -> String {
+    format!("\"{:x}\"", md5::compute(body))
+}
+
+/// ETag of an object assembled from parts: MD5 of the parts' MD5s, then the part count
+fn multipart_etag(parts: &[&Vec<u8>]) -> String {
+    let digests: Vec<u8> = parts.iter().flat_map(|p| md5::compute(p).0).collect();
+    format!("\"{:x}-{}\"", md5::compute(digests), parts.len())
+}
+
+/// Text of every `<tag>` element in an XML document
+fn xml_values<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    xml.split(open.as_str()).skip(1).filter_map(|rest| rest.split_once(close.as_str()).map(|(v, _)| v)).collect()
+}
+
+/// Multipart upload API: create (`?uploads`), part PUTs, complete and abort (`?uploadId=`)
+fn s3_multipart(state: &mut State, req: &Request, key: String) -> S3Response {
+    if req.method == "POST" && req.param("uploads").is_some() {
+        state.next_id += 1;
+        let upload_id = format!("upload-{}", state.next_id);
+        state.uploads.insert(upload_id.clone(), (key.clone(), BTreeMap::new()));
+        let body = format!(
+            "<InitiateMultipartUploadResult><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId></InitiateMultipartUploadResult>",
+            BUCKET, key, upload_id
+        );
+        return (200, Vec::new(), body.into_bytes());
+    }
+
+    let upload_id = req.param("uploadId").unwrap_or_default().to_string();
+    let Some((upload_key, parts)) = state.uploads.get_mut(&upload_id) else {
+        return s3_error(404, "NoSuchUpload");
+    };
+    if *upload_key != key {
+        return s3_error(400, "InvalidRequest");
+    }
+
+    match req.method.as_str() {
+        "PUT" => {
+            let number: u32 = req.param("partNumber").and_then(|n| n.parse().ok()).unwrap_or(0);
+            if let Some(i) = state.failing_parts.iter().position(|n| *n == number) {
+                state.failing_parts.remove(i);
+                return s3_error(500, "InternalError");
+            }
+            parts.insert(number, req.bytes.clone());
+            (200, vec![("ETag".to_string(), etag(&req.bytes))], Vec::new())
+        }
+        "POST" => {
+            let xml = String::from_utf8_lossy(&req.bytes).into_owned();
+            let listed: Vec<(u32, String)> = xml_values(&xml, "PartNumber").into_iter()
+                .zip(xml_values(&xml, "ETag"))
+                .map(|(n, e)| (n.parse().unwrap_or(0), e.to_string()))
+                .collect();
+            let mut chosen = Vec::new();
+            for (number, tag) in &listed {
+                match parts.get(number) {
+                    Some(part) if etag(part) == *tag => chosen.push(part),
+                    _ => return s3_error(400, "InvalidPart"),
+                }
+            }
+            if listed.is_empty() || listed.windows(2).any(|w| w[0].0 >= w[1].0) {
+                return s3_error(400, "InvalidPartOrder");
+            }
+            let tag = multipart_etag(&chosen);
+            let object: Vec<u8> = chosen.into_iter().flatten().copied().collect();
+            state.uploads.remove(&upload_id);
+            state.objects.insert(key.clone(), object);
+            state.etags.insert(key, tag.clone());
+            let body = format!("<CompleteMultipartUploadResult><ETag>{}</ETag></CompleteMultipartUploadResult>", tag);
+            (200, Vec::new(), body.into_bytes())
+        }
+        "DELETE" => {
+            state.uploads.remove(&upload_id);
+            (204, Vec::new(), Vec::new())
+        }
+        _ => s3_error(405, "MethodNotAllowed"),
+    }
+}
+
+/// Bucket-level listing of open multipart uploads (`GET /?uploads&prefix=`)
+fn s3_list_uploads(state: &State, req: &Request) -> S3Response {
+    let prefix = req.param("prefix").unwrap_or_default();
+    let uploads: String = state.uploads.iter()
+        .filter(|(_, (key, _))| key.starts_with(prefix))
+        .map(|(id, (key, _))| format!(
+            "<Upload><Key>{}</Key><UploadId>{}</UploadId><Initiated>{}</Initiated><StorageClass>STANDARD</StorageClass></Upload>",
+            key, id, CREATED_ON
+        ))
+        .collect();
+    let body = format!(
+        "<ListMultipartUploadsResult><Bucket>{}</Bucket><Prefix>{}</Prefix><IsTruncated>false</IsTruncated>{}</ListMultipartUploadsResult>",
+        BUCKET, prefix, uploads
+    );
+    (200, Vec::new(), body.into_bytes())
+}
+
+/// ListObjectsV2 (`GET /?list-type=2&prefix=`), all keys in one page
+fn s3_list_objects(state: &State, req: &Request) -> S3Response {
+    let prefix = req.param("prefix").unwrap_or_default();
+    let contents: String = state.objects.iter()
+        .filter(|(key, _)| key.starts_with(prefix))
+        .map(|(key, body)| format!(
+            "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+            key,
+            MODIFIED_ON,
+            state.etags.get(key).cloned().unwrap_or_else(|| etag(body)).replace('"', "&quot;"),
+            body.len()
+        ))
+        .collect();
+    let body = format!(
+        "<ListBucketResult><Name>{}</Name><Prefix>{}</Prefix><IsTruncated>false</IsTruncated>{}</ListBucketResult>",
+        BUCKET, prefix, contents
+    );
+    (200, Vec::new(), body.into_bytes())
+}
+
+/// Minimal S3 object API for the R2 bucket
+fn s3_route(state: &mut State, req: &Request, key: &str) -> S3Response {
+    let key = percent_decode(key);
+    if key.is_empty() && req.method == "GET" {
+        return match req.param("uploads") {
+            Some(_) => s3_list_uploads(state, req),
+            None => s3_list_objects(state, req),
+        };
+    }
+    if req.param("uploads").is_some() || req.param("uploadId").is_some() {
+        return s3_multipart(state, req, key);
+    }
+    match req.method.as_str() {
+        "PUT" => {
+            let tag = etag(&req.bytes);
+            state.etags.remove(&key);
+            state.objects.insert(key, req.bytes.clone());
+            (200, vec![("ETag".to_string(), tag)], Vec::new())
+        }
+        "GET" | "HEAD" => {
+            let Some(object) = state.objects.get(&key) else {
+                return s3_error(404, "NoSuchKey");
+            };
+            let tag = state.etags.get(&key).cloned().unwrap_or_else(|| etag(object));
+            if req.header("if-match").is_some_and(|m| m != tag) {
+                return s3_error(412, "PreconditionFailed");
+            }
+            let mut headers = vec![("ETag".to_string(), tag)];
+            let range = req.header("range").and_then(|r| r.strip_prefix("bytes=")).and_then(|r| r.split_once('-'));
+            let (status, body) = match range {
+                Some((start, end)) => {
+                    let len = object.len();
+                    let start: usize = start.parse().unwrap_or(0);
+                    let end = end.parse::<usize>().map_or(len, |e| (e + 1).min(len));
+                    if start >= end {
+                        return s3_error(416, "InvalidRange");
+                    }
+                    headers.push(("Content-Range".to_string(), format!("bytes {}-{}/{}", start, end - 1, len)));
+                    (206, object[start..end].to_vec())
+                }
+                None => (200, object.clone()),
+            };
+            (status, headers, body)
+        }
+        "DELETE" => {
+            if state.protected_objects.contains(&key) {
+                return s3_error(403, "AccessDenied");
+            }
+            state.etags.remove(&key);
+            state.objects.remove(&key);
+            (204, Vec::new(), Vec::new())
+        }
+        _ => s3_error(405, "MethodNotAllowed"),
+    }
+}
+
+/// A fresh zone's settings, before any hardening
+fn default_settings() -> Vec<Value> {
+    vec![
+        json!({"id": "ssl", "value": "flexible", "editable": true}),
+        json!({"id": "always_use_https", "value": "off", "editable": true}),
+        json!({"id": "min_tls_version", "value": "1.0", "editable": true}),
+        json!({"id": "browser_cache_ttl", "value": 14400, "editable": true}),
+        json!({"id": "security_header", "editable": true, "value": {
+            "strict_transport_security": {
+                "enabled": false, "max_age": 0, "include_subdomains": false, "preload": false, "nosniff": false
+            }
+        }}),
+        json!({"id": "http3", "value": "on", "editable": false}),
+    ]
+}
+
+fn list_records(state: &State, req: &Request) -> (u16, Value) {
+    let contains = |field: &Value, needle: Option<&str>| {
+        needle.is_none_or(|n| field.as_str().unwrap_or("").to_lowercase().contains(&n.to_lowercase()))
+    };
+
+    let mut records: Vec<Value> = state.records.iter()
+        .filter(|r| req.param("name").is_none_or(|n| r["name"].as_str().unwrap_or("").eq_ignore_ascii_case(n)))
+        .filter(|r| req.param("type").is_none_or(|t| r["type"] == t))
+        .filter(|r| req.param("content.exact").is_none_or(|c| r["content"] == c))
+        .filter(|r| req.param("proxied").is_none_or(|p| r["proxied"] == (p == "true")))
+        .filter(|r| contains(&r["name"], req.param("name.contains")))
+        .filter(|r| contains(&r["comment"], req.param("comment.contains")))
+        .filter(|r| req.param("tag").is_none_or(|t| has_tag(r, |tag| tag == t)))
+        .filter(|r| req.param("tag.present").is_none_or(|k| has_tag(r, |tag| tag.split(':').next() == Some(k))))
+        .cloned()
+        .collect();
+
+    if let Some(order) = req.param("order") {
+        records.sort_by_key(|r| r[order].to_string());
+        if req.param("direction") == Some("desc") {
+            records.reverse();
+        }
+    }
+
+    let page: usize = req.param("page").and_then(|p| p.parse().ok()).unwrap_or(1).max(1);
+    let per_page: usize = req.param("per_page").and_then(|p| p.parse().ok()).unwrap_or(100).max(1);
+    let total_count = records.len();
+    let total_pages = total_count.div_ceil(per_page).max(1);
+    let items: Vec<Value> = records.into_iter().skip((page - 1) * per_page).take(per_page).collect();
+
+    let mut payload = success(json!(items));
+    payload["result_info"] = json!({
+        "page": page,
+        "per_page": per_page,
+        "count": items.len(),
+        "total_count": total_count,
+        "total_pages": total_pages
+    });
+    (200, payload)
+}
+
+fn has_tag(record: &Value, pred: impl Fn(&str) -> bool) -> bool {
+    record["tags"].as_array().is_some_and(|tags| tags.iter().filter_map(Value::as_str).any(pred))
+}
+
+fn zone_name(state: &State, zone_id: &str) -> String {
+    state.zones.iter()
+        .find(|(id, _)| id == zone_id)
+        .map(|(_, name)| name.clone())
+        .unwrap_or_default()
+}
+
+fn absolute_name(zone: &str, name: &str) -> String {
+    if name == "@" || name.is_empty() {
+        zone.to_string()
+    } else if name == zone || name.ends_with(&format!(".{}", zone)) {
+        name.to_string()
+    } else {
+        format!("{}.{}", name, zone)
+    }
+}
+
+/// Build a stored record from an API body, as Cloudflare would
+fn new_record(state: &mut State, zone: &str, body: &Value) -> Value {
+    let id = format!("{:032x}", state.next_id);
+    state.next_id += 1;
+
+    let mut record = json!({
+        "id": id,
+        "zone_id": ZONE_ID,
+        "zone_name": zone,
+        "type": body["type"],
+        "name": absolute_name(zone, body["name"].as_str().unwrap_or("@")),
+        "content": body.get("content").cloned().unwrap_or(json!("")),
+        "proxied": body.get("proxied").cloned().unwrap_or(json!(false)),
+        "ttl": body.get("ttl").cloned().unwrap_or(json!(1)),
+        "comment": body.get("comment").cloned().unwrap_or(Value::Null),
+        "tags": body.get("tags").cloned().unwrap_or(json!([])),
+        "created_on": CREATED_ON,
+        "modified_on": CREATED_ON,
+    });
+    for key in ["priority", "data"] {
+        if let Some(v) = body.get(key) {
+            record[key] = v.clone();
+        }
+    }
+    sync_content(&mut record);
+    record
+}
+
+/// Derive `content` from `data` for SRV and CAA records, as the API does
+fn sync_content(record: &mut Value) {
+    let data = record["data"].clone();
+    let content = match record["type"].as_str() {
+        Some("SRV") if data.is_object() => format!(
+            "{} {} {}",
+            data["weight"], data["port"], data["target"].as_str().unwrap_or("")
+        ),
+        Some("CAA") if data.is_object() => format!(
+            "{} {} \"{}\"",
+            data["flags"], data["tag"].as_str().unwrap_or(""), data["value"].as_str().unwrap_or("")
+        ),
+        _ => return,
+    };
+    record["content"] = json!(content);
+}
+
+// ============ Mock DNS ============
+
+/// UDP socket and TCP listener on the same port; the TCP port is taken first and
+/// a fresh pair is tried when another test already holds the UDP side
+fn bind_dns_pair() -> (UdpSocket, TcpListener) {
+    for _ in 0..50 {
+        let tcp = TcpListener::bind("127.0.0.1:0").expect("bind mock DNS TCP server");
+        if let Ok(socket) = UdpSocket::bind(tcp.local_addr().unwrap()) {
+            return (socket, tcp);
+        }
+    }
+    panic!("no free port for the mock DNS server");
+}
+
+/// Answer A, AAAA and TXT queries from the mock's records
+fn serve_dns(socket: UdpSocket, state: &Mutex<State>) {
+    let mut buf = [0u8; 512];
+    while let Ok((len, peer)) = socket.recv_from(&mut buf) {
+        if let Some(mut reply) = dns_reply(&buf[..len], state) {
+            if reply.len() > 512 {
+                // Truncated: header and question only (the query's length), TC set, no answers
+                reply.truncate(len);
+                reply[2] |= 0x02;
+                reply[6..8].copy_from_slice(&[0, 0]);
+            }
+            socket.send_to(&reply, peer).ok();
+        }
+    }
+}
+
+/// Same answers over TCP, framed with a two-byte length prefix
+fn serve_dns_tcp(listener: TcpListener, state: &Mutex<State>) {
+    for mut stream in listener.incoming().flatten() {
+        let mut len = [0u8; 2];
+        if stream.read_exact(&mut len).is_err() {
+            continue;
+        }
+        let mut query = vec![0u8; u16::from_be_bytes(len) as usize];
+        if stream.read_exact(&mut query).is_err() {
+            continue;
+        }
+        if let Some(reply) = dns_reply(&query, state) {
+            let mut framed = (reply.len() as u16).to_be_bytes().to_vec();
+            framed.extend_from_slice(&reply);
+            stream.write_all(&framed).ok();
+        }
+    }
+}
+
+/// TXT character-strings: quoted parts as stored by Cloudflare, else 255-byte chunks
+fn txt_strings(content: &str) -> Vec<Vec<u8>> {
+    if !content.starts_with('"') {
+        return content.as_bytes().chunks(255).map(<[u8]>::to_vec).collect();
+    }
+    content.split('"').skip(1).step_by(2).map(|part| part.as_bytes().to_vec()).collect()
+}
+
+fn dns_reply(query: &[u8], state: &Mutex<State>) -> Option<Vec<u8>> {
+    // Question: labels from offset 12, then QTYPE and QCLASS
+    let mut pos = 12;
+    let mut labels = Vec::new();
+    loop {
+        let len = *query.get(pos)? as usize;
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+        labels.push(String::from_utf8_lossy(query.get(pos..pos + len)?).into_owned());
+        pos += len;
+    }
+    let name = labels.join(".");
+    let qtype = u16::from_be_bytes([*query.get(pos)?, *query.get(pos + 1)?]);
+    let question = &query[12..pos + 4];
+
+    let mut state = state.lock().unwrap();
+    let lagging = state.dns_lag > 0;
+    state.dns_lag = state.dns_lag.saturating_sub(1);
+
+    let named: Vec<&Value> = state.records.iter()
+        .filter(|r| !lagging && r["name"].as_str().is_some_and(|n| n.eq_ignore_ascii_case(&name)))
+        .collect();
+    let answers: Vec<Vec<u8>> = named.iter()
+        .filter_map(|r| {
+            let content = r["content"].as_str()?;
+            match (r["type"].as_str()?, qtype) {
+                ("A", 1) => content.parse::<std::net::Ipv4Addr>().ok().map(|ip| ip.octets().to_vec()),
+                ("AAAA", 28) => content.parse::<std::net::Ipv6Addr>().ok().map(|ip| ip.octets().to_vec()),
+                ("TXT", 16) => Some(txt_strings(content).into_iter().flat_map(|c| {
+                    let mut s = vec![c.len() as u8];
+                    s.extend_from_slice(&c);
+                    s
+                }).collect()),
+                _ => None,
+            }
+        })
+        .collect();
+
+    let rcode: u8 = if named.is_empty() { 3 } else { 0 };
+    let mut reply = Vec::new();
+    reply.extend_from_slice(&query[0..2]);
+    reply.extend_from_slice(&[0x85, 0x80 | rcode]);  // QR AA RD RA
+    reply.extend_from_slice(&[0, 1]);
+    reply.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+    reply.extend_from_slice(&[0, 0, 0, 0]);
+    reply.extend_from_slice(question);
+    for rdata in answers {
+        reply.extend_from_slice(&[0xc0, 12]);  // pointer to the question name
+        reply.extend_from_slice(&qtype.to_be_bytes());
+        reply.extend_from_slice(&[0, 1, 0, 0, 1, 44]);  // IN, TTL 300
+        reply.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        reply.extend_from_slice(&rdata);
+    }
+    Some(reply)
+}
+
+// ============ Running cf ============
+
+/// Outcome of one `cf --agent` invocation
+#[derive(Debug)]
+pub struct Run {
+    pub code: i32,
+    pub events: Vec<Value>,
+    /// Raw stdout, for commands that write data rather than events
+    pub stdout: Vec<u8>,
+    pub stderr: String,
+}
+
+impl Run {
+    /// Payload of the final `result` event
+    pub fn result(&self) -> &Value {
+        self.event("result")
+            .unwrap_or_else(|| panic!("no result event (exit {}): {:?}\n{}", self.code, self.events, self.stderr))
+    }
+
+    /// Payload of the `error` event
+    pub fn error(&self) -> &Value {
+        self.event("error")
+            .unwrap_or_else(|| panic!("no error event (exit {}): {:?}\n{}", self.code, self.events, self.stderr))
+    }
+
+    pub fn progress(&self) -> Vec<&Value> {
+        self.events.iter().filter(|e| e["type"] == "progress").map(|e| &e["payload"]).collect()
+    }
+
+    fn event(&self, kind: &str) -> Option<&Value> {
+        self.events.iter().rev().find(|e| e["type"] == kind).map(|e| &e["payload"])
+    }
+}
+
+/// A temporary project directory wired to a fresh mock
+pub struct Project {
+    pub mock: MockCloudflare,
+    dir: tempfile::TempDir,
+}
+
+impl Project {
+    pub fn new() -> Self {
+        let mock = MockCloudflare::start();
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let env = format!(
+            "CF_API_BASE={}\nCLOUDFLARE_EXAMPLE_TEST_API_TOKEN={}\n",
+            mock.base_url(),
+            TOKEN
+        );
+        std::fs::write(dir.path().join(".env"), env).unwrap();
+        Self { mock, dir }
+    }
+
+    /// Project whose `.env` also sets the zone ID, so requests go straight to the zone
+    pub fn with_zone() -> Self {
+        let p = Self::new();
+        p.append_env(&format!("CLOUDFLARE_EXAMPLE_TEST_ZONE_ID={}", ZONE_ID));
+        p
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    pub fn append_env(&self, line: &str) {
+        let path = self.file(".env");
+        let mut env = std::fs::read_to_string(&path).unwrap();
+        env.push_str(line);
+        env.push('\n');
+        std::fs::write(path, env).unwrap();
+    }
+
+    /// Write registry.json with the given records for example.test
+    pub fn write_registry(&self, records: Value) {
+        let registry = json!({
+            "version": "1.0",
+            "updated": "2024-01-01",
+            "domains": {DOMAIN: {"zone_id": "", "records": records}},
+            "servers": {}
+        });
+        std::fs::write(self.file("registry.json"), serde_json::to_string_pretty(&registry).unwrap()).unwrap();
+    }
+
+    pub fn read_registry(&self) -> Value {
+        serde_json::from_str(&std::fs::read_to_string(self.file("registry.json")).unwrap()).unwrap()
+    }
+
+    /// Put a fake `ssh` on PATH that logs each remote command and its stdin, then exits with `code`
+    pub fn fake_ssh(&self, code: i32) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let bin = self.file("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let log = self.file("ssh.log");
+        let script = format!(
+            "#!/bin/sh\nfor cmd; do :; done\nprintf '$ %s\\n' \"$cmd\" >> '{log}'\ncat >> '{log}'\nexit {code}\n",
+            log = log.display(),
+            code = code
+        );
+        std::fs::write(bin.join("ssh"), script).unwrap();
+        std::fs::set_permissions(bin.join("ssh"), std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// Everything the fake `ssh` has seen
+    pub fn ssh_log(&self) -> String {
+        std::fs::read_to_string(self.file("ssh.log")).unwrap_or_default()
+    }
+
+    /// Run `cf --agent <args>` inside the project directory
+    pub fn cf(&self, args: &[&str]) -> Run {
+        self.cf_with_env(args, &[])
+    }
+
+    /// Like `cf`, with extra environment variables overriding the defaults
+    pub fn cf_with_env(&self, args: &[&str], env: &[(&str, &str)]) -> Run {
+        let output = Command::new(env!("CARGO_BIN_EXE_cf"))
+            .arg("--agent")
+            .args(args)
+            .current_dir(self.path())
+            .env("CF_PROJECT_DIR", self.path())
+            .env("PATH", format!("{}:{}", self.file("bin").display(), std::env::var("PATH").unwrap_or_default()))
+            .env_remove("CF_API_BASE")
+            .env_remove("CLOUDFLARE_API_TOKEN")
+            .env_remove("CLOUDFLARE_EXAMPLE_TEST_API_TOKEN")
+            .env_remove("CLOUDFLARE_EXAMPLE_TEST_ZONE_ID")
+            .env_remove("CLOUDFLARE_ACCOUNT_ID")
+            .env("CLOUDFLARE_R2_BUCKET_NAME", BUCKET)
+            .env("CLOUDFLARE_R2_S3_API_URL", self.mock.s3_url())
+            .env("CLOUDFLARE_R2_ACCESS_KEY_ID", "test-access-key")
+            .env("CLOUDFLARE_R2_SECRET_ACCESS_KEY", "test-secret-key")
+            .env("CLOUDFLARE_R2_PUBLIC_URL", PUBLIC_URL)
+            .env("CLOUDFLARE_R2_FOLDER_PREFIX", "uploads/")
+            .envs(env.iter().copied())
+            .output()
+            .expect("run cf");
+
+        let events = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|l| serde_json::from_str(l).ok())
+            .collect();
+
+        Run {
+            code: output.status.code().unwrap_or(-1),
+            events,
+            stdout: output.stdout,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+}
@@ -0,0 +1,891 @@
+//! End-to-end tests for `cf dns` against the mock Cloudflare API
+
+mod common;
+
+use common::{Project, CREATED_ON, DOMAIN, MODIFIED_ON, ZONE_ID};
+use serde_json::json;
+
+// ============ list / get ============
+
+#[test]
+fn list_walks_every_page() {
+    let p = Project::with_zone();
+    for i in 0..250 {
+        p.mock.seed("A", &format!("host{}", i), "192.0.2.1");
+    }
+
+    let run = p.cf(&["dns", "list", "--domain", DOMAIN]);
+
+    assert_eq!(run.code, 0);
+    assert_eq!(run.result()["count"], 250);
+    assert_eq!(run.result()["total"], 250);
+    assert_eq!(run.result()["truncated"], false);
+    let pages: Vec<_> = p.mock.requests_with("GET").iter().filter_map(|r| r.param("page").map(String::from)).collect();
+    assert_eq!(pages, ["1", "2", "3"]);
+}
+
+#[test]
+fn list_sends_filters_and_honors_limit() {
+    let p = Project::with_zone();
+    for i in 0..5 {
+        p.mock.seed("CNAME", &format!("api{}", i), "target.example.net");
+    }
+    p.mock.seed("A", "api-direct", "192.0.2.1");
+
+    let run = p.cf(&["dns", "list", "--domain", DOMAIN, "--type", "cname", "--name-contains", "api", "--limit", "3"]);
+
+    assert_eq!(run.code, 0);
+    assert_eq!(run.result()["count"], 3);
+    assert_eq!(run.result()["total"], 5);
+    assert_eq!(run.result()["truncated"], true);
+
+    let request = &p.mock.requests_with("GET")[0];
+    assert_eq!(request.param("type"), Some("CNAME"));
+    assert_eq!(request.param("name.contains"), Some("api"));
+}
+
+#[test]
+fn get_reports_missing_and_existing_records() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "app", "192.0.2.10");
+
+    let run = p.cf(&["dns", "get", "--domain", DOMAIN, "--name", "missing"]);
+    assert_eq!(run.result()["exists"], false);
+
+    let run = p.cf(&["dns", "get", "--domain", DOMAIN, "--name", "app"]);
+    assert_eq!(run.result()["exists"], true);
+    assert_eq!(run.result()["record"]["content"], "192.0.2.10");
+}
+
+#[test]
+fn get_returns_every_record_at_a_name() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "lb", "192.0.2.1");
+    p.mock.seed("A", "lb", "192.0.2.2");
+    p.mock.seed("TXT", "lb", "owner=ops");
+
+    let run = p.cf(&["dns", "get", "--domain", DOMAIN, "--name", "lb"]);
+    assert_eq!(run.result()["count"], 3);
+    assert!(run.result().get("record").is_none());
+
+    let run = p.cf(&["dns", "get", "--domain", DOMAIN, "--name", "lb", "--type", "TXT"]);
+    assert_eq!(run.result()["count"], 1);
+    assert_eq!(run.result()["record"]["content"], "owner=ops");
+}
+
+#[test]
+fn lookups_by_name_walk_every_page() {
+    let p = Project::with_zone();
+    for i in 0..150 {
+        p.mock.seed("A", "lb", &format!("192.0.2.{}", i));
+    }
+
+    let run = p.cf(&["dns", "get", "--domain", DOMAIN, "--name", "lb"]);
+    assert_eq!(run.result()["count"], 150);
+
+    // The match is on the second page
+    let run = p.cf(&["dns", "delete", "--domain", DOMAIN, "--name", "lb", "--content", "192.0.2.149"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(p.mock.records().len(), 149);
+}
+
+#[test]
+fn list_filters_by_tag_and_shows_metadata() {
+    let p = Project::with_zone();
+    p.mock.seed_with(json!({"type": "A", "name": "app", "content": "192.0.2.10", "tags": ["owner:platform"]}));
+    p.mock.seed_with(json!({"type": "A", "name": "db", "content": "192.0.2.20", "tags": ["owner:data"]}));
+    p.mock.seed("A", "misc", "192.0.2.30");
+
+    let run = p.cf(&["dns", "list", "--domain", DOMAIN, "--tag", "owner:platform"]);
+    assert_eq!(run.result()["count"], 1);
+    assert_eq!(run.result()["records"][0]["tags"], json!(["owner:platform"]));
+    assert_eq!(run.result()["records"][0]["created_on"], CREATED_ON);
+    assert_eq!(p.mock.requests_with("GET")[0].param("tag"), Some("owner:platform"));
+
+    let run = p.cf(&["dns", "list", "--domain", DOMAIN, "--tag", "owner"]);
+    assert_eq!(run.result()["count"], 2);
+}
+
+// ============ create ============
+
+#[test]
+fn create_posts_record() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&["dns", "create", "--domain", DOMAIN, "--name", "app", "--ip", "192.0.2.10", "--desc", "App server"]);
+
+    assert_eq!(run.code, 0);
+    assert_eq!(run.result()["action"], "created");
+    assert_eq!(run.result()["fqdn"], "app.example.test");
+
+    let records = p.mock.records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["name"], "app.example.test");
+    assert_eq!(records[0]["content"], "192.0.2.10");
+    assert_eq!(records[0]["comment"], "App server");
+}
+
+#[test]
+fn create_sets_ttl_and_tags() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&[
+        "dns", "create", "--domain", DOMAIN, "--name", "app", "--ip", "192.0.2.10",
+        "--ttl", "300", "--tag", "owner:platform", "--tag", "env:prod",
+    ]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["ttl"], 300);
+    let body = p.mock.requests_with("POST")[0].body.clone().unwrap();
+    assert_eq!(body["ttl"], 300);
+    assert_eq!(body["tags"], json!(["owner:platform", "env:prod"]));
+
+    let run = p.cf(&["dns", "create", "--domain", DOMAIN, "--name", "bad", "--ip", "192.0.2.10", "--ttl", "5"]);
+    assert!(run.stderr.contains("TTL must be 1 (auto) or 30-86400"));
+    let run = p.cf(&["dns", "create", "--domain", DOMAIN, "--name", "bad", "--ip", "192.0.2.10", "--tag", "no-colon"]);
+    assert!(run.stderr.contains("tag must be key:value"));
+    assert_eq!(p.mock.records().len(), 1);
+}
+
+#[test]
+fn create_existing_record_is_an_input_error() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "app", "192.0.2.10");
+
+    let run = p.cf(&["dns", "create", "--domain", DOMAIN, "--name", "app", "--ip", "192.0.2.10"]);
+
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "RECORD_EXISTS");
+    assert_eq!(run.error()["op"], "dns.create");
+    assert_eq!(p.mock.records().len(), 1);
+}
+
+#[test]
+fn create_upsert_updates_in_place() {
+    let p = Project::with_zone();
+    let id = p.mock.seed("A", "app", "192.0.2.10");
+
+    let run = p.cf(&["dns", "create", "--domain", DOMAIN, "--name", "app", "--ip", "192.0.2.20", "--upsert"]);
+
+    assert_eq!(run.code, 0);
+    assert_eq!(run.result()["action"], "updated");
+    assert_eq!(run.result()["before"]["content"], "192.0.2.10");
+    assert_eq!(run.result()["after"]["content"], "192.0.2.20");
+    assert!(p.mock.requests_with("POST").is_empty());
+    assert_eq!(p.mock.records()[0]["id"], id.as_str());
+}
+
+#[test]
+fn create_upsert_keeps_comment_and_proxy_unless_given() {
+    let p = Project::with_zone();
+    p.mock.seed_with(json!({"type": "A", "name": "app", "content": "192.0.2.10", "proxied": true, "comment": "web"}));
+
+    let run = p.cf(&["dns", "create", "--domain", DOMAIN, "--name", "app", "--ip", "192.0.2.20", "--upsert"]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let body = p.mock.requests_with("PATCH")[0].body.clone().unwrap();
+    assert!(body.get("comment").is_none() && body.get("proxied").is_none(), "{}", body);
+    assert_eq!(p.mock.records()[0]["proxied"], true);
+    assert_eq!(p.mock.records()[0]["comment"], "web");
+}
+
+#[test]
+fn srv_weight_and_port_can_be_changed() {
+    let p = Project::with_zone();
+    p.mock.seed_with(json!({
+        "type": "SRV", "name": "_sip._tcp",
+        "data": {"priority": 10, "weight": 5, "port": 5060, "target": "sip.example.test"}
+    }));
+
+    let run = p.cf(&[
+        "dns", "create", "--domain", DOMAIN, "--name", "_sip._tcp", "--type", "SRV",
+        "--content", "sip.example.test", "--priority", "10", "--weight", "20", "--port", "5061", "--upsert",
+    ]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(p.mock.records()[0]["data"]["weight"], 20);
+    assert_eq!(p.mock.records()[0]["data"]["port"], 5061);
+
+    let run = p.cf(&["dns", "update", "--domain", DOMAIN, "--name", "_sip._tcp", "--type", "SRV", "--port", "5062"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(p.mock.records()[0]["data"], json!({"priority": 10, "weight": 20, "port": 5062, "target": "sip.example.test"}));
+}
+
+#[test]
+fn create_validates_content_before_calling_the_api() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&["dns", "create", "--domain", DOMAIN, "--name", "app", "--ip", "not-an-ip"]);
+
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "INVALID_RECORD");
+    assert!(p.mock.requests().is_empty());
+}
+
+#[test]
+fn create_sends_structured_srv_data() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&[
+        "dns", "create", "--domain", DOMAIN, "--name", "_sip._tcp", "--type", "SRV",
+        "--content", "sip.example.test", "--priority", "10", "--weight", "5", "--port", "5060",
+    ]);
+
+    assert_eq!(run.code, 0);
+    let body = p.mock.requests_with("POST")[0].body.clone().unwrap();
+    assert_eq!(body["data"], json!({"priority": 10, "weight": 5, "port": 5060, "target": "sip.example.test"}));
+}
+
+// ============ update / delete ============
+
+#[test]
+fn update_patches_only_changed_fields() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "app", "192.0.2.10");
+
+    let run = p.cf(&["dns", "update", "--domain", DOMAIN, "--name", "app", "--proxied", "true", "--ttl", "300"]);
+
+    assert_eq!(run.code, 0);
+    assert_eq!(run.result()["before"]["proxied"], false);
+    assert_eq!(run.result()["after"]["proxied"], true);
+    assert_eq!(p.mock.requests_with("PATCH")[0].body, Some(json!({"proxied": true, "ttl": 300})));
+}
+
+#[test]
+fn update_merges_tags_by_key() {
+    let p = Project::with_zone();
+    p.mock.seed_with(json!({"type": "A", "name": "app", "content": "192.0.2.10", "tags": ["owner:ops", "temp:yes", "env:prod"]}));
+
+    let run = p.cf(&["dns", "update", "--domain", DOMAIN, "--name", "app", "--tag", "owner:platform", "--untag", "temp"]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(p.mock.requests_with("PATCH")[0].body, Some(json!({"tags": ["env:prod", "owner:platform"]})));
+    assert_eq!(run.result()["after"]["modified_on"], MODIFIED_ON);
+}
+
+#[test]
+fn update_missing_record_is_not_found() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&["dns", "update", "--domain", DOMAIN, "--name", "app", "--ip", "192.0.2.20"]);
+
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "NOT_FOUND");
+}
+
+#[test]
+fn delete_removes_record() {
+    let p = Project::with_zone();
+    let id = p.mock.seed("A", "app", "192.0.2.10");
+
+    let run = p.cf(&["dns", "delete", "--domain", DOMAIN, "--name", "app"]);
+    assert_eq!(run.result()["deleted"], true);
+    assert_eq!(run.result()["record_id"], id.as_str());
+    assert!(p.mock.records().is_empty());
+
+    let run = p.cf(&["dns", "delete", "--domain", DOMAIN, "--name", "app"]);
+    assert_eq!(run.result()["deleted"], false);
+}
+
+#[test]
+fn ambiguous_delete_lists_candidates() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "lb", "192.0.2.1");
+    p.mock.seed("A", "lb", "192.0.2.2");
+
+    let run = p.cf(&["dns", "delete", "--domain", DOMAIN, "--name", "lb"]);
+
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "AMBIGUOUS_RECORD");
+    assert_eq!(run.error()["details"]["candidates"].as_array().unwrap().len(), 2);
+    assert!(p.mock.requests_with("DELETE").is_empty());
+}
+
+#[test]
+fn delete_narrows_by_content_type_or_id() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "lb", "192.0.2.1");
+    p.mock.seed("A", "lb", "192.0.2.2");
+    let txt = p.mock.seed("TXT", "lb", "owner=ops");
+
+    let run = p.cf(&["dns", "delete", "--domain", DOMAIN, "--name", "lb", "--content", "192.0.2.2"]);
+    assert_eq!(run.result()["deleted"], true);
+
+    let run = p.cf(&["dns", "delete", "--domain", DOMAIN, "--name", "lb", "--id", &txt]);
+    assert_eq!(run.result()["record_id"], txt.as_str());
+
+    let run = p.cf(&["dns", "delete", "--domain", DOMAIN, "--name", "lb", "--type", "A"]);
+    assert_eq!(run.result()["deleted"], true);
+    assert!(p.mock.records().is_empty());
+}
+
+#[test]
+fn ambiguous_update_requires_id() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "lb", "192.0.2.1");
+    let second = p.mock.seed("A", "lb", "192.0.2.2");
+
+    let run = p.cf(&["dns", "update", "--domain", DOMAIN, "--name", "lb", "--ip", "192.0.2.3"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "AMBIGUOUS_RECORD");
+
+    let run = p.cf(&["dns", "update", "--domain", DOMAIN, "--name", "lb", "--id", &second, "--ip", "192.0.2.3"]);
+    assert_eq!(run.result()["before"]["content"], "192.0.2.2");
+    assert_eq!(run.result()["after"]["content"], "192.0.2.3");
+}
+
+// ============ plan / apply / export / import ============
+
+#[test]
+fn apply_syncs_registry_records() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "keep", "192.0.2.1");
+    p.mock.seed("A", "move", "192.0.2.2");
+    p.mock.seed("A", "stale", "192.0.2.9");
+    p.mock.seed_with(json!({"type": "MX", "name": "@", "content": "mail.example.test", "priority": 10}));
+    p.write_registry(json!([
+        {"type": "A", "name": "keep", "content": "192.0.2.1"},
+        {"type": "A", "name": "move", "content": "192.0.2.3"},
+        {"type": "CNAME", "name": "www", "content": "example.test"}
+    ]));
+
+    let run = p.cf(&["dns", "plan", "--domain", DOMAIN]);
+    assert_eq!(run.result()["summary"], json!({"create": 1, "update": 1, "delete": 1}));
+    assert_eq!(run.result()["unchanged"], 1);
+    assert_eq!(run.result()["unmanaged"], 1);
+    assert_eq!(p.mock.records().len(), 4);
+
+    let run = p.cf(&["dns", "apply", "--domain", DOMAIN, "--prune"]);
+    assert_eq!(run.code, 0);
+    assert_eq!(run.progress().len(), 3);
+    // MX is not a type registry.json manages here, so --prune leaves it alone
+    assert!(p.mock.records().iter().any(|r| r["type"] == "MX"));
+
+    let run = p.cf(&["dns", "plan", "--domain", DOMAIN]);
+    assert_eq!(run.result()["in_sync"], true);
+}
+
+#[test]
+fn plan_reports_proxy_and_ttl_drift() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "web", "192.0.2.1");
+    p.mock.seed("A", "api", "192.0.2.2");
+    p.mock.seed("A", "free", "192.0.2.3");
+    p.write_registry(json!([
+        {"type": "A", "name": "web", "content": "192.0.2.1", "proxied": true},
+        {"type": "A", "name": "api", "content": "192.0.2.2", "ttl": 300},
+        {"type": "A", "name": "free", "content": "192.0.2.3"}
+    ]));
+
+    let run = p.cf(&["dns", "plan", "--domain", DOMAIN]);
+    assert_eq!(run.result()["summary"], json!({"create": 0, "update": 2, "delete": 0}));
+    assert_eq!(run.result()["unchanged"], 1);
+
+    let run = p.cf(&["dns", "apply", "--domain", DOMAIN]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let records = p.mock.records();
+    let find = |name: &str| records.iter().find(|r| r["name"] == format!("{}.{}", name, DOMAIN)).unwrap();
+    assert_eq!(find("web")["proxied"], true);
+    assert_eq!(find("api")["ttl"], 300);
+
+    let run = p.cf(&["dns", "plan", "--domain", DOMAIN]);
+    assert_eq!(run.result()["in_sync"], true);
+}
+
+#[test]
+fn registry_import_records_live_proxy_and_ttl() {
+    let p = Project::with_zone();
+    p.mock.seed_with(json!({"type": "A", "name": "web", "content": "192.0.2.1", "proxied": true}));
+    p.mock.seed_with(json!({"type": "A", "name": "api", "content": "192.0.2.2", "ttl": 300}));
+    p.write_registry(json!([
+        {"type": "A", "name": "web", "content": "192.0.2.1", "proxied": false}
+    ]));
+
+    let run = p.cf(&["registry", "import", "--domain", DOMAIN]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["updated"], 1);
+    assert_eq!(run.result()["added"], 1);
+
+    let registry = p.read_registry();
+    let records = registry["domains"][DOMAIN]["records"].as_array().unwrap();
+    let find = |name: &str| records.iter().find(|r| r["name"] == name).unwrap();
+    assert_eq!(find("web")["proxied"], true);
+    assert_eq!(find("api")["proxied"], false);
+    assert_eq!(find("api")["ttl"], 300);
+
+    let run = p.cf(&["dns", "plan", "--domain", DOMAIN]);
+    assert_eq!(run.result()["in_sync"], true);
+}
+
+#[test]
+fn export_and_import_round_trip() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "@", "192.0.2.1");
+    p.mock.seed_with(json!({"type": "MX", "name": "@", "content": "mail.example.test", "priority": 10}));
+    p.mock.seed("TXT", "@", "v=spf1 -all");
+
+    let zone_file = p.file("example.test.zone");
+    let run = p.cf(&["dns", "export", "--domain", DOMAIN, "--out", zone_file.to_str().unwrap()]);
+    assert_eq!(run.result()["count"], 3);
+    let text = std::fs::read_to_string(&zone_file).unwrap();
+    assert!(text.contains("$ORIGIN example.test."));
+
+    let run = p.cf(&["dns", "import", "--file", zone_file.to_str().unwrap(), "--dry-run"]);
+    assert_eq!(run.result()["in_sync"], true);
+    assert_eq!(run.result()["unchanged"], 3);
+}
+
+#[test]
+fn import_restores_proxy_status_ttl_and_long_txt() {
+    let source = Project::with_zone();
+    let dkim = format!("v=DKIM1; k=rsa; p={}", "A".repeat(300));
+    source.mock.seed_with(json!({"type": "A", "name": "www", "content": "192.0.2.1", "proxied": true}));
+    source.mock.seed_with(json!({"type": "A", "name": "api", "content": "192.0.2.2", "ttl": 300}));
+    source.mock.seed("TXT", "mail._domainkey", &dkim);
+
+    let zone_file = source.file("example.test.zone");
+    source.cf(&["dns", "export", "--domain", DOMAIN, "--out", zone_file.to_str().unwrap()]);
+    let text = std::fs::read_to_string(&zone_file).unwrap();
+    assert!(text.contains("cf_tags=cf-proxied:true"));
+
+    let target = Project::with_zone();
+    let run = target.cf(&["dns", "import", "--file", zone_file.to_str().unwrap()]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+
+    let records = target.mock.records();
+    let find = |name: &str| records.iter().find(|r| r["name"] == format!("{}.{}", name, DOMAIN)).unwrap();
+    assert_eq!(find("www")["proxied"], true);
+    assert_eq!(find("api")["proxied"], false);
+    assert_eq!(find("api")["ttl"], 300);
+    assert_eq!(find("mail._domainkey")["content"], dkim.as_str());
+
+    let run = target.cf(&["dns", "import", "--file", zone_file.to_str().unwrap(), "--dry-run"]);
+    assert_eq!(run.result()["in_sync"], true);
+}
+
+#[test]
+fn export_splits_long_txt_between_characters() {
+    let p = Project::with_zone();
+    let note = "é".repeat(200);
+    p.mock.seed("TXT", "note", &note);
+
+    let zone_file = p.file("example.test.zone");
+    p.cf(&["dns", "export", "--domain", DOMAIN, "--out", zone_file.to_str().unwrap()]);
+    let text = std::fs::read_to_string(&zone_file).unwrap();
+    assert!(!text.contains('\u{FFFD}'), "{}", text);
+    assert!(text.contains(&format!("\"{}\" \"{}\"", "é".repeat(127), "é".repeat(73))), "{}", text);
+
+    let run = p.cf(&["dns", "import", "--file", zone_file.to_str().unwrap(), "--dry-run"]);
+    assert_eq!(run.result()["in_sync"], true);
+}
+
+// ============ batch ============
+
+/// Content of the first mock record with this relative name
+fn content_of(p: &Project, name: &str) -> Option<String> {
+    let fqdn = if name == "@" { DOMAIN.to_string() } else { format!("{}.{}", name, DOMAIN) };
+    p.mock.records().iter()
+        .find(|r| r["name"] == fqdn.as_str())
+        .map(|r| r["content"].as_str().unwrap().to_string())
+}
+
+#[test]
+fn batch_runs_json_operations() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "web", "192.0.2.1");
+    p.mock.seed("CNAME", "old", "legacy.example.net");
+    std::fs::write(p.file("ops.json"), json!([
+        {"op": "create", "name": "api", "type": "A", "content": "192.0.2.10"},
+        {"op": "create", "name": "@", "type": "MX", "content": "mail.example.test", "priority": 10},
+        {"op": "update", "name": "web", "type": "A", "content": "192.0.2.2"},
+        {"op": "delete", "name": "old", "type": "CNAME"}
+    ]).to_string()).unwrap();
+
+    let run = p.cf(&["dns", "batch", "--domain", DOMAIN, "--file", p.file("ops.json").to_str().unwrap()]);
+
+    assert_eq!(run.code, 0);
+    assert_eq!(run.result()["count"], 4);
+    assert!(run.result()["results"].as_array().unwrap().iter().all(|r| r["status"] == "ok"));
+    assert_eq!(run.progress().len(), 4);
+    assert_eq!(content_of(&p, "api").as_deref(), Some("192.0.2.10"));
+    assert_eq!(content_of(&p, "web").as_deref(), Some("192.0.2.2"));
+    assert_eq!(content_of(&p, "old"), None);
+}
+
+#[test]
+fn batch_reads_csv() {
+    let p = Project::with_zone();
+    std::fs::write(
+        p.file("ops.csv"),
+        "op,name,type,content,proxied,comment\ncreate,api,A,192.0.2.10,true,\"API, primary\"\ncreate,@,TXT,\"v=spf1 -all\",,\n",
+    )
+    .unwrap();
+
+    let run = p.cf(&["dns", "batch", "--domain", DOMAIN, "--file", p.file("ops.csv").to_str().unwrap()]);
+
+    assert_eq!(run.code, 0);
+    let records = p.mock.records();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records.iter().find(|r| r["type"] == "A").unwrap()["comment"], "API, primary");
+    assert_eq!(content_of(&p, "@").as_deref(), Some("v=spf1 -all"));
+}
+
+#[test]
+fn batch_runs_operations_on_one_name_in_file_order() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "www", "192.0.2.1");
+    std::fs::write(p.file("ops.json"), json!([
+        {"op": "delete", "name": "www", "type": "A"},
+        {"op": "create", "name": "www", "type": "CNAME", "content": "example.pages.dev"},
+        {"op": "create", "name": "api", "type": "A", "content": "192.0.2.10"}
+    ]).to_string()).unwrap();
+
+    let run = p.cf(&["dns", "batch", "--domain", DOMAIN, "--file", p.file("ops.json").to_str().unwrap(), "--concurrency", "8"]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(content_of(&p, "www").as_deref(), Some("example.pages.dev"));
+    assert_eq!(content_of(&p, "api").as_deref(), Some("192.0.2.10"));
+}
+
+#[test]
+fn batch_csv_rejects_proxied_typos() {
+    let p = Project::with_zone();
+    std::fs::write(p.file("ops.csv"), "op,name,type,content,proxied\ncreate,api,A,192.0.2.10,ture\n").unwrap();
+
+    let run = p.cf(&["dns", "batch", "--domain", DOMAIN, "--file", p.file("ops.csv").to_str().unwrap()]);
+
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "PARSE_FAIL");
+    assert!(p.mock.requests().is_empty());
+}
+
+#[test]
+fn batch_rejects_invalid_operations_before_sending() {
+    let p = Project::with_zone();
+    std::fs::write(p.file("ops.json"), json!([
+        {"op": "create", "name": "api", "type": "A", "content": "192.0.2.10"},
+        {"op": "create", "name": "bad", "type": "A", "content": "not-an-ip"},
+        {"op": "rename", "name": "x"}
+    ]).to_string()).unwrap();
+
+    let run = p.cf(&["dns", "batch", "--domain", DOMAIN, "--file", p.file("ops.json").to_str().unwrap()]);
+
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "INVALID_BATCH");
+    assert_eq!(run.error()["details"]["invalid"].as_array().unwrap().len(), 2);
+    assert!(p.mock.requests().is_empty());
+}
+
+#[test]
+fn batch_without_atomic_keeps_completed_operations() {
+    let p = Project::with_zone();
+    std::fs::write(p.file("ops.json"), json!([
+        {"op": "create", "name": "api", "type": "A", "content": "192.0.2.10"},
+        {"op": "delete", "name": "missing", "type": "A"}
+    ]).to_string()).unwrap();
+
+    let run = p.cf(&["dns", "batch", "--domain", DOMAIN, "--file", p.file("ops.json").to_str().unwrap()]);
+
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "BATCH_FAILED");
+    assert_eq!(run.error()["cat"], "in");
+    assert_eq!(run.error()["details"]["results"][0]["status"], "ok");
+    assert_eq!(run.error()["details"]["results"][1]["status"], "failed");
+    assert_eq!(content_of(&p, "api").as_deref(), Some("192.0.2.10"));
+}
+
+#[test]
+fn batch_reports_api_outages_as_retryable() {
+    let p = Project::with_zone();
+    p.mock.fail_next(503, Some(0));
+    std::fs::write(p.file("ops.json"), json!([
+        {"op": "create", "name": "api", "type": "A", "content": "192.0.2.10"}
+    ]).to_string()).unwrap();
+
+    let run = p.cf(&["dns", "batch", "--domain", DOMAIN, "--file", p.file("ops.json").to_str().unwrap()]);
+
+    assert_eq!(run.code, 2);
+    assert_eq!(run.error()["code"], "BATCH_FAILED");
+    assert_eq!(run.error()["cat"], "ext");
+    assert_eq!(run.error()["retryable"], true);
+    assert_eq!(run.error()["details"]["results"][0]["status"], "failed");
+}
+
+#[test]
+fn batch_atomic_rolls_back_completed_operations() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "web", "192.0.2.1");
+    p.mock.seed_with(json!({"type": "TXT", "name": "gone", "content": "keep me", "comment": "note", "tags": ["owner:ops"]}));
+    std::fs::write(p.file("ops.json"), json!([
+        {"op": "create", "name": "api", "type": "A", "content": "192.0.2.10"},
+        {"op": "update", "name": "web", "type": "A", "content": "192.0.2.2", "ttl": 300},
+        {"op": "delete", "name": "gone", "type": "TXT"},
+        {"op": "delete", "name": "missing", "type": "A"},
+        {"op": "create", "name": "never", "type": "A", "content": "192.0.2.99"}
+    ]).to_string()).unwrap();
+
+    let run = p.cf(&[
+        "dns", "batch", "--domain", DOMAIN, "--file", p.file("ops.json").to_str().unwrap(),
+        "--atomic", "--concurrency", "1",
+    ]);
+
+    assert_eq!(run.code, 1);
+    let results = &run.error()["details"]["results"];
+    assert_eq!(results[0]["status"], "rolled_back");
+    assert_eq!(results[1]["status"], "rolled_back");
+    assert_eq!(results[2]["status"], "rolled_back");
+    assert_eq!(results[3]["status"], "failed");
+    assert_eq!(results[4]["status"], "skipped");
+
+    assert_eq!(content_of(&p, "api"), None);
+    assert_eq!(content_of(&p, "never"), None);
+    assert_eq!(content_of(&p, "web").as_deref(), Some("192.0.2.1"));
+    let restored = p.mock.records().into_iter().find(|r| r["type"] == "TXT").unwrap();
+    assert_eq!(restored["content"], "keep me");
+    assert_eq!(restored["comment"], "note");
+    assert_eq!(restored["tags"], json!(["owner:ops"]));
+    assert_eq!(p.mock.records().iter().find(|r| r["type"] == "A").unwrap()["ttl"], 1);
+}
+
+// ============ propagation ============
+
+#[test]
+fn create_wait_reports_propagation() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&["dns", "create", "--domain", DOMAIN, "--name", "app", "--ip", "192.0.2.10", "--wait", "--resolvers", p.mock.dns_addr()]);
+
+    assert_eq!(run.code, 0);
+    let propagation = &run.result()["propagation"];
+    assert_eq!(propagation["propagated"], true);
+    assert_eq!(propagation["resolvers"][0]["answers"], json!(["192.0.2.10"]));
+    assert!(!run.progress().is_empty());
+}
+
+#[test]
+fn verify_polls_until_resolvers_catch_up() {
+    let p = Project::with_zone();
+    p.mock.seed("TXT", "@", "v=spf1 -all");
+    p.mock.lag_dns(2);
+
+    let run = p.cf(&[
+        "dns", "verify", "--domain", DOMAIN, "--name", "@", "--type", "TXT",
+        "--interval", "1", "--resolvers", p.mock.dns_addr(),
+    ]);
+
+    assert_eq!(run.code, 0);
+    assert_eq!(run.result()["attempts"], 3);
+    assert_eq!(run.result()["expected"], json!({"state": "present", "content": ["v=spf1 -all"]}));
+    assert_eq!(run.progress().len(), 3);
+}
+
+#[test]
+fn verify_joins_long_txt_served_over_tcp() {
+    let p = Project::with_zone();
+    let parts: Vec<String> = ["a", "b", "c"].iter().map(|c| c.repeat(250)).collect();
+    let stored = parts.iter().map(|s| format!("\"{}\"", s)).collect::<Vec<_>>().join(" ");
+    p.mock.seed("TXT", "dkim._domainkey", &stored);
+
+    // 750 bytes of TXT overflow a 512-byte UDP answer, forcing the TCP retry
+    let run = p.cf(&[
+        "dns", "verify", "--domain", DOMAIN, "--name", "dkim._domainkey", "--type", "TXT",
+        "--timeout", "1", "--interval", "1", "--resolvers", p.mock.dns_addr(),
+    ]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["propagated"], true);
+    assert_eq!(run.result()["resolvers"][0]["answers"], json!([parts.concat()]));
+}
+
+#[test]
+fn verify_times_out_with_time_error() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "app", "192.0.2.10");
+
+    let run = p.cf(&[
+        "dns", "verify", "--domain", DOMAIN, "--name", "app", "--content", "192.0.2.99",
+        "--timeout", "1", "--interval", "1", "--resolvers", p.mock.dns_addr(),
+    ]);
+
+    assert_eq!(run.code, 4);
+    assert_eq!(run.error()["code"], "PROPAGATION_TIMEOUT");
+    assert_eq!(run.error()["cat"], "time");
+    assert_eq!(run.error()["details"]["resolvers"][0]["answers"], json!(["192.0.2.10"]));
+}
+
+#[test]
+fn delete_wait_checks_record_is_gone() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "app", "192.0.2.10");
+
+    let run = p.cf(&["dns", "delete", "--domain", DOMAIN, "--name", "app", "--wait", "--resolvers", p.mock.dns_addr()]);
+
+    assert_eq!(run.code, 0);
+    assert_eq!(run.result()["propagation"]["propagated"], true);
+    assert_eq!(run.result()["propagation"]["expected"]["state"], "absent");
+}
+
+// ============ ddns ============
+
+#[test]
+fn ddns_creates_then_updates_only_on_change() {
+    let p = Project::with_zone();
+    let echo = p.mock.echo_url();
+    let ddns = ["dns", "ddns", "--domain", DOMAIN, "--name", "home", "--echo-url", &echo];
+
+    let run = p.cf(&ddns);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["records"][0]["action"], "created");
+    assert_eq!(content_of(&p, "home").as_deref(), Some("203.0.113.10"));
+
+    let run = p.cf(&ddns);
+    assert_eq!(run.result()["changed"], false);
+    assert_eq!(run.result()["records"][0]["action"], "unchanged");
+    assert!(p.mock.requests_with("PATCH").is_empty());
+
+    p.mock.set_public_ip("203.0.113.20");
+    let run = p.cf(&ddns);
+    assert_eq!(run.result()["records"][0]["action"], "updated");
+    assert_eq!(run.result()["records"][0]["previous"], "203.0.113.10");
+    assert_eq!(content_of(&p, "home").as_deref(), Some("203.0.113.20"));
+}
+
+#[test]
+fn ddns_rejects_address_of_the_wrong_family() {
+    let p = Project::with_zone();
+    let echo = p.mock.echo_url();
+
+    let run = p.cf(&["dns", "ddns", "--domain", DOMAIN, "--name", "home", "--family", "ipv6", "--echo-url", &echo]);
+
+    assert_eq!(run.code, 2);
+    assert_eq!(run.error()["code"], "IP_DISCOVERY_FAILED");
+    assert!(p.mock.records().is_empty());
+}
+
+#[test]
+fn ddns_keeps_the_ipv4_change_when_ipv6_fails() {
+    let p = Project::with_zone();
+    let echo = p.mock.echo_url();
+
+    let run = p.cf(&["dns", "ddns", "--domain", DOMAIN, "--name", "home", "--family", "both", "--echo-url", &echo]);
+
+    assert_eq!(run.code, 2);
+    assert_eq!(run.error()["code"], "IP_DISCOVERY_FAILED");
+    let records = &run.error()["details"]["records"];
+    assert_eq!(records[0]["type"], "A");
+    assert_eq!(records[0]["action"], "created");
+    assert_eq!(records[1]["type"], "AAAA");
+    assert_eq!(records[1]["action"], "failed");
+    assert_eq!(content_of(&p, "home").as_deref(), Some("203.0.113.10"));
+}
+
+#[test]
+fn ddns_proxies_an_existing_record() {
+    let p = Project::with_zone();
+    let echo = p.mock.echo_url();
+    p.mock.seed("A", "home", "203.0.113.10");
+
+    let run = p.cf(&["dns", "ddns", "--domain", DOMAIN, "--name", "home", "--proxied", "--echo-url", &echo]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["records"][0]["action"], "updated");
+    assert_eq!(p.mock.records()[0]["proxied"], true);
+}
+
+#[test]
+fn ddns_with_several_records_needs_a_record_id() {
+    let p = Project::with_zone();
+    let echo = p.mock.echo_url();
+    p.mock.seed("A", "home", "198.51.100.1");
+    let id = p.mock.seed("A", "home", "198.51.100.2");
+
+    let run = p.cf(&["dns", "ddns", "--domain", DOMAIN, "--name", "home", "--echo-url", &echo]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "AMBIGUOUS_RECORD");
+    let message = run.error()["message"].as_str().unwrap();
+    assert!(message.contains("--record-id") && message.contains("delete the extra A records"), "{}", message);
+
+    let run = p.cf(&["dns", "ddns", "--domain", DOMAIN, "--name", "home", "--echo-url", &echo, "--record-id", &id]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["records"][0]["record_id"], id.as_str());
+    assert_eq!(run.result()["records"][0]["previous"], "198.51.100.2");
+}
+
+// ============ credentials and API errors ============
+
+#[test]
+fn zone_id_is_looked_up_and_cached() {
+    let p = Project::new();
+    p.write_registry(json!([]));
+
+    let run = p.cf(&["dns", "list", "--domain", DOMAIN]);
+    assert_eq!(run.code, 0);
+    assert_eq!(p.mock.requests()[0].path, "/zones");
+    assert_eq!(p.read_registry()["domains"][DOMAIN]["zone_id"], ZONE_ID);
+
+    p.cf(&["dns", "list", "--domain", DOMAIN]);
+    assert_eq!(p.mock.requests().iter().filter(|r| r.path == "/zones").count(), 1);
+}
+
+#[test]
+fn unknown_zone_is_an_input_error() {
+    let p = Project::new();
+    p.append_env("CLOUDFLARE_API_TOKEN=test-token");
+
+    let run = p.cf(&["dns", "list", "--domain", "unknown.test"]);
+
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "ZONE_NOT_FOUND");
+}
+
+#[test]
+fn legacy_first_label_env_names_are_rejected() {
+    let p = Project::new();
+    p.append_env("CLOUDFLARE_EXAMPLE_ZONE_ID=legacy-zone");
+
+    let run = p.cf(&["dns", "list", "--domain", DOMAIN]);
+
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "LEGACY_ENV_VAR");
+    assert_eq!(run.error()["details"]["replacement"], "CLOUDFLARE_EXAMPLE_TEST_ZONE_ID");
+    assert!(p.mock.requests().is_empty());
+}
+
+#[test]
+fn rejected_token_is_an_auth_error() {
+    let p = Project::with_zone();
+    let env = std::fs::read_to_string(p.file(".env")).unwrap();
+    std::fs::write(p.file(".env"), env.replace("API_TOKEN=test-token", "API_TOKEN=wrong")).unwrap();
+
+    let run = p.cf(&["dns", "list", "--domain", DOMAIN]);
+
+    assert_eq!(run.code, 3);
+    assert_eq!(run.error()["code"], "CF_AUTH_FAILED");
+    assert_eq!(run.error()["details"]["http_status"], 403);
+    assert_eq!(run.error()["details"]["cf_errors"][0]["code"], 10000);
+}
+
+#[test]
+fn rate_limited_requests_are_retried() {
+    let p = Project::with_zone();
+    p.mock.seed("A", "app", "192.0.2.10");
+    p.mock.fail_next(429, Some(0));
+
+    let run = p.cf(&["dns", "list", "--domain", DOMAIN]);
+
+    assert_eq!(run.code, 0);
+    assert_eq!(run.result()["count"], 1);
+    assert_eq!(p.mock.requests().len(), 2);
+}
+
+#[test]
+fn creates_are_not_retried_on_server_errors() {
+    let p = Project::with_zone();
+    p.mock.fail_next(503, Some(0));
+
+    let run = p.cf(&["dns", "create", "--domain", DOMAIN, "--name", "app", "--ip", "192.0.2.10"]);
+
+    assert_eq!(run.code, 2);
+    assert_eq!(run.error()["code"], "CF_UNAVAILABLE");
+    assert_eq!(p.mock.requests_with("POST").len(), 1);
+    assert!(p.mock.records().is_empty());
+}
@@ -0,0 +1,159 @@
+//! End-to-end tests for `cf firewall`
+
+mod common;
+
+use common::{Project, DOMAIN};
+use serde_json::json;
+
+fn project() -> Project {
+    let p = Project::with_zone();
+    p.write_registry(json!([]));
+    p
+}
+
+/// Pretend a recorded rule's expiry has passed
+fn backdate(p: &Project, id: &str) {
+    let mut registry = p.read_registry();
+    registry["firewall"][id]["expires"] = json!("2020-01-01T00:00:00Z");
+    std::fs::write(p.file("registry.json"), serde_json::to_string_pretty(&registry).unwrap()).unwrap();
+}
+
+#[test]
+fn add_access_rules_by_ip_range_asn_and_country() {
+    let p = project();
+
+    for (args, target, value) in [
+        (vec!["--ip", "198.51.100.7"], "ip", "198.51.100.7"),
+        (vec!["--ip", "2001:db8::1"], "ip6", "2001:db8::1"),
+        (vec!["--ip", "198.51.100.0/24"], "ip_range", "198.51.100.0/24"),
+        (vec!["--asn", "64496"], "asn", "AS64496"),
+        (vec!["--country", "xx", "--action", "challenge"], "country", "XX"),
+    ] {
+        let mut cmd = vec!["firewall", "add", "--domain", DOMAIN];
+        cmd.extend(args);
+        let run = p.cf(&cmd);
+        assert_eq!(run.code, 0, "{}", run.stderr);
+        assert_eq!(run.result()["rule"]["target"], target);
+        assert_eq!(run.result()["rule"]["value"], value);
+    }
+
+    let rules = p.mock.access_rules();
+    assert_eq!(rules.len(), 5);
+    assert_eq!(rules[0]["mode"], "block");
+    assert_eq!(rules[4]["mode"], "challenge");
+
+    let run = p.cf(&["firewall", "add", "--domain", DOMAIN, "--ip", "not-an-ip"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "INVALID_TARGET");
+}
+
+#[test]
+fn add_custom_rule_creates_entrypoint_then_appends() {
+    let p = project();
+
+    let run = p.cf(&["firewall", "add", "--domain", DOMAIN, "--expression", "(http.request.uri.path contains \"/wp-login\")", "--note", "no wordpress"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["rule"]["kind"], "custom_rule");
+    assert_eq!(p.mock.requests_with("PUT").len(), 1);
+
+    let run = p.cf(&["firewall", "add", "--domain", DOMAIN, "--expression", "(ip.src.asnum eq 64496)", "--action", "managed_challenge"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(p.mock.requests_with("PUT").len(), 1);
+
+    let rules = p.mock.custom_rules();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0]["description"], "no wordpress");
+    assert_eq!(rules[1]["action"], "managed_challenge");
+    assert_eq!(run.result()["rule"]["id"], rules[1]["id"]);
+
+    let run = p.cf(&["firewall", "add", "--domain", DOMAIN, "--expression", "true", "--action", "allow"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "INVALID_ACTION");
+}
+
+#[test]
+fn list_and_remove_both_kinds() {
+    let p = project();
+    let ip = p.cf(&["firewall", "add", "--domain", DOMAIN, "--ip", "198.51.100.7"]).result()["rule"]["id"].clone();
+    let waf = p.cf(&["firewall", "add", "--domain", DOMAIN, "--expression", "(http.host eq \"x\")"]).result()["rule"]["id"].clone();
+
+    let run = p.cf(&["firewall", "list", "--domain", DOMAIN]);
+    assert_eq!(run.result()["count"], 2);
+    assert_eq!(run.result()["access_rules"][0]["id"], ip);
+    assert_eq!(run.result()["custom_rules"][0]["id"], waf);
+
+    let run = p.cf(&["firewall", "remove", "--domain", DOMAIN, "--id", waf.as_str().unwrap()]);
+    assert_eq!(run.result()["kind"], "custom_rule");
+    let run = p.cf(&["firewall", "remove", "--domain", DOMAIN, "--id", ip.as_str().unwrap()]);
+    assert_eq!(run.result()["kind"], "access_rule");
+    assert!(p.mock.access_rules().is_empty());
+    assert!(p.mock.custom_rules().is_empty());
+
+    let run = p.cf(&["firewall", "remove", "--domain", DOMAIN, "--id", ip.as_str().unwrap()]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "RULE_NOT_FOUND");
+}
+
+#[test]
+fn expires_is_recorded_and_gc_removes_expired_rules() {
+    let p = project();
+    let run = p.cf(&["firewall", "add", "--domain", DOMAIN, "--ip", "198.51.100.7", "--note", "scraper", "--expires", "24h"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let expires = run.result()["expires"].as_str().unwrap().to_string();
+    let ip = run.result()["rule"]["id"].as_str().unwrap().to_string();
+    assert_eq!(p.mock.access_rules()[0]["notes"], format!("scraper (expires {})", expires));
+
+    let waf = p.cf(&["firewall", "add", "--domain", DOMAIN, "--expression", "(http.host eq \"x\")", "--expires", "1h"])
+        .result()["rule"]["id"].as_str().unwrap().to_string();
+    let keep = p.cf(&["firewall", "add", "--domain", DOMAIN, "--asn", "AS64496", "--expires", "7d"])
+        .result()["rule"]["id"].as_str().unwrap().to_string();
+
+    let registry = p.read_registry();
+    assert_eq!(registry["firewall"][&ip], json!({"domain": DOMAIN, "kind": "access_rule", "expires": expires, "note": "scraper"}));
+    assert_eq!(registry["firewall"][&waf]["kind"], "custom_rule");
+
+    // Nothing has expired yet
+    let run = p.cf(&["firewall", "gc"]);
+    assert_eq!(run.result()["removed"], json!([]));
+    assert_eq!(run.result()["pending"], 3);
+
+    backdate(&p, &ip);
+    backdate(&p, &waf);
+
+    let run = p.cf(&["firewall", "gc", "--dry-run"]);
+    assert_eq!(run.result()["removed"].as_array().unwrap().len(), 2);
+    assert_eq!(p.mock.access_rules().len(), 2);
+
+    let run = p.cf(&["firewall", "gc"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["removed"].as_array().unwrap().len(), 2);
+    assert_eq!(run.result()["pending"], 1);
+
+    let rules = p.mock.access_rules();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0]["id"], keep);
+    assert!(p.mock.custom_rules().is_empty());
+    assert_eq!(p.read_registry()["firewall"].as_object().unwrap().len(), 1);
+}
+
+#[test]
+fn gc_forgets_rules_already_removed_elsewhere() {
+    let p = project();
+    let id = p.cf(&["firewall", "add", "--domain", DOMAIN, "--ip", "198.51.100.7", "--expires", "1h"])
+        .result()["rule"]["id"].as_str().unwrap().to_string();
+    p.cf(&["firewall", "remove", "--domain", DOMAIN, "--id", &id]);
+    assert!(p.read_registry()["firewall"].get(&id).is_none());
+
+    let id = p.cf(&["firewall", "add", "--domain", DOMAIN, "--ip", "198.51.100.8", "--expires", "1h"])
+        .result()["rule"]["id"].as_str().unwrap().to_string();
+    backdate(&p, &id);
+    // Removed behind the CLI's back, e.g. in the dashboard
+    let mut registry = p.read_registry();
+    registry["firewall"]["0000000000000000000000000000dead"] = registry["firewall"][&id].clone();
+    std::fs::write(p.file("registry.json"), serde_json::to_string_pretty(&registry).unwrap()).unwrap();
+
+    let run = p.cf(&["firewall", "gc"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["removed"].as_array().unwrap().len(), 2);
+    assert_eq!(p.read_registry()["firewall"], json!({}));
+}
@@ -0,0 +1,433 @@
+//! End-to-end tests for `cf r2` against the mock S3 endpoint
+
+mod common;
+
+use common::{Project, BUCKET};
+use serde_json::json;
+
+/// 64 KiB of non-repeating bytes
+fn payload() -> Vec<u8> {
+    (0..65536u32).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+#[test]
+fn download_streams_to_file_and_verifies_etag() {
+    let p = Project::new();
+    let data = payload();
+    p.mock.put_object("backups/db.sql.gz", &data);
+
+    let run = p.cf(&["r2", "download", "--key", "backups/db.sql.gz", "--out", "restore.gz"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["bytes"], 65536);
+    assert_eq!(run.result()["etag_verified"], true);
+    assert_eq!(std::fs::read(p.file("restore.gz")).unwrap(), data);
+    assert!(!p.file("restore.gz.part").exists());
+
+    // HEAD first, then a GET pinned to the ETag it reported
+    let gets = p.mock.requests_with("GET");
+    assert_eq!(p.mock.requests_with("HEAD").len(), 1);
+    assert_eq!(gets[0].header("if-match"), run.result()["etag"].as_str());
+
+    // Without --out the file is named after the key
+    let run = p.cf(&["r2", "download", "--key", "backups/db.sql.gz"]);
+    assert_eq!(run.result()["path"], "db.sql.gz");
+    assert_eq!(std::fs::read(p.file("db.sql.gz")).unwrap(), data);
+}
+
+#[test]
+fn download_range() {
+    let p = Project::new();
+    p.mock.put_object("logs/app.log", b"0123456789abcdef");
+
+    for (range, want, start, end) in [
+        ("2-5", "2345", 2, 5),
+        ("10-", "abcdef", 10, 15),
+        ("-3", "def", 13, 15),
+        ("12-99", "cdef", 12, 15),
+    ] {
+        let run = p.cf(&["r2", "download", "--key", "logs/app.log", "--range", range, "--out", "part.log"]);
+        assert_eq!(run.code, 0, "{}", run.stderr);
+        assert_eq!(run.result()["range"], json!({"start": start, "end": end}));
+        assert_eq!(run.result()["etag_verified"], false);
+        assert_eq!(std::fs::read_to_string(p.file("part.log")).unwrap(), want);
+    }
+
+    let run = p.cf(&["r2", "download", "--key", "logs/app.log", "--range", "16-"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "INVALID_RANGE");
+
+    let run = p.cf(&["r2", "download", "--key", "logs/app.log", "--range", "5-2"]);
+    assert_eq!(run.code, 2);
+    assert!(run.stderr.contains("range must be start-end"), "{}", run.stderr);
+}
+
+#[test]
+fn download_to_stdout_keeps_events_off_it() {
+    let p = Project::new();
+    let data = payload();
+    p.mock.put_object("artifacts/build.tar", &data);
+
+    let run = p.cf(&["r2", "download", "--key", "artifacts/build.tar", "--out", "-"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.stdout, data);
+    let result: serde_json::Value = run.stderr.lines()
+        .filter_map(|l| serde_json::from_str::<serde_json::Value>(l).ok())
+        .find(|e| e["type"] == "result")
+        .unwrap_or_else(|| panic!("no result event on stderr: {}", run.stderr));
+    assert_eq!(result["v"], 1);
+    assert_eq!(result["payload"]["etag_verified"], true);
+}
+
+#[test]
+fn download_missing_object() {
+    let p = Project::new();
+
+    let run = p.cf(&["r2", "download", "--key", "nope.txt"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "NOT_FOUND");
+    assert!(!p.file("nope.txt").exists());
+    assert!(p.mock.requests_with("GET").is_empty());
+}
+
+/// Multipart settings small enough to split a few MiB: 5 MiB parts above 6 MiB
+const MULTIPART: [&str; 4] = ["--multipart-threshold", "6M", "--part-size", "5M"];
+
+fn large_file(p: &Project, name: &str, mib: usize) -> Vec<u8> {
+    let data: Vec<u8> = (0..mib << 20).map(|i| (i % 251) as u8).collect();
+    std::fs::write(p.file(name), &data).unwrap();
+    data
+}
+
+#[test]
+fn upload_below_threshold_is_a_single_put() {
+    let p = Project::new();
+    std::fs::write(p.file("small.txt"), "hello").unwrap();
+
+    let mut args = vec!["r2", "upload", "--file", "small.txt", "--key", "small.txt"];
+    args.extend(MULTIPART);
+    let run = p.cf(&args);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["parts"], json!(null));
+    assert_eq!(p.mock.object("small.txt").as_deref(), Some(&b"hello"[..]));
+    assert!(p.mock.requests_with("POST").is_empty());
+}
+
+#[test]
+fn upload_large_file_in_parallel_parts() {
+    let p = Project::new();
+    let data = large_file(&p, "db.dump", 12);
+
+    let mut args = vec!["r2", "upload", "--file", "db.dump", "--key", "backups/db.dump", "--part-concurrency", "3"];
+    args.extend(MULTIPART);
+    let run = p.cf(&args);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["parts"], 3);
+    assert_eq!(run.result()["size"], 12 << 20);
+    assert_eq!(p.mock.object("backups/db.dump").unwrap(), data);
+    assert_eq!(p.mock.open_uploads(), 0);
+
+    let parts: Vec<_> = p.mock.requests_with("PUT").iter().map(|r| r.bytes.len()).collect();
+    assert_eq!(parts.iter().sum::<usize>(), 12 << 20);
+    assert!(parts.iter().all(|&n| n == 5 << 20 || n == 2 << 20), "{:?}", parts);
+
+    let progress = run.progress();
+    assert_eq!(progress.len(), 3);
+    assert_eq!(progress.last().unwrap()["percent"], 100);
+
+    // Multipart ETags are not an MD5, so only the size is checked on the way back
+    let run = p.cf(&["r2", "download", "--key", "backups/db.dump", "--out", "copy.dump"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(run.result()["etag"].as_str().unwrap().ends_with("-3\""));
+    assert_eq!(run.result()["etag_verified"], false);
+    assert_eq!(std::fs::read(p.file("copy.dump")).unwrap(), data);
+}
+
+#[test]
+fn upload_rejects_overflowing_sizes_and_zero_concurrency() {
+    let p = Project::new();
+    large_file(&p, "db.dump", 1);
+
+    let run = p.cf(&["r2", "upload", "--file", "db.dump", "--multipart-threshold", "99999999999G"]);
+    assert_eq!(run.code, 2);
+    assert!(run.stderr.contains("size must be bytes"), "{}", run.stderr);
+
+    let run = p.cf(&["r2", "upload", "--file", "db.dump", "--part-concurrency", "0"]);
+    assert_eq!(run.code, 2);
+    assert!(run.stderr.contains("--part-concurrency"), "{}", run.stderr);
+    assert!(p.mock.requests().is_empty());
+}
+
+#[test]
+fn upload_retries_failed_parts() {
+    let p = Project::new();
+    let data = large_file(&p, "db.dump", 11);
+    p.mock.fail_part(2);
+    p.mock.fail_part(2);
+
+    let mut args = vec!["r2", "upload", "--file", "db.dump", "--key", "db.dump"];
+    args.extend(MULTIPART);
+    let run = p.cf(&args);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(p.mock.object("db.dump").unwrap(), data);
+    let attempts = p.mock.requests_with("PUT").iter().filter(|r| r.param("partNumber") == Some("2")).count();
+    assert_eq!(attempts, 3);
+}
+
+/// Checkpoint files left in the project's `.cf/uploads`
+fn checkpoints(p: &Project) -> Vec<serde_json::Value> {
+    std::fs::read_dir(p.file(".cf/uploads"))
+        .map(|dir| dir.flatten().map(|e| serde_json::from_slice(&std::fs::read(e.path()).unwrap()).unwrap()).collect())
+        .unwrap_or_default()
+}
+
+#[test]
+fn failed_upload_keeps_a_checkpoint_and_resumes() {
+    let p = Project::new();
+    let data = large_file(&p, "db.dump", 11);
+    for _ in 0..4 {
+        p.mock.fail_part(3);
+    }
+
+    let mut args = vec!["r2", "upload", "--file", "db.dump", "--key", "db.dump", "--part-concurrency", "1"];
+    args.extend(MULTIPART);
+    let run = p.cf(&args);
+    assert_eq!(run.code, 2);
+    assert_eq!(run.error()["code"], "UPLOAD_FAILED");
+    assert_eq!(run.error()["details"]["parts_uploaded"], 2);
+    assert_eq!(run.error()["details"]["parts_total"], 3);
+    assert_eq!(p.mock.open_uploads(), 1);
+    assert!(p.mock.object("db.dump").is_none());
+
+    let saved = checkpoints(&p);
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0]["upload_id"], run.error()["details"]["upload_id"]);
+    assert_eq!(saved[0]["parts"].as_array().unwrap().len(), 2);
+
+    // Only the missing part is sent again
+    let puts = p.mock.requests_with("PUT").len();
+    let mut resume = args.clone();
+    resume.push("--resume");
+    let run = p.cf(&resume);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["parts"], 3);
+    assert_eq!(run.result()["resumed_parts"], 2);
+    assert_eq!(p.mock.requests_with("PUT").len(), puts + 1);
+    assert_eq!(p.mock.object("db.dump").unwrap(), data);
+    assert_eq!(p.mock.open_uploads(), 0);
+    assert!(checkpoints(&p).is_empty());
+}
+
+#[test]
+fn resume_starts_over_when_the_file_changed() {
+    let p = Project::new();
+    large_file(&p, "db.dump", 11);
+    for _ in 0..4 {
+        p.mock.fail_part(1);
+    }
+    let mut args = vec!["r2", "upload", "--file", "db.dump", "--key", "db.dump", "--part-concurrency", "1"];
+    args.extend(MULTIPART);
+    assert_eq!(p.cf(&args).code, 2);
+    let stale = checkpoints(&p)[0]["upload_id"].clone();
+
+    let data = large_file(&p, "db.dump", 12);
+    // Different size, so the old parts cannot be reused
+    args.push("--resume");
+    let run = p.cf(&args);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["resumed_parts"], 0);
+    assert!(run.events.iter().any(|e| e["payload"]["message"].as_str().is_some_and(|m| m.contains("file changed"))));
+    assert_eq!(p.mock.object("db.dump").unwrap(), data);
+    assert_eq!(p.mock.open_uploads(), 0);
+    let aborted = p.mock.requests_with("DELETE");
+    assert_eq!(aborted[0].param("uploadId"), stale.as_str());
+}
+
+#[test]
+fn abort_discards_an_unfinished_upload() {
+    let p = Project::new();
+    large_file(&p, "db.dump", 11);
+    for _ in 0..4 {
+        p.mock.fail_part(2);
+    }
+    let mut args = vec!["r2", "upload", "--file", "db.dump", "--key", "db.dump"];
+    args.extend(MULTIPART);
+    assert_eq!(p.cf(&args).code, 2);
+    assert_eq!(p.mock.open_uploads(), 1);
+
+    let run = p.cf(&["r2", "upload", "--file", "db.dump", "--key", "db.dump", "--abort"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["aborted"], true);
+    assert_eq!(p.mock.open_uploads(), 0);
+    assert!(checkpoints(&p).is_empty());
+
+    let run = p.cf(&["r2", "upload", "--file", "db.dump", "--key", "db.dump", "--abort"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "NO_CHECKPOINT");
+
+    let run = p.cf(&["r2", "upload", "--file", "db.dump", "--part-size", "1M"]);
+    assert_eq!(run.code, 2);
+    assert!(run.stderr.contains("at least 5M"), "{}", run.stderr);
+}
+
+/// A small site build: index.html, css/app.css and img/logo.png
+fn site(p: &Project) {
+    std::fs::create_dir_all(p.file("dist/css")).unwrap();
+    std::fs::create_dir_all(p.file("dist/img")).unwrap();
+    std::fs::write(p.file("dist/index.html"), "<h1>v1</h1>").unwrap();
+    std::fs::write(p.file("dist/css/app.css"), "body{}").unwrap();
+    std::fs::write(p.file("dist/img/logo.png"), [0x89, b'P', b'N', b'G']).unwrap();
+}
+
+fn plan_actions(run: &common::Run) -> Vec<(String, String)> {
+    run.result()["plan"].as_array().unwrap().iter()
+        .map(|c| (c["action"].as_str().unwrap().to_string(), c["key"].as_str().unwrap().to_string()))
+        .collect()
+}
+
+#[test]
+fn sync_uploads_only_new_and_changed_files() {
+    let p = Project::new();
+    site(&p);
+
+    let run = p.cf(&["r2", "sync", "dist", "site"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["summary"], json!({"upload": 3, "delete": 0, "remote_only": 0, "unchanged": 0}));
+    assert_eq!(p.mock.object_keys(), ["site/css/app.css", "site/img/logo.png", "site/index.html"]);
+    assert_eq!(p.mock.object("site/index.html").as_deref(), Some(&b"<h1>v1</h1>"[..]));
+
+    // Same size, different bytes: caught by the ETag, not the size
+    std::fs::write(p.file("dist/index.html"), "<h1>v2</h1>").unwrap();
+    std::fs::write(p.file("dist/robots.txt"), "").unwrap();
+    let puts = p.mock.requests_with("PUT").len();
+
+    let run = p.cf(&["r2", "sync", "dist", "site/"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["summary"], json!({"upload": 2, "delete": 0, "remote_only": 0, "unchanged": 2}));
+    assert_eq!(run.result()["uploaded"], json!(["site/index.html", "site/robots.txt"]));
+    assert_eq!(p.mock.requests_with("PUT").len(), puts + 2);
+    assert_eq!(p.mock.object("site/index.html").as_deref(), Some(&b"<h1>v2</h1>"[..]));
+    assert_eq!(run.progress().len(), 2);
+
+    let run = p.cf(&["r2", "sync", "dist", "site/"]);
+    assert_eq!(run.result()["summary"]["upload"], 0);
+    assert_eq!(run.result()["summary"]["unchanged"], 4);
+}
+
+#[test]
+fn sync_dry_run_plans_deletes_without_changing_anything() {
+    let p = Project::new();
+    site(&p);
+    p.mock.put_object("site/old.html", b"gone");
+    p.mock.put_object("site/css/app.css", b"body{}");
+    p.mock.put_object("other/keep.txt", b"outside the prefix");
+
+    let run = p.cf(&["r2", "sync", "dist", "site/", "--delete", "--dry-run"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["dry_run"], true);
+    assert_eq!(run.result()["summary"], json!({"upload": 2, "delete": 1, "remote_only": 0, "unchanged": 1}));
+    assert_eq!(plan_actions(&run), [
+        ("upload".to_string(), "site/img/logo.png".to_string()),
+        ("upload".to_string(), "site/index.html".to_string()),
+        ("delete".to_string(), "site/old.html".to_string()),
+    ]);
+    assert!(p.mock.requests_with("PUT").is_empty());
+    assert!(p.mock.requests_with("DELETE").is_empty());
+
+    // Without --delete, extra objects are reported and left alone
+    let run = p.cf(&["r2", "sync", "dist", "site/"]);
+    assert_eq!(run.result()["summary"]["remote_only"], 1);
+    assert!(p.mock.object("site/old.html").is_some());
+
+    let run = p.cf(&["r2", "sync", "dist", "site/", "--delete"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["deleted"], json!(["site/old.html"]));
+    assert_eq!(p.mock.object_keys(), ["other/keep.txt", "site/css/app.css", "site/img/logo.png", "site/index.html"]);
+}
+
+#[test]
+fn sync_reports_failed_deletes_as_failed() {
+    let p = Project::new();
+    site(&p);
+    p.cf(&["r2", "sync", "dist", "site"]);
+    p.mock.put_object("site/old.html", b"gone");
+    p.mock.protect_object("site/old.html");
+
+    let run = p.cf(&["r2", "sync", "dist", "site", "--delete"]);
+    assert_eq!(run.code, 2);
+    assert_eq!(run.error()["code"], "SYNC_FAILED");
+    assert_eq!(run.error()["details"]["failed"][0]["key"], "site/old.html");
+    assert_eq!(run.progress()[0]["message"], "failed site/old.html");
+}
+
+#[test]
+fn sync_delete_at_bucket_root_needs_confirmation() {
+    let p = Project::new();
+    site(&p);
+    p.mock.put_object("other/keep.txt", b"outside the site");
+
+    for prefix in ["", "/"] {
+        let run = p.cf(&["r2", "sync", "dist", prefix, "--delete"]);
+        assert_eq!(run.code, 1);
+        assert_eq!(run.error()["code"], "CONFIRMATION_REQUIRED");
+    }
+    assert!(p.mock.requests().is_empty());
+
+    let run = p.cf(&["r2", "sync", "dist", "", "--delete", "--allow-bucket-root"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["deleted"], json!(["other/keep.txt"]));
+}
+
+#[test]
+fn sync_compares_multipart_etags() {
+    let p = Project::new();
+    std::fs::create_dir_all(p.file("dumps")).unwrap();
+    let data: Vec<u8> = (0..11usize << 20).map(|i| (i % 251) as u8).collect();
+    std::fs::write(p.file("dumps/db.dump"), &data).unwrap();
+
+    let mut args = vec!["r2", "sync", "dumps", "backups"];
+    args.extend(MULTIPART);
+    let run = p.cf(&args);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(p.mock.object("backups/db.dump").unwrap(), data);
+
+    // The object's ETag is "<md5 of part md5s>-3"; recomputed with the same part size it matches
+    args.push("--dry-run");
+    let run = p.cf(&args);
+    assert_eq!(run.result()["summary"]["unchanged"], 1);
+    assert_eq!(run.result()["plan"], json!([]));
+}
+
+#[test]
+fn sync_requires_an_existing_directory() {
+    let p = Project::new();
+    let run = p.cf(&["r2", "sync", "missing", "site/"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "DIR_NOT_FOUND");
+    assert!(p.mock.requests().is_empty());
+}
+
+#[test]
+fn presign_signs_get_and_put_urls_offline() {
+    let p = Project::new();
+
+    let run = p.cf(&["r2", "presign", "--key", "reports/q3 2024.pdf"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["method"], "GET");
+    assert_eq!(run.result()["expires_in"], 3600);
+    let url = run.result()["url"].as_str().unwrap().to_string();
+    assert!(url.starts_with(&format!("{}/{}/reports/q3%202024.pdf?", p.mock.s3_url(), BUCKET)), "{}", url);
+    assert!(url.contains("X-Amz-Expires=3600"), "{}", url);
+    assert!(url.contains("X-Amz-Signature="), "{}", url);
+    assert!(!url.contains("test-secret-key"), "{}", url);
+
+    let run = p.cf(&["r2", "presign", "--key", "incoming/build.tar.gz", "--method", "put", "--expires", "15m"]);
+    assert_eq!(run.result()["method"], "PUT");
+    assert!(run.result()["url"].as_str().unwrap().contains("X-Amz-Expires=900"));
+
+    // Signing needs no request to R2
+    assert!(p.mock.requests().is_empty());
+
+    let run = p.cf(&["r2", "presign", "--key", "a.txt", "--expires", "8d"]);
+    assert_eq!(run.code, 2);
+    assert!(run.stderr.contains("between 1 second and 7 days"), "{}", run.stderr);
+}
@@ -0,0 +1,123 @@
+//! End-to-end tests for `cf redirect`
+
+mod common;
+
+use common::{Project, DOMAIN};
+use serde_json::json;
+
+#[test]
+fn add_builds_single_redirect_rule() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&["redirect", "add", "--domain", DOMAIN, "--from", "/old", "--to", "https://example.test/new"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["from"], "example.test/old");
+    assert_eq!(run.result()["status"], 301);
+
+    let run = p.cf(&[
+        "redirect", "add", "--domain", DOMAIN, "--from", "https://go.example.test/docs",
+        "--to", "https://docs.example.net/", "--status", "302", "--preserve-query",
+    ]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+
+    let rules = p.mock.redirect_rules();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0]["action"], "redirect");
+    assert_eq!(rules[0]["expression"], r#"(http.host eq "example.test" and http.request.uri.path eq "/old")"#);
+    assert_eq!(rules[0]["description"], "example.test/old -> https://example.test/new");
+    assert_eq!(rules[1]["action_parameters"], json!({
+        "from_value": {"status_code": 302, "target_url": {"value": "https://docs.example.net/"}, "preserve_query_string": true}
+    }));
+
+    let run = p.cf(&["redirect", "add", "--domain", DOMAIN, "--from", "/old", "--to", "https://example.test/other"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "REDIRECT_EXISTS");
+}
+
+#[test]
+fn add_rejects_bad_input() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&["redirect", "add", "--domain", DOMAIN, "--from", "other.test/x", "--to", "https://example.test/"]);
+    assert_eq!(run.error()["code"], "INVALID_REDIRECT");
+
+    let run = p.cf(&["redirect", "add", "--domain", DOMAIN, "--from", "/x", "--to", "/relative"]);
+    assert_eq!(run.error()["code"], "INVALID_REDIRECT");
+
+    let run = p.cf(&["redirect", "add", "--domain", DOMAIN, "--from", "/x", "--to", "https://example.test/", "--status", "303"]);
+    assert!(run.stderr.contains("status must be 301, 302, 307 or 308"), "{}", run.stderr);
+    assert!(p.mock.requests().is_empty());
+}
+
+#[test]
+fn list_and_remove_by_from_or_id() {
+    let p = Project::with_zone();
+    p.cf(&["redirect", "add", "--domain", DOMAIN, "--from", "/a", "--to", "https://example.test/1"]);
+    p.cf(&["redirect", "add", "--domain", DOMAIN, "--from", "/b \"quoted\"", "--to", "https://example.test/2"]);
+
+    let run = p.cf(&["redirect", "list", "--domain", DOMAIN]);
+    assert_eq!(run.result()["count"], 2);
+    assert_eq!(run.result()["redirects"][1]["from"], "example.test/b \"quoted\"");
+    assert_eq!(run.result()["redirects"][1]["to"], "https://example.test/2");
+    let id = run.result()["redirects"][0]["id"].as_str().unwrap().to_string();
+
+    let run = p.cf(&["redirect", "remove", "--domain", DOMAIN, "--from", "/b \"quoted\""]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let run = p.cf(&["redirect", "remove", "--domain", DOMAIN, "--id", &id]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert!(p.mock.redirect_rules().is_empty());
+
+    let run = p.cf(&["redirect", "remove", "--domain", DOMAIN, "--from", "/a"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "REDIRECT_NOT_FOUND");
+}
+
+#[test]
+fn import_syncs_from_csv() {
+    let p = Project::with_zone();
+    p.cf(&["redirect", "add", "--domain", DOMAIN, "--from", "/keep", "--to", "https://example.test/kept"]);
+    p.cf(&["redirect", "add", "--domain", DOMAIN, "--from", "/move", "--to", "https://example.test/before"]);
+    p.cf(&["redirect", "add", "--domain", DOMAIN, "--from", "/stale", "--to", "https://example.test/gone"]);
+    let move_id = p.mock.redirect_rules()[1]["id"].clone();
+
+    std::fs::write(p.file("redirects.csv"), "\
+from,to,status,preserve_query
+/keep,https://example.test/kept,,
+/move,https://example.test/after,308,true
+blog.example.test/feed,\"https://example.test/rss.xml\",302,
+").unwrap();
+
+    let run = p.cf(&["redirect", "import", "--domain", DOMAIN, "--file", "redirects.csv", "--dry-run", "--prune"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["summary"], json!({"create": 1, "update": 1, "delete": 1, "unchanged": 1}));
+    assert_eq!(p.mock.redirect_rules().len(), 3);
+
+    // Without --prune the unlisted rule stays
+    let run = p.cf(&["redirect", "import", "--domain", DOMAIN, "--file", "redirects.csv"]);
+    assert_eq!(run.result()["summary"], json!({"create": 1, "update": 1, "delete": 0, "unchanged": 1}));
+    assert_eq!(p.mock.redirect_rules().len(), 4);
+
+    let run = p.cf(&["redirect", "import", "--domain", DOMAIN, "--file", "redirects.csv", "--prune"]);
+    assert_eq!(run.result()["summary"], json!({"create": 0, "update": 0, "delete": 1, "unchanged": 3}));
+
+    let rules = p.mock.redirect_rules();
+    assert_eq!(rules.len(), 3);
+    assert_eq!(rules[1]["id"], move_id);
+    assert_eq!(rules[1]["action_parameters"]["from_value"]["status_code"], 308);
+    assert_eq!(rules[2]["expression"], r#"(http.host eq "blog.example.test" and http.request.uri.path eq "/feed")"#);
+
+    let run = p.cf(&["redirect", "import", "--domain", DOMAIN, "--file", "redirects.csv", "--prune"]);
+    assert_eq!(run.result()["in_sync"], true);
+}
+
+#[test]
+fn import_reports_bad_rows() {
+    let p = Project::with_zone();
+    std::fs::write(p.file("redirects.csv"), "from,to\n/a,https://example.test/\n/b,not a url\n").unwrap();
+
+    let run = p.cf(&["redirect", "import", "--domain", DOMAIN, "--file", "redirects.csv"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "INVALID_REDIRECTS");
+    assert!(run.error()["message"].as_str().unwrap().starts_with("row 3:"));
+    assert!(p.mock.requests().is_empty());
+}
@@ -0,0 +1,130 @@
+//! End-to-end tests for `cf tunnel`
+
+mod common;
+
+use common::{Project, DOMAIN};
+use serde_json::json;
+
+/// Create a tunnel named `name` and return its ID
+fn create(p: &Project, name: &str) -> String {
+    let run = p.cf(&["tunnel", "create", "--domain", DOMAIN, "--name", name]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    run.result()["id"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn create_prints_connector_token() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&["tunnel", "create", "--domain", DOMAIN, "--name", "homelab"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let id = run.result()["id"].as_str().unwrap();
+    assert_eq!(run.result()["token"], format!("token-{}", id));
+    assert_eq!(run.result()["run_command"], format!("cloudflared tunnel run --token token-{}", id));
+
+    let body = p.mock.requests_with("POST")[0].body.clone().unwrap();
+    assert_eq!(body, json!({"name": "homelab", "config_src": "cloudflare"}));
+
+    let run = p.cf(&["tunnel", "create", "--domain", DOMAIN, "--name", "homelab"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "TUNNEL_EXISTS");
+}
+
+#[test]
+fn route_adds_ingress_cname_and_registry_entry() {
+    let p = Project::with_zone();
+    p.write_registry(json!([]));
+    let id = create(&p, "homelab");
+
+    let run = p.cf(&[
+        "tunnel", "route", "--domain", DOMAIN,
+        "--tunnel", "homelab", "--name", "grafana", "--service", "http://localhost:3000",
+    ]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["dns"]["action"], "created");
+    assert_eq!(run.result()["registry"], true);
+
+    assert_eq!(p.mock.ingress(&id), [
+        json!({"hostname": "grafana.example.test", "service": "http://localhost:3000"}),
+        json!({"service": "http_status:404"}),
+    ]);
+
+    let records = p.mock.records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["type"], "CNAME");
+    assert_eq!(records[0]["content"], format!("{}.cfargotunnel.com", id));
+    assert_eq!(records[0]["proxied"], true);
+
+    assert_eq!(p.read_registry()["tunnels"]["grafana.example.test"], id);
+
+    let run = p.cf(&["tunnel", "list", "--domain", DOMAIN]);
+    assert_eq!(run.result()["tunnels"][0]["routes"], json!(["grafana.example.test"]));
+}
+
+#[test]
+fn route_is_idempotent_and_updates_service() {
+    let p = Project::with_zone();
+    let id = create(&p, "homelab");
+    let args = |service: &'static str| {
+        ["tunnel", "route", "--domain", DOMAIN, "--tunnel", "homelab", "--name", "app", "--service", service]
+    };
+
+    assert_eq!(p.cf(&args("http://localhost:8080")).code, 0);
+    let run = p.cf(&args("http://localhost:9090"));
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["dns"]["action"], "unchanged");
+
+    assert_eq!(p.mock.ingress(&id), [
+        json!({"hostname": "app.example.test", "service": "http://localhost:9090"}),
+        json!({"service": "http_status:404"}),
+    ]);
+    assert_eq!(p.mock.records().len(), 1);
+}
+
+#[test]
+fn route_rejects_bad_service_and_unknown_tunnel() {
+    let p = Project::with_zone();
+    create(&p, "homelab");
+
+    let run = p.cf(&["tunnel", "route", "--domain", DOMAIN, "--tunnel", "homelab", "--name", "app", "--service", "localhost:3000"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "INVALID_SERVICE");
+
+    let run = p.cf(&["tunnel", "route", "--domain", DOMAIN, "--tunnel", "nope", "--name", "app", "--service", "http://localhost:3000"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "TUNNEL_NOT_FOUND");
+    assert!(p.mock.records().is_empty());
+}
+
+#[test]
+fn ingress_remove_drops_rule_record_and_registry_entry() {
+    let p = Project::with_zone();
+    p.write_registry(json!([]));
+    let id = create(&p, "homelab");
+    p.cf(&["tunnel", "route", "--domain", DOMAIN, "--tunnel", "homelab", "--name", "grafana", "--service", "http://localhost:3000"]);
+
+    let run = p.cf(&["tunnel", "ingress", "--domain", DOMAIN, "--tunnel", &id, "--remove", "grafana"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["removed"], true);
+    assert_eq!(run.result()["dns_deleted"], true);
+
+    assert_eq!(p.mock.ingress(&id), [json!({"service": "http_status:404"})]);
+    assert!(p.mock.records().is_empty());
+    assert!(p.read_registry()["tunnels"].get("grafana.example.test").is_none());
+}
+
+#[test]
+fn dns_prune_keeps_tunnel_routes() {
+    let p = Project::with_zone();
+    p.write_registry(json!([{"type": "A", "name": "@", "content": "192.0.2.1"}]));
+    p.mock.seed("A", "@", "192.0.2.1");
+    create(&p, "homelab");
+    p.cf(&["tunnel", "route", "--domain", DOMAIN, "--tunnel", "homelab", "--name", "grafana", "--service", "http://localhost:3000"]);
+
+    let run = p.cf(&["dns", "plan", "--domain", DOMAIN]);
+    assert_eq!(run.result()["in_sync"], true);
+
+    let run = p.cf(&["dns", "apply", "--domain", DOMAIN, "--prune"]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(p.mock.records().len(), 2);
+}
@@ -0,0 +1,95 @@
+//! End-to-end tests for `cf zone settings` against the mock Cloudflare API
+
+mod common;
+
+use common::{Project, DOMAIN};
+use serde_json::json;
+
+#[test]
+fn get_shows_all_or_selected_settings() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&["zone", "settings", "get", "--domain", DOMAIN]);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["settings"]["ssl"], "flexible");
+    assert_eq!(run.result()["count"], 6);
+
+    let run = p.cf(&["zone", "settings", "get", "--domain", DOMAIN, "--setting", "min_tls_version"]);
+    assert_eq!(run.result()["settings"], json!({"min_tls_version": "1.0"}));
+
+    let run = p.cf(&["zone", "settings", "get", "--domain", DOMAIN, "--setting", "sll"]);
+    assert_eq!(run.code, 1);
+    assert_eq!(run.error()["code"], "UNKNOWN_SETTING");
+}
+
+#[test]
+fn set_updates_only_settings_that_differ() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&[
+        "zone", "settings", "set", "--domain", DOMAIN,
+        "--ssl", "strict", "--always-https", "on", "--min-tls", "1.2", "browser_cache_ttl=14400",
+    ]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["summary"], json!({"in_sync": 1, "drift": 3, "updated": 3, "failed": 0}));
+    assert_eq!(p.mock.setting("ssl"), "strict");
+    assert_eq!(p.mock.setting("min_tls_version"), "1.2");
+    assert_eq!(p.mock.requests_with("PATCH").len(), 3);
+}
+
+#[test]
+fn set_hsts_merges_into_security_header() {
+    let p = Project::with_zone();
+
+    let run = p.cf(&["zone", "settings", "set", "--domain", DOMAIN, "--hsts-max-age", "31536000", "--hsts-subdomains"]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    let hsts = &p.mock.setting("security_header")["strict_transport_security"];
+    assert_eq!(hsts["enabled"], true);
+    assert_eq!(hsts["max_age"], 31536000);
+    assert_eq!(hsts["include_subdomains"], true);
+    // Fields the command did not set are kept
+    assert_eq!(hsts["nosniff"], false);
+}
+
+#[test]
+fn apply_dry_run_reports_drift_without_changes() {
+    let p = Project::with_zone();
+    std::fs::write(p.file("baseline.json"), json!({
+        "ssl": "strict",
+        "min_tls_version": "1.0",
+        "security_header": {"strict_transport_security": {"enabled": true}}
+    }).to_string()).unwrap();
+
+    let run = p.cf(&["zone", "settings", "apply", "--domain", DOMAIN, "--profile", "baseline.json", "--dry-run"]);
+
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.result()["in_sync"], false);
+    assert_eq!(run.result()["summary"]["drift"], 2);
+    let statuses: Vec<_> = run.result()["settings"].as_array().unwrap().iter()
+        .map(|s| (s["setting"].as_str().unwrap().to_string(), s["status"].as_str().unwrap().to_string()))
+        .collect();
+    assert!(statuses.contains(&("ssl".into(), "drift".into())));
+    assert!(statuses.contains(&("min_tls_version".into(), "in_sync".into())));
+    assert!(p.mock.requests_with("PATCH").is_empty());
+
+    let run = p.cf(&["zone", "settings", "apply", "--domain", DOMAIN, "--profile", "baseline.json"]);
+    assert_eq!(run.result()["summary"]["updated"], 2);
+
+    let run = p.cf(&["zone", "settings", "apply", "--domain", DOMAIN, "--profile", "baseline.json", "--dry-run"]);
+    assert_eq!(run.result()["in_sync"], true);
+}
+
+#[test]
+fn apply_reports_locked_settings_as_failures() {
+    let p = Project::with_zone();
+    std::fs::write(p.file("profile.json"), r#"{"http3": "off", "ssl": "full"}"#).unwrap();
+
+    let run = p.cf(&["zone", "settings", "apply", "--domain", DOMAIN, "--profile", "profile.json"]);
+
+    assert_eq!(run.code, 2);
+    assert_eq!(run.error()["code"], "ZONE_SETTINGS_FAILED");
+    assert_eq!(run.error()["details"]["summary"]["updated"], 1);
+    assert_eq!(run.error()["details"]["summary"]["failed"], 1);
+}